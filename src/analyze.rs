@@ -0,0 +1,451 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value};
+
+/// How often a running process's resource usage is sampled with
+/// `--analyze`.
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(250);
+
+/// What a process and everything it started used, across all its runs.
+/// CPU time and I/O are only as recent as the last sample, so a run
+/// shorter than `SAMPLE_INTERVAL` may show none at all.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ResourceUsage {
+  /// The most memory in use at once, summed over the process tree.
+  pub peak_rss_bytes: u64,
+  pub user_secs: f64,
+  pub sys_secs: f64,
+  /// Bytes read from and written to storage.
+  pub read_bytes: u64,
+  pub write_bytes: u64,
+  /// How long the process ran, not counting the waits between restarts.
+  pub wall_secs: f64,
+}
+
+impl ResourceUsage {
+  /// CPU time over wall time. Above 100% with more than one core busy.
+  pub fn cpu_percent(&self) -> f64 {
+    if self.wall_secs > 0.0 {
+      (self.user_secs + self.sys_secs) / self.wall_secs * 100.0
+    } else {
+      0.0
+    }
+  }
+
+  /// Fold in the usage of another run of the same process.
+  pub fn add(&mut self, run: &ResourceUsage) {
+    self.peak_rss_bytes = self.peak_rss_bytes.max(run.peak_rss_bytes);
+    self.user_secs += run.user_secs;
+    self.sys_secs += run.sys_secs;
+    self.read_bytes += run.read_bytes;
+    self.write_bytes += run.write_bytes;
+    self.wall_secs += run.wall_secs;
+  }
+
+  pub fn to_json(&self) -> Value {
+    json!({
+      "wall_secs": self.wall_secs,
+      "user_secs": self.user_secs,
+      "sys_secs": self.sys_secs,
+      "cpu_percent": self.cpu_percent(),
+      "peak_rss_bytes": self.peak_rss_bytes,
+      "read_bytes": self.read_bytes,
+      "write_bytes": self.write_bytes,
+    })
+  }
+}
+
+/// The fields of `/proc/<pid>/stat` we use. Times are in clock ticks,
+/// memory in pages.
+#[derive(Debug, PartialEq)]
+struct Stat {
+  session: u32,
+  utime: u64,
+  stime: u64,
+  cutime: u64,
+  cstime: u64,
+  rss: u64,
+}
+
+/// Parse the contents of `/proc/<pid>/stat`. The command name in
+/// parentheses may itself hold spaces and parentheses, so fields are
+/// counted from the last `)`.
+fn parse_stat(text: &str) -> Option<Stat> {
+  let fields: Vec<&str> = text[text.rfind(')')? + 1..].split_whitespace().collect();
+  // `fields[0]` is field 3 of proc(5), the state.
+  let field = |n: usize| fields.get(n - 3)?.parse::<i64>().ok();
+  Some(Stat {
+    session: field(6)? as u32,
+    utime: field(14)? as u64,
+    stime: field(15)? as u64,
+    cutime: field(16)? as u64,
+    cstime: field(17)? as u64,
+    rss: field(24)?.max(0) as u64,
+  })
+}
+
+/// `read_bytes` and `write_bytes` from `/proc/<pid>/io`.
+fn parse_io(text: &str) -> (u64, u64) {
+  let value = |key: &str| {
+    text
+      .lines()
+      .find_map(|line| line.strip_prefix(key)?.trim().parse().ok())
+      .unwrap_or(0)
+  };
+  (value("read_bytes:"), value("write_bytes:"))
+}
+
+#[cfg(unix)]
+fn sysconf(name: libc::c_int, fallback: u64) -> u64 {
+  match unsafe { libc::sysconf(name) } {
+    value if value > 0 => value as u64,
+    _ => fallback,
+  }
+}
+
+#[cfg(unix)]
+fn clock_ticks_per_sec() -> u64 {
+  sysconf(libc::_SC_CLK_TCK, 100)
+}
+
+#[cfg(not(unix))]
+fn clock_ticks_per_sec() -> u64 {
+  100
+}
+
+#[cfg(unix)]
+fn page_size() -> u64 {
+  sysconf(libc::_SC_PAGESIZE, 4096)
+}
+
+#[cfg(not(unix))]
+fn page_size() -> u64 {
+  4096
+}
+
+/// Usage added up over one session, from a single scan of `/proc`. Times
+/// are in clock ticks, memory in pages.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Totals {
+  rss: u64,
+  utime: u64,
+  stime: u64,
+  read_bytes: u64,
+  write_bytes: u64,
+}
+
+impl Totals {
+  /// Keep the highest of each total. CPU time and I/O of a process that
+  /// exits only move to its parent once it's reaped, so the running total
+  /// can dip in between.
+  fn raise_to(&mut self, sample: &Totals) {
+    self.rss = self.rss.max(sample.rss);
+    self.utime = self.utime.max(sample.utime);
+    self.stime = self.stime.max(sample.stime);
+    self.read_bytes = self.read_bytes.max(sample.read_bytes);
+    self.write_bytes = self.write_bytes.max(sample.write_bytes);
+  }
+}
+
+/// Walk `/proc` once and add up the current usage of every process in
+/// each of `sessions`. Where there is no `/proc`, this comes up empty.
+fn scan(sessions: &HashSet<u32>) -> HashMap<u32, Totals> {
+  let mut totals: HashMap<u32, Totals> = HashMap::new();
+  let Ok(entries) = std::fs::read_dir("/proc") else {
+    return totals;
+  };
+  for entry in entries.flatten() {
+    let name = entry.file_name();
+    if !name.to_string_lossy().bytes().all(|b| b.is_ascii_digit()) {
+      continue;
+    }
+    let dir = entry.path();
+    let Some(stat) = read(&dir.join("stat")).as_deref().and_then(parse_stat) else {
+      continue;
+    };
+    if !sessions.contains(&stat.session) {
+      continue;
+    }
+    let total = totals.entry(stat.session).or_default();
+    total.rss += stat.rss;
+    total.utime += stat.utime + stat.cutime;
+    total.stime += stat.stime + stat.cstime;
+    // Not readable for processes that changed user, e.g. through sudo.
+    if let Some(io) = read(&dir.join("io")) {
+      let (read, written) = parse_io(&io);
+      total.read_bytes += read;
+      total.write_bytes += written;
+    }
+  }
+  totals
+}
+
+/// A run being sampled: when it started and the most it used so far.
+struct Run {
+  started: Instant,
+  peak: Totals,
+}
+
+/// Samples the resource usage of running children for `--analyze`. Each
+/// child leads its own session (see `Process::run`), so everything in that
+/// session is its process tree, including orphans that were reparented
+/// away from it. One background task scans `/proc` for all of them at
+/// once, on the blocking pool rather than a runtime worker.
+pub struct UsageMonitor {
+  runs: Mutex<HashMap<u32, Run>>,
+}
+
+impl UsageMonitor {
+  /// Start sampling every `SAMPLE_INTERVAL`, until the monitor is dropped.
+  pub fn start() -> Arc<Self> {
+    let monitor = Arc::new(Self {
+      runs: Mutex::new(HashMap::new()),
+    });
+    let weak = Arc::downgrade(&monitor);
+    tokio::spawn(async move {
+      let mut ticks = tokio::time::interval(SAMPLE_INTERVAL);
+      loop {
+        ticks.tick().await;
+        let Some(monitor) = weak.upgrade() else {
+          break;
+        };
+        let sessions: HashSet<u32> =
+          monitor.runs.lock().unwrap().keys().copied().collect();
+        if sessions.is_empty() {
+          continue;
+        }
+        let Ok(totals) = tokio::task::spawn_blocking(move || scan(&sessions)).await
+        else {
+          continue;
+        };
+        // Runs that finished during the scan are gone by now.
+        for (session, run) in monitor.runs.lock().unwrap().iter_mut() {
+          if let Some(sample) = totals.get(session) {
+            run.peak.raise_to(sample);
+          }
+        }
+      }
+    });
+    monitor
+  }
+
+  /// Start sampling a run of the child leading `session`.
+  pub fn watch(&self, session: u32) {
+    let run = Run {
+      started: Instant::now(),
+      peak: Totals::default(),
+    };
+    self.runs.lock().unwrap().insert(session, run);
+  }
+
+  /// Stop sampling the run in `session` and return what it used.
+  pub fn finish(&self, session: u32) -> ResourceUsage {
+    let Some(run) = self.runs.lock().unwrap().remove(&session) else {
+      return ResourceUsage::default();
+    };
+    let ticks = clock_ticks_per_sec() as f64;
+    ResourceUsage {
+      peak_rss_bytes: run.peak.rss * page_size(),
+      user_secs: run.peak.utime as f64 / ticks,
+      sys_secs: run.peak.stime as f64 / ticks,
+      read_bytes: run.peak.read_bytes,
+      write_bytes: run.peak.write_bytes,
+      wall_secs: run.started.elapsed().as_secs_f64(),
+    }
+  }
+}
+
+fn read(path: &Path) -> Option<String> {
+  std::fs::read_to_string(path).ok()
+}
+
+/// A byte count in the largest binary unit that keeps it at or above 1,
+/// e.g. `512 B` or `1.5 GiB`.
+pub fn format_bytes(bytes: u64) -> String {
+  const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+  if bytes < 1024 {
+    return format!("{} B", bytes);
+  }
+  let mut value = bytes as f64 / 1024.0;
+  let mut unit = 0;
+  while value >= 1024.0 && unit < UNITS.len() - 1 {
+    value /= 1024.0;
+    unit += 1;
+  }
+  format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// The lines of the `--analyze` table: a header, then one row per process
+/// with its label, the biggest memory user first.
+pub fn usage_table(rows: &[(String, &ResourceUsage)]) -> Vec<String> {
+  let mut rows: Vec<&(String, &ResourceUsage)> = rows.iter().collect();
+  rows.sort_by(|a, b| b.1.peak_rss_bytes.cmp(&a.1.peak_rss_bytes));
+  let header = [
+    "process", "wall", "cpu", "user", "sys", "peak mem", "read", "written",
+  ];
+  let mut cells: Vec<[String; 8]> = vec![header.map(str::to_string)];
+  for (label, usage) in rows {
+    cells.push([
+      label.clone(),
+      format!("{:.2}s", usage.wall_secs),
+      format!("{:.0}%", usage.cpu_percent()),
+      format!("{:.2}s", usage.user_secs),
+      format!("{:.2}s", usage.sys_secs),
+      format_bytes(usage.peak_rss_bytes),
+      format_bytes(usage.read_bytes),
+      format_bytes(usage.write_bytes),
+    ]);
+  }
+  let mut widths = [0; 8];
+  for row in &cells {
+    for (width, cell) in widths.iter_mut().zip(row) {
+      *width = (*width).max(cell.chars().count());
+    }
+  }
+  cells
+    .iter()
+    .map(|row| {
+      // The label is left-aligned, the numbers right-aligned.
+      let mut line = format!("  {:<width$}", row[0], width = widths[0]);
+      for (cell, width) in row.iter().zip(widths).skip(1) {
+        line.push_str(&format!("  {:>width$}", cell, width = width));
+      }
+      line
+    })
+    .collect()
+}
+
+/// Write the `--analyze-report` JSON to `path`.
+pub fn write_analyze_report(path: &Path, report: &Value) -> Result<(), String> {
+  let text = serde_json::to_string_pretty(report).expect("JSON values serialize");
+  std::fs::write(path, text + "\n")
+    .map_err(|e| format!("Could not write analyze report {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parse_stat_counts_fields_after_the_command_name() {
+    let text = "4242 (my (odd) cmd) S 1 4242 4242 0 -1 4194560 120 0 0 0 \
+                37 12 5 3 20 0 1 0 1000 11111111 2560 18446744073709551615";
+    assert_eq!(
+      parse_stat(text),
+      Some(Stat {
+        session: 4242,
+        utime: 37,
+        stime: 12,
+        cutime: 5,
+        cstime: 3,
+        rss: 2560,
+      })
+    );
+    assert_eq!(parse_stat("4242 (cut short) S 1 2"), None);
+    assert_eq!(parse_stat(""), None);
+  }
+
+  #[test]
+  fn parse_io_reads_storage_bytes() {
+    let text = "rchar: 100\nwchar: 200\nsyscr: 3\nsyscw: 4\n\
+                read_bytes: 4096\nwrite_bytes: 8192\ncancelled_write_bytes: 0\n";
+    assert_eq!(parse_io(text), (4096, 8192));
+    assert_eq!(parse_io(""), (0, 0));
+  }
+
+  #[test]
+  fn scans_the_current_process_tree() {
+    // The test runner isn't a session leader, so look up its session.
+    let stat = std::fs::read_to_string("/proc/self/stat");
+    let Some(stat) = stat.ok().as_deref().and_then(parse_stat) else {
+      return; // No /proc here.
+    };
+    let totals = scan(&HashSet::from([stat.session]));
+    assert!(totals[&stat.session].rss > 0, "{:?}", totals);
+    assert!(scan(&HashSet::new()).is_empty());
+  }
+
+  #[test]
+  fn peaks_never_go_down() {
+    let mut peak = Totals {
+      rss: 10,
+      utime: 5,
+      ..Default::default()
+    };
+    peak.raise_to(&Totals {
+      rss: 4,
+      utime: 7,
+      write_bytes: 3,
+      ..Default::default()
+    });
+    assert_eq!(
+      peak,
+      Totals {
+        rss: 10,
+        utime: 7,
+        write_bytes: 3,
+        ..Default::default()
+      }
+    );
+  }
+
+  #[test]
+  fn runs_add_up() {
+    let mut total = ResourceUsage::default();
+    let run = |rss, user_secs, wall_secs| ResourceUsage {
+      peak_rss_bytes: rss,
+      user_secs,
+      sys_secs: 0.5,
+      read_bytes: 10,
+      write_bytes: 20,
+      wall_secs,
+    };
+    total.add(&run(300, 1.0, 2.0));
+    total.add(&run(100, 2.0, 2.0));
+    assert_eq!(total.peak_rss_bytes, 300);
+    assert_eq!(total.user_secs, 3.0);
+    assert_eq!(total.write_bytes, 40);
+    assert_eq!(total.cpu_percent(), 100.0);
+    assert_eq!(ResourceUsage::default().cpu_percent(), 0.0);
+  }
+
+  #[test]
+  fn formats_bytes() {
+    assert_eq!(format_bytes(0), "0 B");
+    assert_eq!(format_bytes(1023), "1023 B");
+    assert_eq!(format_bytes(1536), "1.5 KiB");
+    assert_eq!(format_bytes(3 * 1024 * 1024 * 1024), "3.0 GiB");
+  }
+
+  #[test]
+  fn table_lists_the_biggest_memory_user_first() {
+    let small = ResourceUsage {
+      peak_rss_bytes: 2048,
+      user_secs: 0.25,
+      wall_secs: 1.0,
+      ..Default::default()
+    };
+    let big = ResourceUsage {
+      peak_rss_bytes: 5 * 1024 * 1024,
+      user_secs: 3.0,
+      sys_secs: 1.0,
+      wall_secs: 2.0,
+      ..Default::default()
+    };
+    let lines = usage_table(&[
+      ("[0] lint".to_string(), &small),
+      ("[1] build".to_string(), &big),
+    ]);
+    assert_eq!(
+      lines,
+      vec![
+        "  process     wall   cpu   user    sys  peak mem  read  written",
+        "  [1] build  2.00s  200%  3.00s  1.00s   5.0 MiB   0 B      0 B",
+        "  [0] lint   1.00s   25%  0.25s  0.00s   2.0 KiB   0 B      0 B",
+      ]
+    );
+  }
+}
@@ -0,0 +1,271 @@
+use owo_colors::Style;
+
+/// Named colors accepted wherever a process color can be configured, as the
+/// RGB values used for the prefix.
+const NAMED_COLORS: &[(&str, (u8, u8, u8))] = &[
+  ("black", (0, 0, 0)),
+  ("red", (205, 49, 49)),
+  ("green", (13, 188, 121)),
+  ("yellow", (229, 229, 16)),
+  ("blue", (36, 114, 200)),
+  ("magenta", (188, 63, 188)),
+  ("cyan", (17, 168, 205)),
+  ("white", (229, 229, 229)),
+  ("gray", (118, 118, 118)),
+  ("grey", (118, 118, 118)),
+  ("blackbright", (102, 102, 102)),
+  ("redbright", (241, 76, 76)),
+  ("greenbright", (35, 209, 139)),
+  ("yellowbright", (245, 245, 67)),
+  ("bluebright", (59, 142, 234)),
+  ("magentabright", (214, 112, 214)),
+  ("cyanbright", (41, 184, 219)),
+  ("whitebright", (255, 255, 255)),
+];
+
+/// Colors handed out by process index for `auto`, chosen to stay readable
+/// and distinct from their neighbours on dark and light terminals.
+const AUTO_PALETTE: &[(u8, u8, u8)] = &[
+  (17, 168, 205),  // cyan
+  (229, 229, 16),  // yellow
+  (188, 63, 188),  // magenta
+  (13, 188, 121),  // green
+  (59, 142, 234),  // bright blue
+  (241, 76, 76),   // bright red
+  (41, 184, 219),  // bright cyan
+  (214, 112, 214), // bright magenta
+];
+
+/// How a process prefix is drawn, as given to `--prefix-colors` or `color`
+/// in `mlti.toml`: a foreground color, an optional `bg` background and any
+/// number of modifiers, joined with `.` (`red.bold`, `bgBlue.white`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PrefixColor {
+  /// `None` keeps the terminal's own color unless `auto` is set.
+  pub fg: Option<(u8, u8, u8)>,
+  pub bg: Option<(u8, u8, u8)>,
+  /// Take the foreground from the palette; see `resolve`.
+  pub auto: bool,
+  pub bold: bool,
+  pub dim: bool,
+  pub italic: bool,
+  pub underline: bool,
+  pub inverse: bool,
+  pub strikethrough: bool,
+}
+
+impl PrefixColor {
+  pub const AUTO: PrefixColor = PrefixColor {
+    fg: None,
+    bg: None,
+    auto: true,
+    bold: false,
+    dim: false,
+    italic: false,
+    underline: false,
+    inverse: false,
+    strikethrough: false,
+  };
+
+  pub fn rgb(color: (u8, u8, u8)) -> Self {
+    Self {
+      fg: Some(color),
+      ..Self::default()
+    }
+  }
+
+  /// Parse a single color such as `cyan`, `#23de43`, `bgRed.white.bold` or
+  /// `auto`.
+  pub fn parse(s: &str) -> Result<Self, String> {
+    let mut color = Self::default();
+    let parts = s.split('.').map(str::trim).filter(|p| !p.is_empty());
+    let mut empty = true;
+    for part in parts {
+      empty = false;
+      match part.to_lowercase().as_str() {
+        "auto" => color.auto = true,
+        "bold" => color.bold = true,
+        "dim" => color.dim = true,
+        "italic" => color.italic = true,
+        "underline" => color.underline = true,
+        "inverse" => color.inverse = true,
+        "strikethrough" => color.strikethrough = true,
+        _ => match part.strip_prefix("bg") {
+          // `bgRed`, `bg#ff0000`; a plain name can't start with `bg` then
+          // an uppercase letter.
+          Some(bg)
+            if bg.starts_with(|c: char| c.is_ascii_uppercase() || c == '#') =>
+          {
+            color.bg = Some(parse_color(bg)?)
+          }
+          _ => color.fg = Some(parse_color(part)?),
+        },
+      }
+    }
+    if empty {
+      return Err("Invalid color: ''".to_string());
+    }
+    Ok(color)
+  }
+
+  /// Fix an `auto` color to the palette entry for process `index`.
+  pub fn resolve(mut self, index: usize) -> Self {
+    if self.auto {
+      self.fg = Some(AUTO_PALETTE[index % AUTO_PALETTE.len()]);
+      self.auto = false;
+    }
+    self
+  }
+
+  pub fn style(&self) -> Style {
+    let mut style = Style::new();
+    if let Some((r, g, b)) = self.fg {
+      style = style.truecolor(r, g, b);
+    }
+    if let Some((r, g, b)) = self.bg {
+      style = style.on_truecolor(r, g, b);
+    }
+    if self.bold {
+      style = style.bold();
+    }
+    if self.dim {
+      style = style.dimmed();
+    }
+    if self.italic {
+      style = style.italic();
+    }
+    if self.underline {
+      style = style.underline();
+    }
+    if self.inverse {
+      style = style.reversed();
+    }
+    if self.strikethrough {
+      style = style.strikethrough();
+    }
+    style
+  }
+}
+
+/// Parse a `--prefix-colors` list: comma-separated colors, one per process
+/// in order.
+pub fn parse_prefix_colors(spec: &str) -> Result<Vec<PrefixColor>, String> {
+  spec
+    .split(',')
+    .map(|color| {
+      PrefixColor::parse(color).map_err(|e| format!("--prefix-colors: {}", e))
+    })
+    .collect()
+}
+
+/// The color for process `index`: its entry in `colors`, the last entry
+/// when there are more processes than colors, or `auto` when none were
+/// given.
+pub fn color_for(colors: &[PrefixColor], index: usize) -> PrefixColor {
+  colors
+    .get(index)
+    .or(colors.last())
+    .copied()
+    .unwrap_or(PrefixColor::AUTO)
+    .resolve(index)
+}
+
+/// Parse a color name (`red`, `cyan`, ...) or hex code (`#23de43`, `#fa0`)
+/// into RGB.
+pub fn parse_color(s: &str) -> Result<(u8, u8, u8), String> {
+  let s = s.trim();
+  if let Some(hex) = s.strip_prefix('#') {
+    return parse_hex(hex).ok_or_else(|| format!("Invalid hex color: '{}'", s));
+  }
+  let lower = s.to_lowercase();
+  NAMED_COLORS
+    .iter()
+    .find(|(name, _)| *name == lower)
+    .map(|(_, rgb)| *rgb)
+    .ok_or_else(|| {
+      let names: Vec<&str> = NAMED_COLORS.iter().map(|(n, _)| *n).collect();
+      format!(
+        "Invalid color: '{}'. Expected a hex code like #23de43 or one of: {}",
+        s,
+        names.join(", ")
+      )
+    })
+}
+
+fn parse_hex(hex: &str) -> Option<(u8, u8, u8)> {
+  if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+    return None;
+  }
+  let channel = |i: usize, len: usize| {
+    let value = u8::from_str_radix(&hex[i * len..(i + 1) * len], 16).ok()?;
+    // `#fa0` is shorthand for `#ffaa00`.
+    Some(if len == 1 { value * 17 } else { value })
+  };
+  let len = match hex.len() {
+    3 => 1,
+    6 => 2,
+    _ => return None,
+  };
+  Some((channel(0, len)?, channel(1, len)?, channel(2, len)?))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parse_named_colors_case_insensitively() {
+    assert_eq!(parse_color("red").unwrap(), (205, 49, 49));
+    assert_eq!(parse_color("Cyan").unwrap(), parse_color("cyan").unwrap());
+    assert_eq!(parse_color("grey").unwrap(), parse_color("gray").unwrap());
+  }
+
+  #[test]
+  fn parse_hex_colors() {
+    assert_eq!(parse_color("#23de43").unwrap(), (0x23, 0xde, 0x43));
+    assert_eq!(parse_color("#FA0").unwrap(), (0xff, 0xaa, 0x00));
+  }
+
+  #[test]
+  fn parse_rejects_unknown_colors() {
+    assert!(parse_color("mauve").is_err());
+    assert!(parse_color("#12345").is_err());
+    assert!(parse_color("#gg0000").is_err());
+    assert!(parse_color("").is_err());
+  }
+
+  #[test]
+  fn parse_prefix_color_parts() {
+    assert_eq!(
+      PrefixColor::parse("redBright").unwrap(),
+      PrefixColor::rgb((241, 76, 76))
+    );
+    let color = PrefixColor::parse("bgBlue.white.bold.dim").unwrap();
+    assert_eq!(color.fg, Some((229, 229, 229)));
+    assert_eq!(color.bg, Some((36, 114, 200)));
+    assert!(color.bold && color.dim && !color.italic);
+    assert_eq!(
+      PrefixColor::parse("bg#fa0").unwrap().bg,
+      Some((0xff, 0xaa, 0x00))
+    );
+    assert!(PrefixColor::parse("auto.underline").unwrap().auto);
+    assert!(PrefixColor::parse("red.blink").is_err());
+    assert!(PrefixColor::parse("bgMauve").is_err());
+    assert!(PrefixColor::parse(" ").is_err());
+  }
+
+  #[test]
+  fn colors_repeat_the_last_entry_and_auto_is_stable() {
+    let colors = parse_prefix_colors("red, auto.bold").unwrap();
+    assert_eq!(color_for(&colors, 0), PrefixColor::rgb((205, 49, 49)));
+    let second = color_for(&colors, 1);
+    assert_eq!(second.fg, Some(AUTO_PALETTE[1]));
+    assert!(second.bold && !second.auto);
+    assert_eq!(color_for(&colors, 2).fg, Some(AUTO_PALETTE[2]));
+
+    assert_eq!(color_for(&[], 3), color_for(&[], 3));
+    assert_ne!(color_for(&[], 0), color_for(&[], 1));
+    assert_eq!(color_for(&[], 0), color_for(&[], AUTO_PALETTE.len()));
+    assert!(parse_prefix_colors("red,,blue").is_err());
+  }
+}
@@ -1,14 +1,24 @@
-use std::process::{self, Stdio};
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::OnceLock;
 
+use regex::{Captures, Regex};
 use tokio::process::Child;
 
+use crate::color::PrefixColor;
+
 pub(crate) struct Process {
   pub name: String,
   pub args: Vec<String>,
   pub cmd: String,
   pub raw_cmd: String,
   pub index: usize,
-  pub color: (u8, u8, u8),
+  pub color: PrefixColor,
+  /// Working directory for the child; mlti's own when `None`.
+  pub cwd: Option<PathBuf>,
+  /// Extra environment variables for the child, on top of mlti's own.
+  /// Starts out with `MLTI_INDEX` and `MLTI_NAME`; later entries win.
+  pub env: Vec<(String, String)>,
 }
 
 impl Process {
@@ -16,25 +26,41 @@ impl Process {
     raw_cmd: String,
     name: Option<String>,
     index: usize,
-    prefix: Option<String>,
-    length: i16,
-    color: (u8, u8, u8),
-    timestamp_format: String,
+    color: PrefixColor,
+    shell: Option<&[String]>,
   ) -> Self {
     // Commands are already expanded by command_expander before reaching here.
-    let mut args = raw_cmd.split_whitespace();
-    let cmd_string = args.next().unwrap_or("");
-    let args = args.map(|x| x.to_string()).collect::<Vec<String>>();
+    // With a shell the whole string is handed over as a single argument so
+    // quotes, pipes and `&&` behave exactly as they would in a terminal.
+    // Without one, the string is tokenized ourselves; `CommandParser`
+    // has already rejected anything that doesn't tokenize.
+    let mut argv = match shell {
+      Some(shell) => {
+        let mut argv = shell.to_vec();
+        argv.push(raw_cmd.clone());
+        argv
+      }
+      None => split_command(&raw_cmd).expect("command was validated on parse"),
+    }
+    .into_iter();
+    let cmd_string = argv.next().unwrap_or_default();
+    let args = argv.collect::<Vec<String>>();
 
-    let name = get_name(&raw_cmd, name, index, prefix, length, timestamp_format);
+    let name = get_name(name, index);
+    let env = vec![
+      ("MLTI_INDEX".to_string(), index.to_string()),
+      ("MLTI_NAME".to_string(), name.clone()),
+    ];
 
     Self {
       color,
       index,
       name,
       args,
-      cmd: cmd_string.to_string(),
+      cmd: cmd_string,
       raw_cmd: raw_cmd.clone(),
+      cwd: None,
+      env,
     }
   }
   pub fn run(&self, handle_input: bool) -> Result<Child, std::io::Error> {
@@ -45,93 +71,273 @@ impl Process {
       cmd.stdin(Stdio::piped());
     }
     cmd.args(self.args.clone());
+    if let Some(cwd) = &self.cwd {
+      cmd.current_dir(cwd);
+    }
+    cmd.envs(self.env.iter().map(|(k, v)| (k, v)));
+    // Start each child in its own session so it leads a fresh process
+    // group. Anything it spawns (node, esbuild, ...) joins that group and
+    // can be signalled together, and a terminal Ctrl-C only reaches mlti,
+    // which then shuts the groups down itself.
+    #[cfg(unix)]
+    unsafe {
+      cmd.pre_exec(|| {
+        if libc::setsid() == -1 {
+          return Err(std::io::Error::last_os_error());
+        }
+        Ok(())
+      });
+    }
 
     cmd.spawn()
   }
 }
 
-fn replace_prefix(prefix: String, key: String, value: String) -> String {
-  if prefix == key {
-    value
+/// The shell used to run commands when `--shell` isn't given.
+pub fn default_shell() -> String {
+  if cfg!(windows) {
+    "cmd /C".to_string()
   } else {
-    let format_str = format!("{{{}}}", key);
-    prefix.replace(&format_str, &value)
+    "sh -c".to_string()
   }
 }
 
-fn get_name(
-  raw_cmd: &str,
-  name: Option<String>,
-  index: usize,
-  prefix: Option<String>,
-  length: i16,
-  timestamp_format: String,
-) -> String {
-  // if Prefix template parse it
-
-  if let Some(prefix) = prefix {
-    let mut prefix = prefix;
-    let replace_list = vec![
-      ("index", index.to_string()),
-      ("command", raw_cmd.to_string()),
-      ("name", (&raw_cmd).to_string()),
-      ("pid", process::id().to_string()),
-      (
-        "time",
-        chrono::Local::now().format(&timestamp_format).to_string(),
-      ),
-      ("none", "".to_string()),
-    ];
+/// Split a command string into argv following POSIX shell quoting rules.
+///
+/// Whitespace separates words; single quotes preserve everything up to the
+/// closing quote; double quotes preserve everything except `\` escapes of
+/// `$`, `` ` ``, `"`, `\` and newline; an unquoted backslash escapes the
+/// next character. No expansion of any kind is performed.
+pub fn split_command(input: &str) -> Result<Vec<String>, String> {
+  let mut words = Vec::new();
+  let mut word = String::new();
+  // Tracks whether we're inside a word so `''` yields an empty argument.
+  let mut in_word = false;
+  let mut chars = input.chars();
 
-    for (key, value) in replace_list {
-      prefix = replace_prefix(prefix, key.to_string(), value);
+  while let Some(c) = chars.next() {
+    match c {
+      '\'' => {
+        in_word = true;
+        loop {
+          match chars.next() {
+            Some('\'') => break,
+            Some(c) => word.push(c),
+            None => return Err(format!("Unterminated single quote in: {}", input)),
+          }
+        }
+      }
+      '"' => {
+        in_word = true;
+        loop {
+          match chars.next() {
+            Some('"') => break,
+            Some('\\') => match chars.next() {
+              Some('\n') => {}
+              Some(c @ ('$' | '`' | '"' | '\\')) => word.push(c),
+              Some(c) => {
+                word.push('\\');
+                word.push(c);
+              }
+              None => {
+                return Err(format!("Unterminated double quote in: {}", input))
+              }
+            },
+            Some(c) => word.push(c),
+            None => return Err(format!("Unterminated double quote in: {}", input)),
+          }
+        }
+      }
+      '\\' => match chars.next() {
+        // Backslash-newline is a line continuation and vanishes entirely.
+        Some('\n') => {}
+        Some(c) => {
+          in_word = true;
+          word.push(c);
+        }
+        None => return Err(format!("Trailing backslash in: {}", input)),
+      },
+      c if c.is_whitespace() => {
+        if in_word {
+          words.push(std::mem::take(&mut word));
+          in_word = false;
+        }
+      }
+      c => {
+        in_word = true;
+        word.push(c);
+      }
     }
-
-    let prefix = truncate(&prefix, length.try_into().unwrap());
-    return prefix.to_string();
   }
+  if in_word {
+    words.push(word);
+  }
+  Ok(words)
+}
 
-  // if explicitly named, use that (includes auto-names from command_expander)
-
-  if let Some(name) = name {
-    return name;
+/// Quote `arg` so a POSIX shell (or `split_command`) reads it back as one
+/// word. Arguments made only of safe characters are left as they are.
+pub fn quote_argument(arg: &str) -> String {
+  let safe = |c: char| c.is_ascii_alphanumeric() || "-_./:=@%+,".contains(c);
+  if !arg.is_empty() && arg.chars().all(safe) {
+    return arg.to_string();
   }
+  format!("'{}'", arg.replace('\'', r"'\''"))
+}
 
-  // Fallback to index-based name for plain commands with no explicit name
-  format!("{}", index)
+/// Fill the `-P` placeholders in `command` from `args`: `{N}` is the Nth
+/// argument, `{@}` every argument quoted on its own and `{*}` all of them
+/// joined into one quoted argument. Placeholders past the last argument
+/// become empty, and a backslash in front of one keeps it as written.
+pub fn expand_arguments(command: &str, args: &[String]) -> String {
+  static PLACEHOLDER: OnceLock<Regex> = OnceLock::new();
+  let placeholder =
+    PLACEHOLDER.get_or_init(|| Regex::new(r"\\?\{([@*]|[1-9][0-9]*)\}").unwrap());
+  placeholder
+    .replace_all(command, |captures: &Captures| {
+      let matched = &captures[0];
+      if let Some(literal) = matched.strip_prefix('\\') {
+        return literal.to_string();
+      }
+      match &captures[1] {
+        "@" => args
+          .iter()
+          .map(|arg| quote_argument(arg))
+          .collect::<Vec<_>>()
+          .join(" "),
+        "*" if args.is_empty() => String::new(),
+        "*" => quote_argument(&args.join(" ")),
+        n => n
+          .parse::<usize>()
+          .ok()
+          .and_then(|n| args.get(n - 1))
+          .map(|arg| quote_argument(arg))
+          .unwrap_or_default(),
+      }
+    })
+    .into_owned()
 }
 
-fn truncate(s: &str, max_chars: usize) -> &str {
-  match s.char_indices().nth(max_chars) {
-    None => s,
-    Some((idx, _)) => &s[..idx],
-  }
+/// The name a process is known by: its explicit name (including auto-names
+/// from command_expander), else its index. How it's shown is up to the
+/// `--prefix` template (see `messenger::PrefixFormat`).
+fn get_name(name: Option<String>, index: usize) -> String {
+  name.unwrap_or_else(|| index.to_string())
 }
 
 #[cfg(test)]
 mod tests {
   use super::*;
 
+  fn split(s: &str) -> Vec<String> {
+    split_command(s).unwrap()
+  }
+
   #[test]
-  fn get_name_explicit_name_wins() {
+  fn split_command_plain_whitespace() {
+    assert_eq!(split("  npm   run\tbuild "), vec!["npm", "run", "build"]);
+    assert!(split("   ").is_empty());
+  }
+
+  #[test]
+  fn split_command_single_quotes_are_literal() {
     assert_eq!(
-      get_name(
-        "npm run test",
-        Some("my-name".to_string()),
-        0,
-        None,
-        20,
-        String::new()
-      ),
-      "my-name"
+      split(r#"echo 'hello  "world" \n'"#),
+      vec!["echo", r#"hello  "world" \n"#]
     );
   }
 
   #[test]
-  fn get_name_falls_back_to_index() {
+  fn split_command_double_quote_escapes() {
     assert_eq!(
-      get_name("echo hello", None, 3, None, 20, String::new()),
-      "3"
+      split(r#"echo "a \"b\" \$HOME \x""#),
+      vec!["echo", r#"a "b" $HOME \x"#]
     );
   }
+
+  #[test]
+  fn split_command_backslash_outside_quotes() {
+    assert_eq!(split(r"echo a\ b \'c"), vec!["echo", "a b", "'c"]);
+    assert_eq!(split("echo a\\\nb"), vec!["echo", "ab"]);
+  }
+
+  #[test]
+  fn split_command_adjacent_quotes_join_and_empty_args_survive() {
+    assert_eq!(split(r#"a'b'"c"d"#), vec!["abcd"]);
+    assert_eq!(split(r#"cmd '' """#), vec!["cmd", "", ""]);
+  }
+
+  #[test]
+  fn split_command_rejects_unterminated() {
+    assert!(split_command("echo 'oops").is_err());
+    assert!(split_command("echo \"oops").is_err());
+    assert!(split_command("echo oops\\").is_err());
+  }
+
+  #[test]
+  fn new_with_shell_passes_command_as_single_arg() {
+    let shell = vec!["sh".to_string(), "-c".to_string()];
+    let p = Process::new(
+      "sleep 1 && echo 'hi there'".to_string(),
+      None,
+      0,
+      PrefixColor::default(),
+      Some(&shell),
+    );
+    assert_eq!(p.cmd, "sh");
+    assert_eq!(p.args, vec!["-c", "sleep 1 && echo 'hi there'"]);
+  }
+
+  #[test]
+  fn new_without_shell_tokenizes_command() {
+    let p = Process::new(
+      "echo 'hi there' x".to_string(),
+      None,
+      0,
+      PrefixColor::default(),
+      None,
+    );
+    assert_eq!(p.cmd, "echo");
+    assert_eq!(p.args, vec!["hi there", "x"]);
+  }
+
+  #[test]
+  fn quote_argument_round_trips_through_split_command() {
+    assert_eq!(quote_argument("--port=4000"), "--port=4000");
+    assert_eq!(quote_argument("two words"), "'two words'");
+    assert_eq!(quote_argument(""), "''");
+    for arg in ["it's", "$HOME", "a\"b", "x; rm -rf /", ""] {
+      assert_eq!(split_command(&quote_argument(arg)).unwrap(), vec![arg]);
+    }
+  }
+
+  #[test]
+  fn expand_arguments_fills_each_placeholder() {
+    let args: Vec<String> = vec!["--port".into(), "4000".into(), "a b".into()];
+    assert_eq!(
+      expand_arguments("vite {@}", &args),
+      "vite --port 4000 'a b'"
+    );
+    assert_eq!(
+      expand_arguments("echo {*}", &args),
+      "echo '--port 4000 a b'"
+    );
+    assert_eq!(
+      expand_arguments("serve {2} {3} {4}", &args),
+      "serve 4000 'a b' "
+    );
+    assert_eq!(expand_arguments("echo \\{1} {0}", &args), "echo {1} {0}");
+    assert_eq!(expand_arguments("tsc -w", &args), "tsc -w");
+    assert_eq!(expand_arguments("vite {@} {*}", &[]), "vite  ");
+  }
+
+  #[test]
+  fn get_name_explicit_name_wins() {
+    assert_eq!(get_name(Some("my-name".to_string()), 0), "my-name");
+  }
+
+  #[test]
+  fn get_name_falls_back_to_index() {
+    assert_eq!(get_name(None, 3), "3");
+  }
 }
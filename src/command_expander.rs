@@ -153,6 +153,22 @@ pub fn expand_commands(
   names: Vec<String>,
   manifest_path: Option<String>,
 ) -> Result<(Vec<String>, Vec<Option<String>>)> {
+  let (processes, names, _) =
+    expand_commands_with_origins(processes, names, manifest_path)?;
+  Ok((processes, names))
+}
+
+/// Expanded commands, their names, and for each the index of the command
+/// it came from.
+pub type Expansion = (Vec<String>, Vec<Option<String>>, Vec<usize>);
+
+/// Like `expand_commands`, also returning for each expanded process the
+/// index of the command it came from.
+pub fn expand_commands_with_origins(
+  processes: Vec<String>,
+  names: Vec<String>,
+  manifest_path: Option<String>,
+) -> Result<Expansion> {
   // Pre-parse all patterns in a single pass to avoid re-parsing in the loop.
   let parsed: Vec<Option<WildcardPattern>> =
     processes.iter().map(|p| parse_wildcard(p)).collect();
@@ -167,9 +183,12 @@ pub fn expand_commands(
 
   let mut expanded_processes = Vec::new();
   let mut expanded_names: Vec<Option<String>> = Vec::new();
+  let mut origins = Vec::new();
   let mut name_idx = 0;
 
-  for (process, pattern) in processes.iter().zip(parsed.into_iter()) {
+  for (origin, (process, pattern)) in
+    processes.iter().zip(parsed.into_iter()).enumerate()
+  {
     if let Some(pattern) = pattern {
       // Validate exclusion content: only simple script-name characters allowed.
       // Rejects things like *(!fix|ts) which would produce a malformed glob.
@@ -208,11 +227,13 @@ pub fn expand_commands(
         };
         expanded_processes.push(m.command.clone());
         expanded_names.push(name);
+        origins.push(origin);
         name_idx += 1;
       }
     } else if let Some((expanded, auto_name)) = expand_shortcut(process) {
       // Non-wildcard shortcut (e.g., "npm:build" → "npm run build")
       expanded_processes.push(expanded);
+      origins.push(origin);
       let name = if name_idx < names.len() {
         Some(names[name_idx].clone())
       } else {
@@ -223,6 +244,7 @@ pub fn expand_commands(
     } else {
       // Plain command — pass through unchanged
       expanded_processes.push(process.clone());
+      origins.push(origin);
       let name = if name_idx < names.len() {
         Some(names[name_idx].clone())
       } else {
@@ -250,7 +272,7 @@ pub fn expand_commands(
     }
   }
 
-  Ok((expanded_processes, expanded_names))
+  Ok((expanded_processes, expanded_names, origins))
 }
 
 /// Read and parse the scripts field from a package.json manifest file.
@@ -346,6 +368,21 @@ mod tests {
     );
   }
 
+  #[test]
+  fn expand_reports_where_each_process_came_from() {
+    let dir = tempfile::tempdir().unwrap();
+    let manifest = write_test_manifest(&dir);
+    let processes = vec![
+      "npm:build:*".into(),
+      "npm:nothing:*".into(),
+      "node server.js".into(),
+    ];
+    let (procs, _, origins) =
+      expand_commands_with_origins(processes, vec![], Some(manifest)).unwrap();
+    assert_eq!(procs.len(), 3);
+    assert_eq!(origins, vec![0, 0, 2]);
+  }
+
   #[test]
   fn expand_explicit_names_override_auto() {
     let dir = tempfile::tempdir().unwrap();
@@ -0,0 +1,599 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Deserializer};
+
+use crate::color::PrefixColor;
+use crate::dotenv::load_env_file;
+use crate::input_router::resolve_target;
+use crate::timeout::parse_duration;
+
+/// Loaded from the working directory when neither `--config` nor
+/// `MLTI_CONFIG` is given.
+pub const DEFAULT_CONFIG_FILE: &str = "mlti.toml";
+
+/// Contents of an `mlti.toml`. Top-level keys mirror the CLI flags of the
+/// same name; every one is optional and only fills in for a flag that was
+/// left at its default (see `CommandParser::new`).
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+  pub kill_others: Option<bool>,
+  pub kill_others_on_fail: Option<bool>,
+  pub kill_signal: Option<String>,
+  pub kill_timeout: Option<u64>,
+  pub restart_tries: Option<i64>,
+  pub restart_always: Option<bool>,
+  pub restart_after: Option<i64>,
+  pub prefix: Option<String>,
+  pub prefix_length: Option<i16>,
+  pub prefix_colors: Option<String>,
+  pub stderr_marker: Option<String>,
+  pub stderr_color: Option<String>,
+  pub max_processes: Option<MaxProcesses>,
+  pub sequential: Option<bool>,
+  pub continue_on_error: Option<bool>,
+  pub raw: Option<bool>,
+  pub output: Option<String>,
+  pub tui: Option<bool>,
+  pub no_color: Option<bool>,
+  pub group: Option<bool>,
+  pub timestamp_format: Option<String>,
+  pub timestamps: Option<bool>,
+  pub pad_prefix: Option<bool>,
+  pub success: Option<String>,
+  pub timings: Option<bool>,
+  pub analyze: Option<bool>,
+  /// Relative paths are resolved against the config file's directory.
+  pub analyze_report: Option<PathBuf>,
+  pub passthrough_arguments: Option<bool>,
+  pub manifest_path: Option<String>,
+  pub handle_input: Option<bool>,
+  pub default_input_target: Option<String>,
+  pub shell: Option<String>,
+  pub no_shell: Option<bool>,
+  pub ready_timeout: Option<u64>,
+  /// How long any process may run, like a bare `--timeout`.
+  pub timeout: Option<String>,
+  /// Dotenv files loaded into every process. Relative paths are resolved
+  /// against the config file's directory.
+  #[serde(default, deserialize_with = "one_or_many")]
+  pub env_file: Vec<PathBuf>,
+  /// Directory each process's output is logged to. Relative paths are
+  /// resolved against the config file's directory.
+  pub log_dir: Option<PathBuf>,
+  /// Relative paths are resolved against the config file's directory.
+  pub junit_report: Option<PathBuf>,
+  /// Relative paths are resolved against the config file's directory.
+  pub control_socket: Option<PathBuf>,
+  pub show_output_on_failure: Option<bool>,
+  pub tail_lines: Option<usize>,
+  #[serde(default)]
+  pub processes: Vec<ProcessConfig>,
+}
+
+/// `max-processes` may be written as a count or, like on the command line,
+/// as a percentage string such as `"50%"`.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum MaxProcesses {
+  Count(i64),
+  Spec(String),
+}
+
+/// Accept either a single value or a list of them.
+fn one_or_many<'de, D: Deserializer<'de>, T: Deserialize<'de>>(
+  deserializer: D,
+) -> std::result::Result<Vec<T>, D::Error> {
+  #[derive(Deserialize)]
+  #[serde(untagged)]
+  enum OneOrMany<T> {
+    One(T),
+    Many(Vec<T>),
+  }
+  Ok(match OneOrMany::deserialize(deserializer)? {
+    OneOrMany::One(value) => vec![value],
+    OneOrMany::Many(values) => values,
+  })
+}
+
+impl MaxProcesses {
+  pub fn to_spec(&self) -> String {
+    match self {
+      MaxProcesses::Count(n) => n.to_string(),
+      MaxProcesses::Spec(s) => s.clone(),
+    }
+  }
+}
+
+/// One `[[processes]]` entry.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ProcessConfig {
+  pub name: String,
+  pub command: String,
+  /// Relative paths are resolved against the config file's directory.
+  pub cwd: Option<PathBuf>,
+  #[serde(default)]
+  pub env: BTreeMap<String, String>,
+  /// Dotenv files for this process, loaded after the global ones.
+  #[serde(default, deserialize_with = "one_or_many")]
+  pub env_file: Vec<PathBuf>,
+  /// Log file for this process, instead of `<name>.log` in `log-dir`.
+  pub log_file: Option<PathBuf>,
+  pub color: Option<String>,
+  pub restart_tries: Option<i64>,
+  pub restart_after: Option<i64>,
+  pub restart_always: Option<bool>,
+  /// How long the process may run, e.g. `"30s"`.
+  pub timeout: Option<String>,
+  #[serde(default)]
+  pub hide: bool,
+  /// Same syntax as the right-hand side of `--depends-on`.
+  #[serde(default)]
+  pub depends_on: Vec<String>,
+  /// Same syntax as the right-hand side of `--ready`.
+  pub ready: Option<String>,
+  /// Globs to restart the process for, like the right-hand side of
+  /// `--watch`.
+  #[serde(default, deserialize_with = "one_or_many")]
+  pub watch: Vec<String>,
+}
+
+/// Settings that apply to a single process rather than the whole run.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ProcessOptions {
+  pub cwd: Option<PathBuf>,
+  pub env: Vec<(String, String)>,
+  pub env_files: Vec<PathBuf>,
+  pub log_file: Option<PathBuf>,
+  pub color: Option<PrefixColor>,
+  pub restart_tries: Option<i64>,
+  pub restart_after: Option<i64>,
+  pub restart_always: Option<bool>,
+  /// How long the process may run; zero for no limit.
+  pub timeout: Option<Duration>,
+  pub hide: bool,
+}
+
+impl ProcessConfig {
+  pub fn options(&self) -> Result<ProcessOptions, String> {
+    let color = match &self.color {
+      Some(color) => Some(
+        PrefixColor::parse(color)
+          .map_err(|e| format!("process \"{}\": {}", self.name, e))?,
+      ),
+      None => None,
+    };
+    let timeout = match &self.timeout {
+      Some(timeout) => Some(
+        parse_duration(timeout)
+          .map_err(|e| format!("process \"{}\": {}", self.name, e))?,
+      ),
+      None => None,
+    };
+    Ok(ProcessOptions {
+      cwd: self.cwd.clone(),
+      env: self
+        .env
+        .iter()
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect(),
+      env_files: self.env_file.clone(),
+      log_file: self.log_file.clone(),
+      color,
+      restart_tries: self.restart_tries,
+      restart_after: self.restart_after,
+      restart_always: self.restart_always,
+      timeout,
+      hide: self.hide,
+    })
+  }
+}
+
+/// Apply `--cwd target=dir` and `--env target:KEY=VALUE` flags on top of
+/// whatever the config file set. A `--cwd` replaces the configured
+/// directory; `--env` entries are added after the configured ones, so they
+/// win for the same key.
+pub fn apply_cli_options(
+  options: &mut [ProcessOptions],
+  cwd_specs: &[String],
+  env_specs: &[String],
+  names: &[Option<String>],
+) -> Result<(), String> {
+  let num_processes = options.len();
+  let resolve = |flag: &str, target: &str| {
+    resolve_target(target.trim(), names, num_processes).ok_or_else(|| {
+      format!(
+        "{}: \"{}\" does not match any process name or index",
+        flag,
+        target.trim()
+      )
+    })
+  };
+
+  for spec in cwd_specs {
+    let (target, dir) = spec
+      .split_once('=')
+      .filter(|(_, dir)| !dir.is_empty())
+      .ok_or_else(|| format!("Invalid --cwd '{}': expected target=dir", spec))?;
+    let index = resolve("--cwd", target)?;
+    options[index].cwd = Some(PathBuf::from(dir));
+  }
+
+  for spec in env_specs {
+    // Split on the first `=` so values may contain one, then on the last
+    // `:` before it so process names may contain one.
+    let parsed = spec.split_once('=').and_then(|(left, value)| {
+      let (target, key) = left.rsplit_once(':')?;
+      Some((target, key.trim(), value))
+    });
+    let (target, key, value) = parsed
+      .filter(|(_, key, _)| !key.is_empty())
+      .ok_or_else(|| {
+        format!("Invalid --env '{}': expected target:KEY=VALUE", spec)
+      })?;
+    let index = resolve("--env", target)?;
+    options[index]
+      .env
+      .push((key.to_string(), value.to_string()));
+  }
+  Ok(())
+}
+
+/// Load dotenv files into each process's environment: the global files
+/// first, then the process's own, then its explicit `env` entries, so later
+/// sources win for the same key. `${VAR}` references see everything loaded
+/// before them, then mlti's own environment.
+pub fn apply_env_files(
+  options: &mut [ProcessOptions],
+  global_files: &[PathBuf],
+) -> Result<(), String> {
+  let mut global = Vec::new();
+  for path in global_files {
+    let vars = load_env_file(path, |name| lookup_env(&global, name))?;
+    global.extend(vars);
+  }
+  for options in options.iter_mut() {
+    let mut env = global.clone();
+    for path in &options.env_files {
+      let vars = load_env_file(path, |name| lookup_env(&env, name))?;
+      env.extend(vars);
+    }
+    env.append(&mut options.env);
+    options.env = env;
+  }
+  Ok(())
+}
+
+fn lookup_env(vars: &[(String, String)], name: &str) -> Option<String> {
+  vars
+    .iter()
+    .rev()
+    .find(|(key, _)| key == name)
+    .map(|(_, value)| value.clone())
+    .or_else(|| std::env::var(name).ok())
+}
+
+/// Work out which config file to use: `--config`, then `MLTI_CONFIG`, then
+/// `mlti.toml` in the working directory if there is one.
+pub fn find_config(cli: Option<String>) -> Option<PathBuf> {
+  cli
+    .or_else(|| std::env::var("MLTI_CONFIG").ok().filter(|v| !v.is_empty()))
+    .map(PathBuf::from)
+    .or_else(|| {
+      let default = PathBuf::from(DEFAULT_CONFIG_FILE);
+      default.is_file().then_some(default)
+    })
+}
+
+impl Config {
+  pub fn load(path: &Path) -> Result<Self> {
+    let content = fs::read_to_string(path)
+      .context(format!("Could not read config file: {}", path.display()))?;
+    let mut config: Config = toml::from_str(&content)
+      .context(format!("Invalid config file: {}", path.display()))?;
+
+    let base = path.parent().unwrap_or_else(|| Path::new(""));
+    for env_file in config.env_file.iter_mut() {
+      *env_file = base.join(&env_file);
+    }
+    if let Some(log_dir) = config.log_dir.take() {
+      config.log_dir = Some(base.join(log_dir));
+    }
+    if let Some(report) = config.junit_report.take() {
+      config.junit_report = Some(base.join(report));
+    }
+    if let Some(report) = config.analyze_report.take() {
+      config.analyze_report = Some(base.join(report));
+    }
+    if let Some(socket) = config.control_socket.take() {
+      config.control_socket = Some(base.join(socket));
+    }
+    for (i, process) in config.processes.iter_mut().enumerate() {
+      if process.name.trim().is_empty() {
+        bail!("{}: process #{} has an empty name", path.display(), i + 1);
+      }
+      if let Some(cwd) = process.cwd.take() {
+        process.cwd = Some(base.join(cwd));
+      }
+      for env_file in process.env_file.iter_mut() {
+        *env_file = base.join(&env_file);
+      }
+      if let Some(log_file) = process.log_file.take() {
+        process.log_file = Some(base.join(log_file));
+      }
+    }
+    for (i, process) in config.processes.iter().enumerate() {
+      if config.processes[..i].iter().any(|p| p.name == process.name) {
+        bail!(
+          "{}: more than one process is named \"{}\"",
+          path.display(),
+          process.name
+        );
+      }
+    }
+    Ok(config)
+  }
+
+  /// The processes to run, in file order: all of them, or only those named
+  /// in `selected`.
+  pub fn select(&self, selected: &[String]) -> Result<Vec<&ProcessConfig>, String> {
+    for name in selected {
+      if !self.processes.iter().any(|p| &p.name == name) {
+        let available: Vec<&str> =
+          self.processes.iter().map(|p| p.name.as_str()).collect();
+        return Err(format!(
+          "Unknown process \"{}\". Available: {}",
+          name,
+          available.join(", ")
+        ));
+      }
+    }
+    Ok(
+      self
+        .processes
+        .iter()
+        .filter(|p| selected.is_empty() || selected.contains(&p.name))
+        .collect(),
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn write_config(content: &str) -> (tempfile::TempDir, PathBuf) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("mlti.toml");
+    fs::write(&path, content).unwrap();
+    (dir, path)
+  }
+
+  #[test]
+  fn load_globals_and_processes() {
+    let (dir, path) = write_config(
+      r##"
+kill-others = true
+max-processes = "50%"
+restart-tries = 2
+
+[[processes]]
+name = "api"
+command = "cargo run"
+cwd = "backend"
+env = { PORT = "3000", RUST_LOG = "debug" }
+color = "#ff0000"
+restart-always = true
+watch = "src/**/*.rs"
+timeout = "10m"
+
+[[processes]]
+name = "web"
+command = "npm run dev"
+hide = true
+depends-on = ["api@ready"]
+ready = "tcp:5173"
+watch = ["src/**/*.ts", "index.html"]
+"##,
+    );
+    let config = Config::load(&path).unwrap();
+    assert_eq!(config.kill_others, Some(true));
+    assert_eq!(config.max_processes.unwrap().to_spec(), "50%");
+    assert_eq!(config.restart_tries, Some(2));
+    assert_eq!(config.processes.len(), 2);
+
+    let api = config.processes[0].options().unwrap();
+    assert_eq!(api.cwd, Some(dir.path().join("backend")));
+    assert_eq!(
+      api.env,
+      vec![
+        ("PORT".to_string(), "3000".to_string()),
+        ("RUST_LOG".to_string(), "debug".to_string())
+      ]
+    );
+    assert_eq!(api.color, Some(PrefixColor::rgb((255, 0, 0))));
+    assert_eq!(api.restart_always, Some(true));
+    assert_eq!(api.timeout, Some(Duration::from_secs(600)));
+    assert_eq!(config.processes[0].watch, vec!["src/**/*.rs".to_string()]);
+
+    let web = &config.processes[1];
+    assert!(web.options().unwrap().hide);
+    assert_eq!(web.depends_on, vec!["api@ready".to_string()]);
+    assert_eq!(web.ready.as_deref(), Some("tcp:5173"));
+    assert_eq!(web.watch, vec!["src/**/*.ts", "index.html"]);
+  }
+
+  #[test]
+  fn max_processes_accepts_a_number() {
+    let (_dir, path) = write_config("max-processes = 4\n");
+    let config = Config::load(&path).unwrap();
+    assert_eq!(config.max_processes.unwrap().to_spec(), "4");
+  }
+
+  #[test]
+  fn load_rejects_unknown_keys_and_duplicate_names() {
+    let (_dir, path) = write_config("kill-everything = true\n");
+    assert!(Config::load(&path).is_err());
+
+    let (_dir, path) = write_config(
+      "[[processes]]\nname = \"a\"\ncommand = \"x\"\n\
+       [[processes]]\nname = \"a\"\ncommand = \"y\"\n",
+    );
+    let err = Config::load(&path).unwrap_err().to_string();
+    assert!(err.contains("more than one process"), "{}", err);
+  }
+
+  #[test]
+  fn load_reports_missing_file() {
+    let err = Config::load(Path::new("/nonexistent/mlti.toml")).unwrap_err();
+    assert!(err.to_string().contains("Could not read config file"));
+  }
+
+  #[test]
+  fn select_keeps_file_order_and_rejects_unknown_names() {
+    let (_dir, path) = write_config(
+      "[[processes]]\nname = \"a\"\ncommand = \"x\"\n\
+       [[processes]]\nname = \"b\"\ncommand = \"y\"\n\
+       [[processes]]\nname = \"c\"\ncommand = \"z\"\n",
+    );
+    let config = Config::load(&path).unwrap();
+    let names = |sel: &[&str]| -> Vec<String> {
+      let sel: Vec<String> = sel.iter().map(|s| s.to_string()).collect();
+      config
+        .select(&sel)
+        .unwrap()
+        .iter()
+        .map(|p| p.name.clone())
+        .collect()
+    };
+    assert_eq!(names(&[]), vec!["a", "b", "c"]);
+    assert_eq!(names(&["c", "a"]), vec!["a", "c"]);
+    assert!(config.select(&["d".to_string()]).is_err());
+  }
+
+  #[test]
+  fn cli_options_override_and_extend_config() {
+    let names = vec![Some("api".to_string()), Some("web:dev".to_string())];
+    let mut options = vec![
+      ProcessOptions {
+        cwd: Some(PathBuf::from("from-config")),
+        env: vec![("PORT".to_string(), "1".to_string())],
+        ..Default::default()
+      },
+      ProcessOptions::default(),
+    ];
+    apply_cli_options(
+      &mut options,
+      &["api=backend".to_string()],
+      &[
+        "api:PORT=3001".to_string(),
+        "web:dev:URL=http://x/?a=b".to_string(),
+        "1:NODE_ENV=development".to_string(),
+      ],
+      &names,
+    )
+    .unwrap();
+    assert_eq!(options[0].cwd, Some(PathBuf::from("backend")));
+    assert_eq!(
+      options[0].env,
+      vec![
+        ("PORT".to_string(), "1".to_string()),
+        ("PORT".to_string(), "3001".to_string())
+      ]
+    );
+    assert_eq!(
+      options[1].env,
+      vec![
+        ("URL".to_string(), "http://x/?a=b".to_string()),
+        ("NODE_ENV".to_string(), "development".to_string())
+      ]
+    );
+  }
+
+  #[test]
+  fn cli_options_reject_bad_specs() {
+    let names = vec![Some("api".to_string())];
+    let mut options = vec![ProcessOptions::default()];
+    let mut apply = |cwd: &[&str], env: &[&str]| {
+      let cwd: Vec<String> = cwd.iter().map(|s| s.to_string()).collect();
+      let env: Vec<String> = env.iter().map(|s| s.to_string()).collect();
+      apply_cli_options(&mut options, &cwd, &env, &names)
+    };
+    assert!(apply(&["api"], &[]).is_err());
+    assert!(apply(&["api="], &[]).is_err());
+    assert!(apply(&["db=x"], &[]).is_err());
+    assert!(apply(&[], &["api=1"]).is_err());
+    assert!(apply(&[], &["api:=1"]).is_err());
+    assert!(apply(&[], &["db:PORT=1"]).is_err());
+  }
+
+  #[test]
+  fn invalid_color_names_the_process() {
+    let (_dir, path) = write_config(
+      "[[processes]]\nname = \"a\"\ncommand = \"x\"\ncolor = \"mauve\"\n",
+    );
+    let config = Config::load(&path).unwrap();
+    let err = config.processes[0].options().unwrap_err();
+    assert!(err.contains("process \"a\""), "{}", err);
+  }
+
+  #[test]
+  fn env_files_layer_under_explicit_env() {
+    let (dir, path) = write_config(
+      r#"
+env-file = ".env"
+
+[[processes]]
+name = "api"
+command = "x"
+env-file = "api/.env.local"
+env = { PORT = "4000" }
+
+[[processes]]
+name = "web"
+command = "y"
+"#,
+    );
+    fs::write(dir.path().join(".env"), "HOST=localhost\nPORT=3000\n").unwrap();
+    fs::create_dir(dir.path().join("api")).unwrap();
+    fs::write(
+      dir.path().join("api/.env.local"),
+      "PORT=3001\nURL=http://${HOST}:${PORT}\n",
+    )
+    .unwrap();
+
+    let config = Config::load(&path).unwrap();
+    assert_eq!(config.env_file, vec![dir.path().join(".env")]);
+    let mut options: Vec<ProcessOptions> = config
+      .processes
+      .iter()
+      .map(|p| p.options().unwrap())
+      .collect();
+    apply_env_files(&mut options, &config.env_file).unwrap();
+
+    let value = |options: &ProcessOptions, key: &str| {
+      options
+        .env
+        .iter()
+        .rev()
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v.clone())
+    };
+    assert_eq!(value(&options[0], "PORT").as_deref(), Some("4000"));
+    assert_eq!(
+      value(&options[0], "URL").as_deref(),
+      Some("http://localhost:3001")
+    );
+    assert_eq!(value(&options[1], "PORT").as_deref(), Some("3000"));
+    assert_eq!(value(&options[1], "URL"), None);
+
+    let missing = vec![dir.path().join("nope.env")];
+    let err = apply_env_files(&mut options, &missing).unwrap_err();
+    assert!(err.contains("Could not read env file"), "{}", err);
+  }
+}
@@ -0,0 +1,561 @@
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use argh::FromArgs;
+use flume::Sender;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::input_router::{resolve_target, InputRouter};
+use crate::message::{Message, MessageType};
+use crate::messenger::{json_message, unpack_replay};
+use crate::scheduler::Control;
+use crate::status::{DisplayState, ProcessStatus};
+
+/// What the control socket knows about the session. `tap` keeps it up to
+/// date from the message channel.
+pub struct Hub {
+  statuses: Mutex<Vec<ProcessStatus>>,
+  // Connections that asked to `subscribe` to the message stream.
+  subscribers: Mutex<Vec<Sender<Message>>>,
+}
+
+impl Hub {
+  pub fn new(num_processes: usize) -> Arc<Self> {
+    Arc::new(Self {
+      statuses: Mutex::new(vec![ProcessStatus::default(); num_processes]),
+      subscribers: Mutex::new(Vec::new()),
+    })
+  }
+
+  /// A sender that shows the hub every message before passing it on to
+  /// `downstream`, in the same order.
+  pub fn tap(self: &Arc<Self>, downstream: Sender<Message>) -> Sender<Message> {
+    let (tx, rx) = flume::unbounded::<Message>();
+    let hub = self.clone();
+    tokio::spawn(async move {
+      while let Ok(message) = rx.recv_async().await {
+        hub.observe(&message);
+        if downstream.send(message).is_err() {
+          break;
+        }
+      }
+    });
+    tx
+  }
+
+  fn observe(&self, message: &Message) {
+    let mut subscribers = self.subscribers.lock().unwrap();
+    if let MessageType::Kill = message.type_ {
+      // The session is over; ending the subscriptions closes them.
+      subscribers.clear();
+      return;
+    }
+    if let Some(index) = message.sender.index {
+      if let Some(status) = self.statuses.lock().unwrap().get_mut(index) {
+        status.update(message);
+      }
+    }
+    for message in unpack_replay(message.clone()) {
+      subscribers.retain(|subscriber| subscriber.send(message.clone()).is_ok());
+    }
+  }
+
+  fn subscribe(&self) -> flume::Receiver<Message> {
+    let (tx, rx) = flume::unbounded();
+    self.subscribers.lock().unwrap().push(tx);
+    rx
+  }
+}
+
+/// A request to the control socket: one JSON object per line.
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(tag = "cmd", rename_all = "lowercase")]
+enum Request {
+  List,
+  Start { process: String },
+  Stop { process: String },
+  Restart { process: String },
+  Send { process: String, text: String },
+  Subscribe,
+}
+
+/// Everything a control socket connection can act on.
+pub struct Session {
+  pub names: Vec<Option<String>>,
+  pub commands: Vec<String>,
+  pub hub: Arc<Hub>,
+  pub control_tx: Sender<Control>,
+  pub input_router: Option<Arc<InputRouter>>,
+}
+
+impl Session {
+  fn name(&self, index: usize) -> String {
+    self
+      .names
+      .get(index)
+      .cloned()
+      .flatten()
+      .unwrap_or_else(|| index.to_string())
+  }
+
+  fn resolve(&self, process: &str) -> Result<usize, String> {
+    resolve_target(process, &self.names, self.commands.len())
+      .ok_or_else(|| format!("Unknown process \"{}\"", process))
+  }
+
+  /// Answer any request but `Subscribe`.
+  async fn handle(&self, request: Request) -> Result<Value, String> {
+    let control = match request {
+      Request::List => return Ok(self.list()),
+      Request::Send { process, text } => {
+        let index = self.resolve(&process)?;
+        let router = self.input_router.as_ref().ok_or(
+          "Input forwarding is off; start mlti with --handle-input".to_string(),
+        )?;
+        router.route_to(index, &text).await;
+        return Ok(json!({ "ok": true }));
+      }
+      Request::Subscribe => unreachable!("subscriptions are handled by serve"),
+      Request::Start { process } => Control::Start(self.resolve(&process)?),
+      Request::Stop { process } => Control::Stop(self.resolve(&process)?),
+      Request::Restart { process } => Control::Restart(self.resolve(&process)?),
+    };
+    self
+      .control_tx
+      .send(control)
+      .map_err(|_| "The session is shutting down".to_string())?;
+    Ok(json!({ "ok": true }))
+  }
+
+  fn list(&self) -> Value {
+    let statuses = self.hub.statuses.lock().unwrap();
+    let processes: Vec<Value> = statuses
+      .iter()
+      .enumerate()
+      .map(|(index, status)| {
+        let exit_code = match status.state {
+          DisplayState::Exited(code) => Some(code),
+          _ => None,
+        };
+        json!({
+          "index": index,
+          "name": self.name(index),
+          "command": self.commands[index],
+          "state": status.state.name(),
+          "exit_code": exit_code,
+          "pid": status.pid,
+          "restarts": status.restarts,
+        })
+      })
+      .collect();
+    json!({ "ok": true, "processes": processes })
+  }
+}
+
+fn error(message: String) -> Value {
+  json!({ "ok": false, "error": message })
+}
+
+#[cfg(unix)]
+pub use unix::{ctl, ControlSocket};
+
+#[cfg(unix)]
+mod unix {
+  use std::os::unix::fs::FileTypeExt;
+  use std::path::Path;
+  use std::sync::Arc;
+
+  use argh::FromArgs;
+  use serde_json::{json, Value};
+  use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+  use tokio::net::{UnixListener, UnixStream};
+
+  use super::{error, json_message, request, CtlCommand, Request, Session};
+
+  /// The `--control-socket` listener.
+  pub struct ControlSocket {
+    listener: UnixListener,
+  }
+
+  impl ControlSocket {
+    /// Listen on `path`. A socket file left behind by a session that
+    /// didn't clean up is replaced, a live one is an error.
+    pub fn bind(path: &Path) -> Result<Self, String> {
+      let bind_error = |e| {
+        format!(
+          "Could not listen on control socket {}: {}",
+          path.display(),
+          e
+        )
+      };
+      let listener = match UnixListener::bind(path) {
+        Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => {
+          let stale = std::fs::symlink_metadata(path)
+            .is_ok_and(|meta| meta.file_type().is_socket())
+            && std::os::unix::net::UnixStream::connect(path).is_err();
+          if !stale {
+            return Err(bind_error(e));
+          }
+          std::fs::remove_file(path).map_err(bind_error)?;
+          UnixListener::bind(path).map_err(bind_error)?
+        }
+        result => result.map_err(bind_error)?,
+      };
+      Ok(Self { listener })
+    }
+
+    /// Answer connections until the process exits.
+    pub async fn serve(self, session: Session) {
+      let session = Arc::new(session);
+      while let Ok((stream, _)) = self.listener.accept().await {
+        tokio::spawn(connection(stream, session.clone()));
+      }
+    }
+  }
+
+  async fn connection(stream: UnixStream, session: Arc<Session>) {
+    let (read, mut write) = stream.into_split();
+    let mut lines = BufReader::new(read).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+      if line.trim().is_empty() {
+        continue;
+      }
+      let response = match serde_json::from_str::<Request>(&line) {
+        Ok(Request::Subscribe) => {
+          // From here on the connection only carries the message stream,
+          // in the same form as `--output json`.
+          let messages = session.hub.subscribe();
+          if send(&mut write, &json!({ "ok": true })).await.is_err() {
+            return;
+          }
+          while let Ok(message) = messages.recv_async().await {
+            if let Some(value) = json_message(&message) {
+              if send(&mut write, &value).await.is_err() {
+                return;
+              }
+            }
+          }
+          return;
+        }
+        Ok(request) => session.handle(request).await.unwrap_or_else(error),
+        Err(e) => error(format!("Invalid request: {}", e)),
+      };
+      if send(&mut write, &response).await.is_err() {
+        return;
+      }
+    }
+  }
+
+  async fn send(
+    write: &mut (impl AsyncWriteExt + Unpin),
+    value: &Value,
+  ) -> std::io::Result<()> {
+    write.write_all(format!("{}\n", value).as_bytes()).await
+  }
+
+  /// Run `mlti ctl` with the arguments after `ctl`, returning its exit
+  /// code.
+  pub async fn ctl(args: &[String]) -> i32 {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let command = match CtlCommand::from_args(&["mlti ctl"], &args) {
+      Ok(command) => command,
+      Err(early_exit) => {
+        return match early_exit.status {
+          Ok(()) => {
+            println!("{}", early_exit.output);
+            0
+          }
+          Err(()) => {
+            eprintln!("{}", early_exit.output);
+            1
+          }
+        };
+      }
+    };
+    match run_ctl(&command).await {
+      Ok(()) => 0,
+      Err(e) => {
+        eprintln!("{}", e);
+        1
+      }
+    }
+  }
+
+  async fn run_ctl(command: &CtlCommand) -> Result<(), String> {
+    let path = command
+      .socket
+      .clone()
+      .or_else(|| std::env::var("MLTI_CONTROL_SOCKET").ok())
+      .ok_or("No control socket: pass --socket or set MLTI_CONTROL_SOCKET")?;
+    let request = request(&command.action, &command.args)?;
+
+    let stream = UnixStream::connect(&path)
+      .await
+      .map_err(|e| format!("Could not connect to {}: {}", path, e))?;
+    let (read, mut write) = stream.into_split();
+    send(&mut write, &request)
+      .await
+      .map_err(|e| format!("Could not send request: {}", e))?;
+    let mut lines = BufReader::new(read).lines();
+    let response = match lines.next_line().await {
+      Ok(Some(line)) => line,
+      _ => return Err("The session closed the connection".to_string()),
+    };
+    let value: Value = serde_json::from_str(&response)
+      .map_err(|e| format!("Invalid response: {}", e))?;
+    if value["ok"] != json!(true) {
+      return Err(value["error"].as_str().unwrap_or(&response).to_string());
+    }
+
+    match command.action.as_str() {
+      "subscribe" => {
+        while let Ok(Some(line)) = lines.next_line().await {
+          println!("{}", line);
+        }
+      }
+      _ if command.json => println!("{}", response),
+      "list" => print_list(&value),
+      _ => {}
+    }
+    Ok(())
+  }
+
+  fn print_list(value: &Value) {
+    let processes = value["processes"].as_array().cloned().unwrap_or_default();
+    let width = |key: &str| {
+      processes
+        .iter()
+        .map(|p| state_text(p, key).chars().count())
+        .max()
+        .unwrap_or(0)
+    };
+    let (name_width, state_width) = (width("name"), width("state"));
+    for p in &processes {
+      println!(
+        "{:>2}  {:<name_width$}  {:<state_width$}  {}",
+        p["index"],
+        state_text(p, "name"),
+        state_text(p, "state"),
+        p["command"].as_str().unwrap_or_default(),
+      );
+    }
+  }
+
+  /// A field of a `list` entry as shown by `mlti ctl list`; the state gets
+  /// the exit code or pid.
+  fn state_text(process: &Value, key: &str) -> String {
+    let text = process[key].as_str().unwrap_or_default().to_string();
+    if key != "state" {
+      return text;
+    }
+    match (&process["exit_code"], &process["pid"]) {
+      (Value::Number(code), _) => format!("{} {}", text, code),
+      (_, Value::Number(pid)) => format!("{} (pid {})", text, pid),
+      _ => text,
+    }
+  }
+}
+
+#[cfg(not(unix))]
+pub use fallback::{ctl, ControlSocket};
+
+#[cfg(not(unix))]
+mod fallback {
+  use std::path::Path;
+
+  use super::Session;
+
+  const UNSUPPORTED: &str = "Control sockets need Unix domain sockets";
+
+  pub struct ControlSocket;
+
+  impl ControlSocket {
+    pub fn bind(_path: &Path) -> Result<Self, String> {
+      Err(UNSUPPORTED.to_string())
+    }
+
+    pub async fn serve(self, _session: Session) {}
+  }
+
+  pub async fn ctl(_args: &[String]) -> i32 {
+    eprintln!("{}", UNSUPPORTED);
+    1
+  }
+}
+
+#[derive(FromArgs)]
+/// Control a running mlti session through its --control-socket.
+struct CtlCommand {
+  /// path of the session's control socket (default: $MLTI_CONTROL_SOCKET)
+  #[argh(option)]
+  socket: Option<String>,
+
+  /// print the session's JSON response as is
+  #[argh(switch)]
+  json: bool,
+
+  /// list, start, stop, restart, send or subscribe
+  #[argh(positional)]
+  action: String,
+
+  /// the process name or index; for send, followed by the line to send
+  #[argh(positional)]
+  args: Vec<String>,
+}
+
+/// Build the request for a `mlti ctl` action.
+fn request(action: &str, args: &[String]) -> Result<Value, String> {
+  let process = || {
+    args
+      .first()
+      .cloned()
+      .ok_or(format!("mlti ctl {} needs a process name or index", action))
+  };
+  match action {
+    "list" | "subscribe" => Ok(json!({ "cmd": action })),
+    "start" | "stop" | "restart" => {
+      Ok(json!({ "cmd": action, "process": process()? }))
+    }
+    "send" => Ok(json!({
+      "cmd": "send",
+      "process": process()?,
+      "text": args[1..].join(" "),
+    })),
+    _ => Err(format!(
+      "Unknown action '{}'. Expected: list, start, stop, restart, send or \
+       subscribe",
+      action
+    )),
+  }
+}
+
+/// Remove the socket file once the session is over.
+pub fn remove_socket(path: &Path) {
+  std::fs::remove_file(path).ok();
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::message::{build_message_sender, Event, SenderType};
+
+  fn session(
+    input_router: Option<Arc<InputRouter>>,
+  ) -> (Session, flume::Receiver<Control>) {
+    let (control_tx, control_rx) = flume::unbounded();
+    let session = Session {
+      names: vec![Some("api".to_string()), None],
+      commands: vec!["cargo run".to_string(), "npm start".to_string()],
+      hub: Hub::new(2),
+      control_tx,
+      input_router,
+    };
+    (session, control_rx)
+  }
+
+  #[test]
+  fn parse_requests() {
+    let parse = |s: &str| serde_json::from_str::<Request>(s).unwrap();
+    assert_eq!(parse(r#"{"cmd":"list"}"#), Request::List);
+    assert_eq!(
+      parse(r#"{"cmd":"restart","process":"api"}"#),
+      Request::Restart {
+        process: "api".to_string()
+      }
+    );
+    assert_eq!(
+      parse(r#"{"cmd":"send","process":"1","text":"hi"}"#),
+      Request::Send {
+        process: "1".to_string(),
+        text: "hi".to_string()
+      }
+    );
+    assert!(serde_json::from_str::<Request>(r#"{"cmd":"stop"}"#).is_err());
+    assert!(serde_json::from_str::<Request>(r#"{"cmd":"explode"}"#).is_err());
+  }
+
+  #[test]
+  fn ctl_builds_requests() {
+    let args = |a: &[&str]| a.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    assert_eq!(request("list", &[]).unwrap(), json!({ "cmd": "list" }));
+    assert_eq!(
+      request("stop", &args(&["api"])).unwrap(),
+      json!({ "cmd": "stop", "process": "api" })
+    );
+    assert_eq!(
+      request("send", &args(&["api", "hello", "there"])).unwrap(),
+      json!({ "cmd": "send", "process": "api", "text": "hello there" })
+    );
+    assert!(request("restart", &[]).is_err());
+    assert!(request("reboot", &[]).is_err());
+  }
+
+  #[tokio::test]
+  async fn requests_become_controls_by_name_or_index() {
+    let (session, control_rx) = session(None);
+    let stop = Request::Stop {
+      process: "api".to_string(),
+    };
+    assert_eq!(session.handle(stop).await, Ok(json!({ "ok": true })));
+    assert_eq!(control_rx.try_recv(), Ok(Control::Stop(0)));
+    let start = Request::Start {
+      process: "1".to_string(),
+    };
+    session.handle(start).await.unwrap();
+    assert_eq!(control_rx.try_recv(), Ok(Control::Start(1)));
+
+    let unknown = Request::Restart {
+      process: "web".to_string(),
+    };
+    assert_eq!(
+      session.handle(unknown).await,
+      Err("Unknown process \"web\"".to_string())
+    );
+    let send = Request::Send {
+      process: "api".to_string(),
+      text: "hi".to_string(),
+    };
+    assert!(session
+      .handle(send)
+      .await
+      .unwrap_err()
+      .contains("--handle-input"));
+  }
+
+  #[tokio::test]
+  async fn list_and_subscribe_follow_the_message_stream() {
+    let (session, _control_rx) = session(None);
+    let (downstream_tx, downstream_rx) = flume::unbounded();
+    let tap = session.hub.tap(downstream_tx);
+    let subscription = session.hub.subscribe();
+
+    let mut sender = build_message_sender(SenderType::Task, Some(1), None);
+    sender.pid = Some(7);
+    let mut spawned = Message::new(MessageType::Lifecycle, None, None, None, sender);
+    spawned.event = Some(Event::Spawned);
+    tap.send(spawned).unwrap();
+    // Passed on unchanged once the hub has seen it.
+    downstream_rx.recv_async().await.unwrap();
+
+    let list = session.list();
+    assert_eq!(list["processes"][0]["state"], "queued");
+    assert_eq!(list["processes"][0]["name"], "api");
+    assert_eq!(list["processes"][1]["state"], "running");
+    assert_eq!(list["processes"][1]["name"], "1");
+    assert_eq!(list["processes"][1]["pid"], 7);
+    assert_eq!(subscription.try_recv().unwrap().sender.pid, Some(7));
+
+    tap
+      .send(Message::new(
+        MessageType::Kill,
+        None,
+        None,
+        None,
+        build_message_sender(SenderType::Main, None, None),
+      ))
+      .unwrap();
+    downstream_rx.recv_async().await.unwrap();
+    assert!(subscription.recv_async().await.is_err());
+  }
+}
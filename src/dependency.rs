@@ -0,0 +1,399 @@
+use crate::input_router::resolve_target;
+use crate::readiness::Readiness;
+
+/// What a dependent process waits for before it may start.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DependencyCondition {
+  /// The dependency exited, whatever its exit code.
+  Completed,
+  /// The dependency exited with code 0.
+  Success,
+  /// The dependency passed its `--ready` check, or was spawned if it has
+  /// none.
+  Ready,
+}
+
+impl DependencyCondition {
+  fn parse(s: &str) -> Result<Self, String> {
+    match s {
+      "completed" => Ok(Self::Completed),
+      "success" | "completed-successfully" => Ok(Self::Success),
+      "ready" => Ok(Self::Ready),
+      other => Err(format!(
+        "Invalid dependency condition: '{}'. Expected: completed, success, ready",
+        other
+      )),
+    }
+  }
+}
+
+/// Split a `dep[@condition]` entry into the dependency and its condition.
+/// Only a known condition is split off, so names containing `@`, such as
+/// `@org/api`, are kept whole.
+pub fn split_condition(dep: &str) -> (&str, Option<DependencyCondition>) {
+  dep
+    .rsplit_once('@')
+    .and_then(|(name, condition)| {
+      let condition = DependencyCondition::parse(condition).ok()?;
+      Some((name, Some(condition)))
+    })
+    .unwrap_or((dep, None))
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Dependency {
+  pub index: usize,
+  pub condition: DependencyCondition,
+}
+
+/// Where a dependency stands from the point of view of its dependent.
+#[derive(Debug, PartialEq, Eq)]
+pub enum DependencyState {
+  Waiting,
+  Satisfied,
+  /// The condition can never be met; the dependent must be skipped.
+  Failed,
+}
+
+/// Lifecycle of a process as tracked by the scheduler.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProcessState {
+  Pending,
+  Running,
+  Exited(i32),
+  Killed,
+  Skipped,
+}
+
+impl Dependency {
+  /// Evaluate this dependency given the state of the process it points at
+  /// and how far that process got towards being ready.
+  pub fn state(
+    &self,
+    target: ProcessState,
+    readiness: Readiness,
+  ) -> DependencyState {
+    match (self.condition, target, readiness) {
+      (DependencyCondition::Ready, _, Readiness::Ready) => {
+        DependencyState::Satisfied
+      }
+      (DependencyCondition::Ready, _, Readiness::TimedOut) => {
+        DependencyState::Failed
+      }
+      (_, ProcessState::Pending | ProcessState::Running, _) => {
+        DependencyState::Waiting
+      }
+      (_, ProcessState::Killed | ProcessState::Skipped, _) => {
+        DependencyState::Failed
+      }
+      (DependencyCondition::Completed, ProcessState::Exited(_), _) => {
+        DependencyState::Satisfied
+      }
+      (DependencyCondition::Success, ProcessState::Exited(0), _) => {
+        DependencyState::Satisfied
+      }
+      // Exited non-zero, or exited without ever becoming ready.
+      (_, ProcessState::Exited(_), _) => DependencyState::Failed,
+    }
+  }
+}
+
+/// Parse `--depends-on` specs into one dependency list per process.
+///
+/// Each spec has the form `target=dep[@condition][,dep[@condition]...]`,
+/// where `target` and every `dep` are a process name or index (resolved
+/// with `resolve_target`) and `condition` is `completed`, `success` (the
+/// default) or `ready`. Specs for the same target accumulate.
+pub fn parse_depends_on(
+  specs: &[String],
+  names: &[Option<String>],
+  num_processes: usize,
+) -> Result<Vec<Vec<Dependency>>, String> {
+  let mut deps: Vec<Vec<Dependency>> = vec![Vec::new(); num_processes];
+  let resolve = |candidate: &str| {
+    resolve_target(candidate, names, num_processes).ok_or_else(|| {
+      format!(
+        "--depends-on: \"{}\" does not match any process name or index",
+        candidate
+      )
+    })
+  };
+
+  for spec in specs {
+    let (target, list) = spec.split_once('=').ok_or_else(|| {
+      format!(
+        "Invalid --depends-on '{}': expected target=dependency[@condition]",
+        spec
+      )
+    })?;
+    let target = resolve(target.trim())?;
+    for dep in list.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+      let (name, condition) = split_condition(dep);
+      // `dep@typo` is a known process with a bad condition, not a name.
+      let index = resolve(name).map_err(|e| match dep.rsplit_once('@') {
+        Some((name, condition)) if resolve(name).is_ok() => {
+          DependencyCondition::parse(condition).err().unwrap_or(e)
+        }
+        _ => e,
+      })?;
+      deps[target].push(Dependency {
+        index,
+        condition: condition.unwrap_or(DependencyCondition::Success),
+      });
+    }
+  }
+
+  check_cycles(&deps, names)?;
+  Ok(deps)
+}
+
+/// Make every process depend on each process in the stage before its own,
+/// `stages[i]` being the stage of process `i`, so a stage starts only once
+/// the previous one meets `condition`. Stage numbers may skip values; the
+/// previous stage is the highest one below.
+pub fn add_stage_dependencies(
+  deps: &mut [Vec<Dependency>],
+  stages: &[usize],
+  condition: DependencyCondition,
+  names: &[Option<String>],
+) -> Result<(), String> {
+  for (index, &stage) in stages.iter().enumerate() {
+    let Some(previous) = stages.iter().copied().filter(|&s| s < stage).max() else {
+      continue;
+    };
+    for (dep, _) in stages.iter().enumerate().filter(|(_, &s)| s == previous) {
+      if !deps[index].iter().any(|d| d.index == dep) {
+        deps[index].push(Dependency {
+          index: dep,
+          condition,
+        });
+      }
+    }
+  }
+  // Explicit dependencies on a later stage can close a loop.
+  check_cycles(deps, names)
+}
+
+/// Reject dependency graphs containing a cycle, naming the processes
+/// involved so the user can find it.
+fn check_cycles(
+  deps: &[Vec<Dependency>],
+  names: &[Option<String>],
+) -> Result<(), String> {
+  #[derive(Clone, Copy, PartialEq)]
+  enum Mark {
+    Unvisited,
+    InProgress,
+    Done,
+  }
+
+  fn visit(
+    node: usize,
+    deps: &[Vec<Dependency>],
+    marks: &mut [Mark],
+    path: &mut Vec<usize>,
+  ) -> Option<Vec<usize>> {
+    marks[node] = Mark::InProgress;
+    path.push(node);
+    for dep in &deps[node] {
+      match marks[dep.index] {
+        Mark::InProgress => {
+          let start = path.iter().position(|&n| n == dep.index).unwrap_or(0);
+          let mut cycle = path[start..].to_vec();
+          cycle.push(dep.index);
+          return Some(cycle);
+        }
+        Mark::Unvisited => {
+          if let Some(cycle) = visit(dep.index, deps, marks, path) {
+            return Some(cycle);
+          }
+        }
+        Mark::Done => {}
+      }
+    }
+    path.pop();
+    marks[node] = Mark::Done;
+    None
+  }
+
+  let mut marks = vec![Mark::Unvisited; deps.len()];
+  for node in 0..deps.len() {
+    if marks[node] != Mark::Unvisited {
+      continue;
+    }
+    if let Some(cycle) = visit(node, deps, &mut marks, &mut Vec::new()) {
+      let display = cycle
+        .iter()
+        .map(|&i| {
+          names
+            .get(i)
+            .cloned()
+            .flatten()
+            .unwrap_or_else(|| i.to_string())
+        })
+        .collect::<Vec<_>>()
+        .join(" -> ");
+      return Err(format!("Dependency cycle detected: {}", display));
+    }
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn names_of(strs: &[&str]) -> Vec<Option<String>> {
+    strs.iter().map(|s| Some(s.to_string())).collect()
+  }
+
+  fn specs(strs: &[&str]) -> Vec<String> {
+    strs.iter().map(|s| s.to_string()).collect()
+  }
+
+  #[test]
+  fn parse_pipeline_by_name_and_index() {
+    let names = names_of(&["codegen", "build", "server", "tests"]);
+    let deps = parse_depends_on(
+      &specs(&["build=codegen", "2=build", "tests=build,server@ready"]),
+      &names,
+      4,
+    )
+    .unwrap();
+    assert!(deps[0].is_empty());
+    assert_eq!(
+      deps[1],
+      vec![Dependency {
+        index: 0,
+        condition: DependencyCondition::Success
+      }]
+    );
+    assert_eq!(deps[2][0].index, 1);
+    assert_eq!(
+      deps[3],
+      vec![
+        Dependency {
+          index: 1,
+          condition: DependencyCondition::Success
+        },
+        Dependency {
+          index: 2,
+          condition: DependencyCondition::Ready
+        },
+      ]
+    );
+  }
+
+  #[test]
+  fn parse_rejects_unknown_targets_and_conditions() {
+    let names = names_of(&["a", "b"]);
+    assert!(parse_depends_on(&specs(&["a=missing"]), &names, 2).is_err());
+    assert!(parse_depends_on(&specs(&["missing=a"]), &names, 2).is_err());
+    let err = parse_depends_on(&specs(&["b=a@whenever"]), &names, 2).unwrap_err();
+    assert!(err.contains("Invalid dependency condition"), "{}", err);
+    assert!(parse_depends_on(&specs(&["b"]), &names, 2).is_err());
+  }
+
+  #[test]
+  fn parse_keeps_names_containing_at_whole() {
+    let names = names_of(&["@org/api", "web"]);
+    let deps = parse_depends_on(&specs(&["web=@org/api"]), &names, 2).unwrap();
+    assert_eq!(
+      deps[1],
+      vec![Dependency {
+        index: 0,
+        condition: DependencyCondition::Success,
+      }]
+    );
+
+    let deps = parse_depends_on(&specs(&["web=@org/api@ready"]), &names, 2).unwrap();
+    assert_eq!(deps[1][0].condition, DependencyCondition::Ready);
+  }
+
+  #[test]
+  fn parse_rejects_cycles() {
+    let names = names_of(&["a", "b", "c"]);
+    let err =
+      parse_depends_on(&specs(&["a=c", "b=a", "c=b@ready"]), &names, 3).unwrap_err();
+    assert!(err.contains("a -> c -> b -> a"), "{}", err);
+    assert!(parse_depends_on(&specs(&["a=a"]), &names, 3).is_err());
+  }
+
+  #[test]
+  fn parse_accepts_diamonds() {
+    let names = names_of(&["root", "left", "right", "join"]);
+    assert!(parse_depends_on(
+      &specs(&["left=root", "right=root", "join=left,right"]),
+      &names,
+      4
+    )
+    .is_ok());
+  }
+
+  #[test]
+  fn stages_depend_on_the_stage_before() {
+    let names = names_of(&["lint", "typecheck", "build", "server", "worker"]);
+    let mut deps =
+      parse_depends_on(&specs(&["worker=server@ready"]), &names, 5).unwrap();
+    add_stage_dependencies(
+      &mut deps,
+      &[0, 0, 1, 3, 3],
+      DependencyCondition::Success,
+      &names,
+    )
+    .unwrap();
+    assert!(deps[0].is_empty() && deps[1].is_empty());
+    let indices =
+      |deps: &[Dependency]| deps.iter().map(|d| d.index).collect::<Vec<_>>();
+    assert_eq!(indices(&deps[2]), vec![0, 1]);
+    assert_eq!(indices(&deps[3]), vec![2]);
+    // An explicit dependency between the same processes is kept as given.
+    assert_eq!(indices(&deps[4]), vec![3, 2]);
+    assert_eq!(deps[4][0].condition, DependencyCondition::Ready);
+    assert_eq!(deps[4][1].condition, DependencyCondition::Success);
+
+    let mut deps = parse_depends_on(&specs(&["lint=build"]), &names, 5).unwrap();
+    let err = add_stage_dependencies(
+      &mut deps,
+      &[0, 0, 1, 2, 2],
+      DependencyCondition::Completed,
+      &names,
+    )
+    .unwrap_err();
+    assert!(err.contains("cycle"), "{}", err);
+  }
+
+  #[test]
+  fn dependency_state_per_condition() {
+    let dep = |condition| Dependency {
+      index: 0,
+      condition,
+    };
+    use DependencyCondition::*;
+    use DependencyState::*;
+
+    use Readiness::{Pending, Ready as IsReady, TimedOut};
+
+    assert_eq!(dep(Success).state(ProcessState::Running, IsReady), Waiting);
+    assert_eq!(
+      dep(Success).state(ProcessState::Exited(0), IsReady),
+      Satisfied
+    );
+    assert_eq!(dep(Success).state(ProcessState::Exited(2), IsReady), Failed);
+    assert_eq!(
+      dep(Completed).state(ProcessState::Exited(2), Pending),
+      Satisfied
+    );
+    assert_eq!(dep(Completed).state(ProcessState::Skipped, Pending), Failed);
+    assert_eq!(dep(Ready).state(ProcessState::Running, Pending), Waiting);
+    assert_eq!(dep(Ready).state(ProcessState::Running, IsReady), Satisfied);
+    assert_eq!(dep(Ready).state(ProcessState::Running, TimedOut), Failed);
+    assert_eq!(dep(Ready).state(ProcessState::Exited(0), Pending), Failed);
+    assert_eq!(dep(Ready).state(ProcessState::Killed, Pending), Failed);
+    // A process that became ready stays ready after it exits.
+    assert_eq!(
+      dep(Ready).state(ProcessState::Exited(1), IsReady),
+      Satisfied
+    );
+  }
+}
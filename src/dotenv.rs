@@ -0,0 +1,255 @@
+use std::path::Path;
+
+/// Read a dotenv file. `${VAR}` references to variables not defined
+/// earlier in the file are resolved with `lookup`.
+pub fn load_env_file(
+  path: &Path,
+  lookup: impl Fn(&str) -> Option<String>,
+) -> Result<Vec<(String, String)>, String> {
+  let content = std::fs::read_to_string(path)
+    .map_err(|e| format!("Could not read env file {}: {}", path.display(), e))?;
+  parse_env(&content, lookup).map_err(|e| format!("{}:{}", path.display(), e))
+}
+
+/// Parse dotenv syntax into variables, in file order.
+///
+/// Blank lines and `#` comments are skipped and an `export ` prefix is
+/// ignored. Single-quoted values are taken literally; double-quoted values
+/// understand `\n`, `\t`, `\"`, `\\` and `\$` escapes; both may span lines.
+/// Unquoted values end at a ` #` comment. `${VAR}`, `${VAR:-default}` and
+/// `$VAR` are expanded in everything but single quotes.
+pub fn parse_env(
+  content: &str,
+  lookup: impl Fn(&str) -> Option<String>,
+) -> Result<Vec<(String, String)>, String> {
+  let mut vars: Vec<(String, String)> = Vec::new();
+  let mut lines = content.lines().enumerate();
+  while let Some((i, line)) = lines.next() {
+    let line_no = i + 1;
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+      continue;
+    }
+    let line = line.strip_prefix("export ").map_or(line, str::trim_start);
+    let (key, value) = line
+      .split_once('=')
+      .ok_or_else(|| format!("{}: expected KEY=VALUE", line_no))?;
+    let key = key.trim();
+    if !is_valid_key(key) {
+      return Err(format!("{}: invalid variable name '{}'", line_no, key));
+    }
+
+    let resolve = |name: &str| {
+      vars
+        .iter()
+        .rev()
+        .find(|(k, _)| k == name)
+        .map(|(_, v)| v.clone())
+        .or_else(|| lookup(name))
+    };
+    let value = value.trim_start();
+    let value = match value.chars().next() {
+      Some(quote @ ('\'' | '"')) => {
+        let mut raw = value[1..].to_string();
+        let end = loop {
+          if let Some(end) = find_closing_quote(&raw, quote) {
+            break end;
+          }
+          match lines.next() {
+            Some((_, next)) => {
+              raw.push('\n');
+              raw.push_str(next);
+            }
+            None => {
+              return Err(format!("{}: unterminated {} quote", line_no, quote))
+            }
+          }
+        };
+        let rest = raw[end + 1..].trim();
+        if !rest.is_empty() && !rest.starts_with('#') {
+          return Err(format!(
+            "{}: unexpected '{}' after closing quote",
+            line_no, rest
+          ));
+        }
+        if quote == '\'' {
+          raw[..end].to_string()
+        } else {
+          expand(&raw[..end], true, &resolve)
+            .map_err(|e| format!("{}: {}", line_no, e))?
+        }
+      }
+      _ => {
+        let end = value
+          .char_indices()
+          .find(|&(i, c)| {
+            c == '#' && value[..i].ends_with(|c: char| c.is_whitespace())
+          })
+          .map_or(value.len(), |(i, _)| i);
+        expand(value[..end].trim_end(), false, &resolve)
+          .map_err(|e| format!("{}: {}", line_no, e))?
+      }
+    };
+    vars.push((key.to_string(), value));
+  }
+  Ok(vars)
+}
+
+fn is_valid_key(key: &str) -> bool {
+  let mut chars = key.chars();
+  chars
+    .next()
+    .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+    && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'))
+}
+
+/// Byte offset of the quote that closes a value, skipping `\"` inside
+/// double quotes.
+fn find_closing_quote(s: &str, quote: char) -> Option<usize> {
+  let mut escaped = false;
+  for (i, c) in s.char_indices() {
+    if escaped {
+      escaped = false;
+    } else if c == '\\' && quote == '"' {
+      escaped = true;
+    } else if c == quote {
+      return Some(i);
+    }
+  }
+  None
+}
+
+/// Expand variable references and, inside double quotes, escapes.
+fn expand(
+  s: &str,
+  escapes: bool,
+  resolve: &dyn Fn(&str) -> Option<String>,
+) -> Result<String, String> {
+  let mut out = String::new();
+  let mut chars = s.chars().peekable();
+  while let Some(c) = chars.next() {
+    match c {
+      '\\' if escapes => match chars.next() {
+        Some('n') => out.push('\n'),
+        Some('r') => out.push('\r'),
+        Some('t') => out.push('\t'),
+        Some(c @ ('"' | '\\' | '$')) => out.push(c),
+        Some(c) => {
+          out.push('\\');
+          out.push(c);
+        }
+        None => out.push('\\'),
+      },
+      '$' if chars.peek() == Some(&'{') => {
+        chars.next();
+        let mut reference = String::new();
+        loop {
+          match chars.next() {
+            Some('}') => break,
+            Some(c) => reference.push(c),
+            None => return Err(format!("unterminated '${{' in '{}'", s)),
+          }
+        }
+        let (name, default) = match reference.split_once(":-") {
+          Some((name, default)) => (name, Some(default)),
+          None => (reference.as_str(), None),
+        };
+        // Like the shell, `:-` also applies when the variable is empty.
+        let value = resolve(name).filter(|v| !v.is_empty() || default.is_none());
+        out.push_str(&value.or(default.map(str::to_string)).unwrap_or_default());
+      }
+      '$'
+        if chars
+          .peek()
+          .is_some_and(|c| c.is_ascii_alphabetic() || *c == '_') =>
+      {
+        let mut name = String::new();
+        while let Some(&c) = chars.peek() {
+          if !(c.is_ascii_alphanumeric() || c == '_') {
+            break;
+          }
+          name.push(c);
+          chars.next();
+        }
+        out.push_str(&resolve(&name).unwrap_or_default());
+      }
+      c => out.push(c),
+    }
+  }
+  Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn parse(content: &str) -> Vec<(String, String)> {
+    parse_env(content, |_| None).unwrap()
+  }
+
+  fn var(key: &str, value: &str) -> (String, String) {
+    (key.to_string(), value.to_string())
+  }
+
+  #[test]
+  fn parse_comments_export_and_inline_comments() {
+    let vars = parse(
+      "# database\n\
+       \n\
+       export DB_HOST = localhost\n\
+       DB_PORT=5432 # default port\n\
+       COLOR=#fff\n\
+       EMPTY=\n",
+    );
+    assert_eq!(
+      vars,
+      vec![
+        var("DB_HOST", "localhost"),
+        var("DB_PORT", "5432"),
+        var("COLOR", "#fff"),
+        var("EMPTY", ""),
+      ]
+    );
+  }
+
+  #[test]
+  fn parse_quoted_values() {
+    let vars = parse(
+      "SINGLE='keep ${HOME} \\n # as is'\n\
+       DOUBLE=\"tab\\there \\\"quoted\\\" \\$HOME\" # comment\n\
+       MULTI=\"line one\nline two\"\n",
+    );
+    assert_eq!(
+      vars,
+      vec![
+        var("SINGLE", "keep ${HOME} \\n # as is"),
+        var("DOUBLE", "tab\there \"quoted\" $HOME"),
+        var("MULTI", "line one\nline two"),
+      ]
+    );
+  }
+
+  #[test]
+  fn parse_interpolates_earlier_vars_then_lookup() {
+    let vars = parse_env(
+      "HOST=localhost\n\
+       URL=http://${HOST}:${PORT:-3000}/$BASE\n\
+       HOST=example.test\n\
+       AGAIN=${HOST}\n",
+      |name| (name == "BASE").then(|| "api".to_string()),
+    )
+    .unwrap();
+    assert_eq!(vars[1], var("URL", "http://localhost:3000/api"));
+    assert_eq!(vars[3], var("AGAIN", "example.test"));
+  }
+
+  #[test]
+  fn parse_reports_line_numbers() {
+    let err = |content: &str| parse_env(content, |_| None).unwrap_err();
+    assert_eq!(err("A=1\nnot a var\n"), "2: expected KEY=VALUE");
+    assert_eq!(err("\n1BAD=x"), "2: invalid variable name '1BAD'");
+    assert_eq!(err("A=\"open\nstill open"), "1: unterminated \" quote");
+    assert_eq!(err("A='x' y"), "1: unexpected 'y' after closing quote");
+    assert!(err("A=${B").starts_with("1: unterminated"));
+  }
+}
@@ -123,7 +123,22 @@ impl InputRouter {
 
   pub async fn route(&self, line: &str) {
     let parsed = self.parse_line(line);
+    self.deliver(parsed).await;
+  }
+
+  /// Send `payload` to the process at `index` as-is, without looking for a
+  /// `name:` prefix. Used where the target was picked some other way, e.g.
+  /// the focused process in `--tui`.
+  pub async fn route_to(&self, index: usize, payload: &str) {
+    let parsed = ParsedInput {
+      target: index,
+      payload: payload.to_string(),
+      target_name: self.display_name(index),
+    };
+    self.deliver(parsed).await;
+  }
 
+  async fn deliver(&self, parsed: ParsedInput) {
     // Quickly grab a clone of the target handle (or the map emptiness),
     // then drop the map lock before any `.await` on the stdin write.
     let handle = {
@@ -0,0 +1,198 @@
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::process_log::strip_ansi;
+use crate::task::{TaskStatus, TaskTiming};
+
+/// Render a JUnit XML report with one `<testcase>` per process, a failed
+/// one with the end of its output (`--tail-lines`). `names` are the process
+/// names by index; `timestamp` is when the run started.
+pub fn junit_report(
+  timings: &[TaskTiming],
+  names: &[Option<String>],
+  timestamp: &str,
+  total_secs: f64,
+) -> String {
+  let count = |status: TaskStatus, failed: bool| {
+    timings
+      .iter()
+      .filter(|t| t.status == status && (!failed || t.exit_code != 0))
+      .count()
+  };
+  let failures =
+    count(TaskStatus::Exited, true) + count(TaskStatus::TimedOut, false);
+  let errors = count(TaskStatus::Killed, false);
+  let skipped = count(TaskStatus::Skipped, false);
+
+  let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+  let totals = format!(
+    "tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\" time=\"{:.3}\"",
+    timings.len(),
+    failures,
+    errors,
+    skipped,
+    total_secs
+  );
+  writeln!(xml, "<testsuites name=\"mlti\" {}>", totals).ok();
+  writeln!(
+    xml,
+    "  <testsuite name=\"mlti\" {} timestamp=\"{}\">",
+    totals,
+    escape(timestamp)
+  )
+  .ok();
+  for t in timings {
+    let name = names
+      .get(t.index)
+      .cloned()
+      .flatten()
+      .unwrap_or_else(|| t.index.to_string());
+    write!(
+      xml,
+      "    <testcase name=\"{}\" classname=\"mlti\" time=\"{:.3}\"",
+      escape(&name),
+      t.duration_secs
+    )
+    .ok();
+    let output = || escape(&strip_ansi(&t.output.join("\n")));
+    match t.status {
+      TaskStatus::Exited if t.exit_code == 0 => xml.push_str("/>\n"),
+      TaskStatus::Exited => {
+        writeln!(
+          xml,
+          ">\n      <failure message=\"{} exited with code {}\" \
+           type=\"exit code\">{}</failure>\n    </testcase>",
+          escape(&t.raw_cmd),
+          t.exit_code,
+          output()
+        )
+        .ok();
+      }
+      TaskStatus::TimedOut => {
+        writeln!(
+          xml,
+          ">\n      <failure message=\"{} timed out\" \
+           type=\"timeout\">{}</failure>\n    </testcase>",
+          escape(&t.raw_cmd),
+          output()
+        )
+        .ok();
+      }
+      TaskStatus::Killed => {
+        writeln!(
+          xml,
+          ">\n      <error message=\"{} was killed before it finished\" \
+           type=\"killed\">{}</error>\n    </testcase>",
+          escape(&t.raw_cmd),
+          output()
+        )
+        .ok();
+      }
+      TaskStatus::Skipped => {
+        xml.push_str(
+          ">\n      <skipped message=\"a dependency was not met\"/>\n    \
+           </testcase>\n",
+        );
+      }
+    }
+  }
+  xml.push_str("  </testsuite>\n</testsuites>\n");
+  xml
+}
+
+/// Write the report to `path`.
+pub fn write_junit_report(path: &Path, report: &str) -> Result<(), String> {
+  std::fs::write(path, report)
+    .map_err(|e| format!("Could not write JUnit report {}: {}", path.display(), e))
+}
+
+/// Escape text for use in XML content and attribute values. Control
+/// characters other than tab and newline aren't allowed in XML at all.
+fn escape(text: &str) -> String {
+  let mut out = String::with_capacity(text.len());
+  for c in text.chars() {
+    match c {
+      '&' => out.push_str("&amp;"),
+      '<' => out.push_str("&lt;"),
+      '>' => out.push_str("&gt;"),
+      '"' => out.push_str("&quot;"),
+      '\'' => out.push_str("&apos;"),
+      '\t' | '\n' => out.push(c),
+      c if c.is_control() => {}
+      c => out.push(c),
+    }
+  }
+  out
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn timing(index: usize, exit_code: i32, status: TaskStatus) -> TaskTiming {
+    TaskTiming {
+      index,
+      raw_cmd: format!("cmd {}", index),
+      exit_code,
+      duration_secs: 1.5,
+      attempts: vec![],
+      status,
+      output: vec![
+        "line <1>".to_string(),
+        "\u{1b}[31mline 2\u{1b}[0m".to_string(),
+      ],
+      usage: None,
+    }
+  }
+
+  #[test]
+  fn escape_special_characters() {
+    assert_eq!(
+      escape("a<b & \"c\" 'd'>\u{7}"),
+      "a&lt;b &amp; &quot;c&quot; &apos;d&apos;&gt;"
+    );
+  }
+
+  #[test]
+  fn report_has_a_testcase_per_process() {
+    let timings = vec![
+      timing(0, 0, TaskStatus::Exited),
+      timing(1, 2, TaskStatus::Exited),
+      timing(2, 143, TaskStatus::Killed),
+      timing(3, 1, TaskStatus::Skipped),
+    ];
+    let names = vec![Some("lint".to_string()), Some("test".to_string())];
+    let xml = junit_report(&timings, &names, "2024-01-01T00:00:00", 3.0);
+
+    assert!(xml.contains(
+      "<testsuite name=\"mlti\" tests=\"4\" failures=\"1\" errors=\"1\" \
+       skipped=\"1\" time=\"3.000\" timestamp=\"2024-01-01T00:00:00\">"
+    ));
+    assert!(
+      xml.contains("<testcase name=\"lint\" classname=\"mlti\" time=\"1.500\"/>")
+    );
+    assert!(xml.contains(
+      "<failure message=\"cmd 1 exited with code 2\" type=\"exit code\">\
+       line &lt;1&gt;\nline 2</failure>"
+    ));
+    assert!(xml.contains("<testcase name=\"2\""));
+    assert!(xml.contains("<error message=\"cmd 2 was killed"));
+    assert!(xml.contains("<skipped message="));
+    assert!(xml.ends_with("</testsuites>\n"));
+  }
+
+  #[test]
+  fn timeouts_are_failures() {
+    let timings = vec![
+      timing(0, 2, TaskStatus::Exited),
+      timing(1, 124, TaskStatus::TimedOut),
+    ];
+    let xml = junit_report(&timings, &[], "2024-01-01T00:00:00", 3.0);
+    assert!(
+      xml.contains("tests=\"2\" failures=\"2\" errors=\"0\""),
+      "{}",
+      xml
+    );
+    assert!(xml.contains("<failure message=\"cmd 1 timed out\" type=\"timeout\">"));
+  }
+}
@@ -1,9 +1,13 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::io::IsTerminal;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
-use command::Process;
-use message::{build_message_sender, Message, MessageType};
+use command::{default_shell, expand_arguments, split_command, Process};
+use message::{build_message_sender, Message, MessageType, Stream};
 use owo_colors::Style;
-use rand::Rng;
 use tokio::io::AsyncBufReadExt;
 
 use anyhow::Result;
@@ -11,18 +15,41 @@ use argh::FromArgs;
 use task::Task;
 
 use crate::{
+  analyze::{ResourceUsage, UsageMonitor},
+  config::{Config, MaxProcesses, ProcessOptions},
+  control::ControlSocket,
+  dependency::{Dependency, DependencyCondition},
   input_router::{resolve_target, InputRouter},
   message::SenderType,
-  messenger::print_message,
+  messenger::{print_json, print_message, OutputFormat, PrefixFormat, StderrStyle},
+  process_log::ProcessLog,
+  readiness::ReadyCheck,
+  signal::KillSignal,
+  task::TaskStatus,
+  watch::{FileWatcher, WatchSet},
 };
 
+mod analyze;
+mod color;
 mod command;
 mod command_expander;
+mod config;
+mod control;
+mod dependency;
+mod dotenv;
 mod input_router;
+mod junit;
 mod message;
 mod messenger;
+mod process_log;
+mod readiness;
 mod scheduler;
+mod signal;
+mod status;
 mod task;
+mod timeout;
+mod tui;
+mod watch;
 
 fn default_restart_tries() -> i64 {
   0
@@ -42,9 +69,26 @@ fn default_success() -> String {
 fn default_timestamp_format() -> String {
   String::from("%Y-%m-%d %H:%M:%S")
 }
+fn default_kill_signal() -> String {
+  "SIGTERM".to_string()
+}
+fn default_kill_timeout() -> u64 {
+  5000
+}
+fn default_ready_timeout() -> u64 {
+  60000
+}
+fn default_tail_lines() -> usize {
+  50
+}
 
 #[derive(FromArgs)]
 /// Launch some commands concurrently
+#[argh(
+  note = "Separate commands with --then to run them in stages, each one \
+               starting once the one before it succeeded.\n\
+               Run `mlti ctl --help` to control a session started with --control-socket."
+)]
 pub struct Commands {
   /// names of processes
   #[argh(option, short = 'n')]
@@ -62,30 +106,70 @@ pub struct Commands {
   #[argh(switch)]
   kill_others_on_fail: bool,
 
-  /// how many times a process will attempt to restart.
+  /// signal sent to processes when killing them: SIGTERM, SIGINT, SIGKILL or
+  /// SIGHUP.
+  #[argh(option, default = "default_kill_signal()")]
+  kill_signal: String,
+
+  /// milliseconds to wait after the kill signal before sending SIGKILL.
+  #[argh(option, default = "default_kill_timeout()")]
+  kill_timeout: u64,
+
+  /// how many times a process that fails will be restarted. Negative values
+  /// restart forever.
   #[argh(option, default = "default_restart_tries()")]
   restart_tries: i64,
 
+  /// restart processes whenever they exit, not only on a non-zero exit code.
+  #[argh(switch)]
+  restart_always: bool,
+
   /// amount of time to delay between restart attempts.
   #[argh(option, default = "default_restart_after()")]
   restart_after: i64,
 
-  /// prefixed used in logging for each process.
+  /// prefix template for each line, built from tokens in braces: index,
+  /// command, name, pid, time, restarts, stream or none.
   #[argh(option, short = 'p')]
   prefix: Option<String>,
 
-  /// max number of characters of prefix that are shown.
+  /// max number of characters of the command shown in the prefix.
   #[argh(option, short = 'l', default = "default_prefix_length()")]
   prefix_length: i16,
 
+  /// comma-separated prefix colors, one per process: names, #hex, bgColor,
+  /// modifiers such as .bold or .dim, or auto (default: auto).
+  #[argh(option, short = 'c')]
+  prefix_colors: Option<String>,
+
   /// how many process should run at once.
   #[argh(option, short = 'm')]
   max_processes: Option<String>,
 
+  /// run one command at a time, in order, stopping at the first failure.
+  #[argh(switch)]
+  sequential: bool,
+
+  /// with --sequential or --then, go on to the next command or stage even
+  /// if one fails.
+  #[argh(switch)]
+  continue_on_error: bool,
+
   /// print raw output of process only.
   #[argh(switch, short = 'r')]
   raw: bool,
 
+  /// output format: text (default) or json, one JSON object per line for
+  /// every output line and process event, then a summary.
+  #[argh(option)]
+  output: Option<String>,
+
+  /// show a full-screen dashboard instead of line output: every process
+  /// with its state and output, and keys to restart, stop and send input
+  /// to them.
+  #[argh(switch)]
+  tui: bool,
+
   /// disable color output.
   #[argh(switch)]
   no_color: bool,
@@ -94,23 +178,79 @@ pub struct Commands {
   #[argh(switch, short = 'g')]
   group: bool,
 
-  /// comma-separated list of process indices or names to hide output from
+  /// comma-separated list of process indices or names to hide output from;
+  /// add :stdout or :stderr to hide just one stream, e.g. api:stdout
   #[argh(option)]
   hide: Option<String>,
 
-  /// processes to run
+  /// text printed before each line a process writes to stderr.
+  #[argh(option)]
+  stderr_marker: Option<String>,
+
+  /// color for lines a process writes to stderr, e.g. red or yellow.dim.
+  #[argh(option)]
+  stderr_color: Option<String>,
+
+  /// processes to run, or the names of processes to run from the config file
   #[argh(positional)]
   processes: Vec<String>,
 
+  /// working directory for a process: "target=dir". Repeatable.
+  #[argh(option)]
+  cwd: Vec<String>,
+
+  /// environment variable for a process: "target:KEY=VALUE". Repeatable.
+  #[argh(option)]
+  env: Vec<String>,
+
+  /// dotenv file loaded into every process. Repeatable.
+  #[argh(option)]
+  env_file: Vec<String>,
+
+  /// directory to log each process's output to, one NAME.log file per
+  /// process, uncolored and timestamped.
+  #[argh(option)]
+  log_dir: Option<String>,
+
+  /// write a JUnit XML report to this path with one testcase per process,
+  /// including the end of a failed process's output.
+  #[argh(option)]
+  junit_report: Option<String>,
+
+  /// listen on this Unix socket for JSON requests to list, start, stop and
+  /// restart processes, send them input or follow their output. See mlti
+  /// ctl.
+  #[argh(option)]
+  control_socket: Option<String>,
+
+  /// hide each process's output while it runs and print it in one block
+  /// only if the process fails; successful processes just report their
+  /// exit.
+  #[argh(switch)]
+  show_output_on_failure: bool,
+
+  /// how many of a process's latest output lines are kept for
+  /// --show-output-on-failure and --junit-report (default: 50).
+  #[argh(option, default = "default_tail_lines()")]
+  tail_lines: usize,
+
+  /// path to a TOML config file (default: mlti.toml, if present)
+  #[argh(option)]
+  config: Option<String>,
+
   /// print version
   #[argh(switch, short = 'v')]
   #[allow(dead_code)]
   version: bool,
 
-  /// timestamp format for logging
+  /// chrono format for the time in --prefix and --timestamps
   #[argh(option, short = 't', default = "default_timestamp_format()")]
   timestamp_format: String,
 
+  /// start every prefixed line with the time it was printed
+  #[argh(switch)]
+  timestamps: bool,
+
   /// pad all prefixes to the same width to align output columns
   #[argh(switch)]
   pad_prefix: bool,
@@ -123,6 +263,21 @@ pub struct Commands {
   #[argh(switch)]
   timings: bool,
 
+  /// sample each process's CPU time, memory and disk I/O, along with
+  /// everything it starts, and print a table of them after completion.
+  #[argh(switch)]
+  analyze: bool,
+
+  /// write the --analyze figures to this path as JSON. Implies --analyze.
+  #[argh(option)]
+  analyze_report: Option<String>,
+
+  /// fill placeholders in the commands with the arguments after --: a
+  /// number in braces for that argument, @ in braces for each of them
+  /// quoted, * in braces for all of them as one argument.
+  #[argh(switch, short = 'P')]
+  passthrough_arguments: bool,
+
   /// path to package.json for wildcard expansion
   #[argh(option)]
   manifest_path: Option<String>,
@@ -131,54 +286,154 @@ pub struct Commands {
   #[argh(switch, short = 'i')]
   handle_input: bool,
 
+  /// make a process wait for others: "target=dep[@condition],..." where
+  /// condition is success (default), completed or ready. Repeatable.
+  #[argh(option)]
+  depends_on: Vec<String>,
+
+  /// how to tell a process is ready: "target=check" where check is
+  /// log:<regex>, tcp:[host:]port, http://host[:port]/path or file:<path>.
+  /// Repeatable.
+  #[argh(option)]
+  ready: Vec<String>,
+
+  /// restart a process when files change: "target=glob", relative to the
+  /// process's working directory. A directory covers everything in it.
+  /// Repeatable.
+  #[argh(option)]
+  watch: Vec<String>,
+
+  /// stop a process with the kill signal once it has run this long, e.g.
+  /// 30s or 10m: "target=duration" for one process, or just the duration
+  /// for all of them. 0 means no limit. Repeatable.
+  #[argh(option)]
+  timeout: Vec<String>,
+
+  /// milliseconds to wait for a --ready check to pass before giving up.
+  #[argh(option, default = "default_ready_timeout()")]
+  ready_timeout: u64,
+
   /// set which process receives input by default (name or index). Implies --handle-input.
   #[argh(option)]
   default_input_target: Option<String>,
+
+  /// shell used to run each command (default: "sh -c", "cmd /C" on Windows)
+  #[argh(option)]
+  shell: Option<String>,
+
+  /// run commands directly instead of through a shell, splitting them into
+  /// arguments with POSIX quoting rules.
+  #[argh(switch)]
+  no_shell: bool,
 }
 
 #[derive(Clone)]
 pub struct MltiConfig {
   pub kill_others: bool,
   pub kill_others_on_fail: bool,
+  pub kill_signal: KillSignal,
+  pub kill_timeout: u64,
+  pub ready_timeout: u64,
   pub restart_tries: i64,
   pub restart_after: i64,
+  pub restart_always: bool,
+  /// How long the process may run before it's stopped; only set per
+  /// process, by `for_process`.
+  pub timeout: Option<Duration>,
   pub prefix: Option<String>,
   pub prefix_length: i16,
   pub max_processes: i32,
   pub raw: bool,
+  pub output: OutputFormat,
+  /// Show the `--tui` dashboard instead of printing lines.
+  pub tui: bool,
   pub no_color: bool,
   pub group: bool,
   pub timestamp_format: String,
+  pub timestamps: bool,
   pub pad_prefix: bool,
   pub timings: bool,
+  /// Sample each process's resource usage for the `--analyze` table.
+  pub analyze: bool,
+  /// Where to write the `--analyze` figures as JSON, if anywhere.
+  pub analyze_report: Option<PathBuf>,
+  /// Where to write the JUnit report, if anywhere.
+  pub junit_report: Option<PathBuf>,
+  /// Where to listen for `mlti ctl` requests, if anywhere.
+  pub control_socket: Option<PathBuf>,
+  /// Hold back process output, printing it only if the process fails.
+  pub show_output_on_failure: bool,
+  /// How many of their latest output lines tasks keep (see `TaskTiming`);
+  /// 0 when nothing needs them.
+  pub tail_lines: usize,
   pub hide_list: Vec<HideTarget>,
+  pub stderr_style: StderrStyle,
   pub handle_input: bool,
+  /// Shell argv that each command string is appended to; `None` runs
+  /// commands directly.
+  pub shell: Option<Vec<String>>,
+}
+
+impl MltiConfig {
+  /// This config with a single process's restart overrides and timeout
+  /// applied.
+  pub fn for_process(&self, options: &ProcessOptions) -> MltiConfig {
+    let mut config = self.clone();
+    config.restart_tries = options.restart_tries.unwrap_or(config.restart_tries);
+    config.restart_after = options.restart_after.unwrap_or(config.restart_after);
+    config.restart_always = options.restart_always.unwrap_or(config.restart_always);
+    config.timeout = options.timeout.filter(|timeout| !timeout.is_zero());
+    config
+  }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum HideTarget {
   Index(usize),
   Name(String),
+  /// Only one output stream of a process, as in `--hide api:stderr`.
+  Stream(Box<HideTarget>, Stream),
 }
 
 impl HideTarget {
-  pub fn matches(&self, index: Option<usize>, name: &str) -> bool {
+  pub fn matches(
+    &self,
+    index: Option<usize>,
+    name: &str,
+    stream: Option<Stream>,
+  ) -> bool {
     match self {
       HideTarget::Index(i) => index == Some(*i),
       HideTarget::Name(n) => n == name,
+      HideTarget::Stream(target, s) => {
+        stream == Some(*s) && target.matches(index, name, stream)
+      }
     }
   }
 }
 
-pub fn is_hidden_by(list: &[HideTarget], index: Option<usize>, name: &str) -> bool {
-  list.iter().any(|t| t.matches(index, name))
+pub fn is_hidden_by(
+  list: &[HideTarget],
+  index: Option<usize>,
+  name: &str,
+  stream: Option<Stream>,
+) -> bool {
+  list.iter().any(|t| t.matches(index, name, stream))
 }
 
 pub struct CommandParser {
   pub names: Vec<Option<String>>,
   pub processes: Vec<String>,
+  pub dependencies: Vec<Vec<Dependency>>,
+  /// The `--then` stage of each process, or its position with
+  /// `--sequential`; all 0 without either.
+  pub stages: Vec<usize>,
+  pub ready_checks: Vec<Option<ReadyCheck>>,
+  pub watch_sets: Vec<Option<WatchSet>>,
+  pub process_options: Vec<ProcessOptions>,
   pub mlti_config: MltiConfig,
   pub default_input_target: Option<String>,
+  success: String,
   success_condition: SuccessCondition,
 }
 
@@ -209,45 +464,165 @@ fn env_parse<T: std::str::FromStr>(key: &str) -> Option<T> {
 }
 
 impl CommandParser {
-  pub fn new(commands: Commands) -> Result<Self, String> {
-    let success_condition = SuccessCondition::parse(&commands.success)?;
-    let handle_input =
-      commands.handle_input || commands.default_input_target.is_some();
-
-    // For boolean switches: CLI true means explicitly set; otherwise fall back to env var.
-    let kill_others =
-      commands.kill_others || env_bool("MLTI_KILL_OTHERS").unwrap_or(false);
+  pub fn new(mut commands: Commands) -> Result<Self, String> {
+    // Settings are taken from, in order: the command line when it differs
+    // from the default, `MLTI_*` env vars, the config file, the default.
+    let config = match config::find_config(commands.config.take()) {
+      Some(path) => Config::load(&path).map_err(|e| format!("{:#}", e))?,
+      None => Config::default(),
+    };
+
+    let success = if commands.success != default_success() {
+      commands.success
+    } else {
+      config.success.clone().unwrap_or(commands.success)
+    };
+    let success_condition = SuccessCondition::parse(&success)?;
+    let default_input_target = commands
+      .default_input_target
+      .or_else(|| config.default_input_target.clone());
+    let tui = commands.tui || env_bool("MLTI_TUI").or(config.tui).unwrap_or(false);
+    // The dashboard owns the terminal, so processes always get their input
+    // through it.
+    let handle_input = commands.handle_input
+      || config.handle_input.unwrap_or(false)
+      || default_input_target.is_some()
+      || tui;
+
+    // For boolean switches: CLI true means explicitly set; otherwise fall back
+    // to the env var, then the config file.
+    let kill_others = commands.kill_others
+      || env_bool("MLTI_KILL_OTHERS")
+        .or(config.kill_others)
+        .unwrap_or(false);
     let kill_others_on_fail = commands.kill_others_on_fail
-      || env_bool("MLTI_KILL_OTHERS_ON_FAIL").unwrap_or(false);
-    let raw = commands.raw || env_bool("MLTI_RAW").unwrap_or(false);
+      || env_bool("MLTI_KILL_OTHERS_ON_FAIL")
+        .or(config.kill_others_on_fail)
+        .unwrap_or(false);
+    let raw = commands.raw || env_bool("MLTI_RAW").or(config.raw).unwrap_or(false);
+    let output = commands
+      .output
+      .or_else(|| std::env::var("MLTI_OUTPUT").ok())
+      .or_else(|| config.output.clone())
+      .map(|spec| OutputFormat::parse(&spec))
+      .transpose()?;
+    // `--raw` only changes text output.
+    let output = match output.unwrap_or_default() {
+      OutputFormat::Json if tui => {
+        return Err("--tui can't be combined with --output json".to_string());
+      }
+      OutputFormat::Text if raw => OutputFormat::Raw,
+      output => output,
+    };
     let no_color = commands.no_color
-      || env_bool("MLTI_NO_COLOR").unwrap_or(false)
+      || env_bool("MLTI_NO_COLOR")
+        .or(config.no_color)
+        .unwrap_or(false)
       || std::env::var("NO_COLOR").is_ok_and(|v| !v.is_empty());
-    let group = commands.group || env_bool("MLTI_GROUP").unwrap_or(false);
-
-    // For options with defaults: if CLI value equals the default, try the env var.
+    let group =
+      commands.group || env_bool("MLTI_GROUP").or(config.group).unwrap_or(false);
+    let restart_always = commands.restart_always
+      || env_bool("MLTI_RESTART_ALWAYS")
+        .or(config.restart_always)
+        .unwrap_or(false);
+    let pad_prefix = commands.pad_prefix || config.pad_prefix.unwrap_or(false);
+    let timings = commands.timings || config.timings.unwrap_or(false);
+    let sequential = commands.sequential
+      || env_bool("MLTI_SEQUENTIAL")
+        .or(config.sequential)
+        .unwrap_or(false);
+    let continue_on_error = commands.continue_on_error
+      || env_bool("MLTI_CONTINUE_ON_ERROR")
+        .or(config.continue_on_error)
+        .unwrap_or(false);
+    let timestamps = commands.timestamps
+      || env_bool("MLTI_TIMESTAMPS")
+        .or(config.timestamps)
+        .unwrap_or(false);
+
+    // For options with defaults: if CLI value equals the default, try the env
+    // var and then the config file.
     let restart_tries = if commands.restart_tries != default_restart_tries() {
       commands.restart_tries
     } else {
-      env_parse::<i64>("MLTI_RESTART_TRIES").unwrap_or(commands.restart_tries)
+      env_parse::<i64>("MLTI_RESTART_TRIES")
+        .or(config.restart_tries)
+        .unwrap_or(commands.restart_tries)
     };
 
     let restart_after = if commands.restart_after != default_restart_after() {
       commands.restart_after
     } else {
-      env_parse::<i64>("MLTI_RESTART_AFTER").unwrap_or(commands.restart_after)
+      env_parse::<i64>("MLTI_RESTART_AFTER")
+        .or(config.restart_after)
+        .unwrap_or(commands.restart_after)
+    };
+
+    let kill_signal = if commands.kill_signal != default_kill_signal() {
+      commands.kill_signal
+    } else {
+      std::env::var("MLTI_KILL_SIGNAL")
+        .ok()
+        .or_else(|| config.kill_signal.clone())
+        .unwrap_or(commands.kill_signal)
+    };
+    let kill_signal = KillSignal::parse(&kill_signal)?;
+
+    let kill_timeout = if commands.kill_timeout != default_kill_timeout() {
+      commands.kill_timeout
+    } else {
+      env_parse::<u64>("MLTI_KILL_TIMEOUT")
+        .or(config.kill_timeout)
+        .unwrap_or(commands.kill_timeout)
+    };
+
+    let ready_timeout = if commands.ready_timeout != default_ready_timeout() {
+      commands.ready_timeout
+    } else {
+      env_parse::<u64>("MLTI_READY_TIMEOUT")
+        .or(config.ready_timeout)
+        .unwrap_or(commands.ready_timeout)
     };
 
     let prefix_length = if commands.prefix_length != default_prefix_length() {
       commands.prefix_length
     } else {
-      env_parse::<i16>("MLTI_PREFIX_LENGTH").unwrap_or(commands.prefix_length)
+      env_parse::<i16>("MLTI_PREFIX_LENGTH")
+        .or(config.prefix_length)
+        .unwrap_or(commands.prefix_length)
     };
 
-    // For Option<String> fields: CLI Some wins; otherwise try env var.
+    // For Option<String> fields: CLI Some wins; otherwise try env var, then
+    // the config file.
     let prefix = commands
       .prefix
-      .or_else(|| std::env::var("MLTI_PREFIX").ok());
+      .or_else(|| std::env::var("MLTI_PREFIX").ok())
+      .or_else(|| config.prefix.clone());
+    let prefix_colors = commands
+      .prefix_colors
+      .or_else(|| std::env::var("MLTI_PREFIX_COLORS").ok())
+      .or_else(|| config.prefix_colors.clone())
+      .map(|spec| color::parse_prefix_colors(&spec))
+      .transpose()?
+      .unwrap_or_default();
+    let stderr_style = StderrStyle {
+      marker: commands
+        .stderr_marker
+        .or_else(|| std::env::var("MLTI_STDERR_MARKER").ok())
+        .or_else(|| config.stderr_marker.clone()),
+      color: commands
+        .stderr_color
+        .or_else(|| std::env::var("MLTI_STDERR_COLOR").ok())
+        .or_else(|| config.stderr_color.clone())
+        .map(|spec| {
+          color::PrefixColor::parse(&spec)
+            .map_err(|e| format!("--stderr-color: {}", e))
+        })
+        .transpose()?,
+    };
+    // Only names given on the command line conflict with config processes;
+    // `MLTI_NAMES` may be set for other runs.
+    let cli_names = commands.names.is_some();
     let names = commands.names.or_else(|| std::env::var("MLTI_NAMES").ok());
     let names_separator = if commands.names_seperator != default_names_separator() {
       commands.names_seperator
@@ -256,46 +631,326 @@ impl CommandParser {
     };
     let max_processes = commands
       .max_processes
-      .or_else(|| std::env::var("MLTI_MAX_PROCESSES").ok());
+      .or_else(|| std::env::var("MLTI_MAX_PROCESSES").ok())
+      .or_else(|| config.max_processes.as_ref().map(MaxProcesses::to_spec));
+    let manifest_path = commands
+      .manifest_path
+      .or_else(|| config.manifest_path.clone());
 
     // For timestamp_format: if CLI value equals the default, try the env var.
     let timestamp_format = if commands.timestamp_format != default_timestamp_format()
     {
       commands.timestamp_format
     } else {
-      std::env::var("MLTI_TIMESTAMP_FORMAT").unwrap_or(commands.timestamp_format)
+      std::env::var("MLTI_TIMESTAMP_FORMAT")
+        .ok()
+        .or_else(|| config.timestamp_format.clone())
+        .unwrap_or(commands.timestamp_format)
+    };
+
+    let no_shell = commands.no_shell
+      || env_bool("MLTI_NO_SHELL")
+        .or(config.no_shell)
+        .unwrap_or(false);
+    let shell = if no_shell {
+      None
+    } else {
+      let shell = commands
+        .shell
+        .or_else(|| std::env::var("MLTI_SHELL").ok())
+        .or_else(|| config.shell.clone())
+        .unwrap_or_else(default_shell);
+      Some(parse_shell(&shell)?)
     };
 
+    let mut hide_list = parse_hide_list(commands.hide);
+    let mut depends_on = commands.depends_on;
+    let mut ready = commands.ready;
+    let mut watch = commands.watch;
+    let passthrough_arguments = commands.passthrough_arguments
+      || env_bool("MLTI_PASSTHROUGH_ARGUMENTS")
+        .or(config.passthrough_arguments)
+        .unwrap_or(false);
+    let (positionals, passthrough) =
+      split_passthrough(commands.processes, passthrough_arguments);
+    // Arguments are filled in before shortcuts are expanded, so they can
+    // end up in a script's arguments too.
+    let fill = |command: &str| {
+      if passthrough_arguments {
+        expand_arguments(command, &passthrough)
+      } else {
+        command.to_string()
+      }
+    };
+    let (positionals, input_stages) = split_stages(positionals)?;
+
     // Expand all package manager shortcuts (both wildcard and non-wildcard).
-    let parsed_names = parse_names(names, names_separator);
-    let (processes, expanded_names) = command_expander::expand_commands(
-      commands.processes,
-      parsed_names,
-      commands.manifest_path,
-    )
-    .map_err(|e| e.to_string())?;
+    let (processes, expanded_names, mut process_options, stages) =
+      if config.processes.is_empty() {
+        let parsed_names = parse_names(names, names_separator);
+        let (processes, expanded_names, origins) =
+          command_expander::expand_commands_with_origins(
+            positionals.iter().map(|command| fill(command)).collect(),
+            parsed_names,
+            manifest_path,
+          )
+          .map_err(|e| e.to_string())?;
+        let options = vec![ProcessOptions::default(); processes.len()];
+        let stages = origins.iter().map(|&i| input_stages[i]).collect();
+        (processes, expanded_names, options, stages)
+      } else {
+        // With processes declared in the config file, positional arguments
+        // pick which of them to run.
+        if cli_names {
+          return Err(
+            "--names can't be used with processes from a config file; \
+           set `name` on each process instead"
+              .to_string(),
+          );
+        }
+        let selected = config.select(&positionals)?;
+
+        let mut processes = Vec::new();
+        // The process names each selected entry expanded to.
+        let mut produced: HashMap<&str, Vec<String>> = HashMap::new();
+        let mut expanded_names = Vec::new();
+        let mut process_options = Vec::new();
+        let mut stages = Vec::new();
+        for entry in &selected {
+          let stage = positionals
+            .iter()
+            .position(|name| name == &entry.name)
+            .map_or(0, |i| input_stages[i]);
+          let (commands, auto_names) = command_expander::expand_commands(
+            vec![fill(&entry.command)],
+            vec![],
+            manifest_path.clone(),
+          )
+          .map_err(|e| e.to_string())?;
+          let options = entry.options()?;
+          // A wildcard entry can expand to several scripts; tell them apart by
+          // their script name.
+          let single = commands.len() == 1;
+          for (command, auto_name) in commands.into_iter().zip(auto_names) {
+            let name = match auto_name {
+              Some(auto) if !single => format!("{}:{}", entry.name, auto),
+              _ => entry.name.clone(),
+            };
+            if options.hide {
+              hide_list.push(HideTarget::Index(processes.len()));
+            }
+            produced.entry(&entry.name).or_default().push(name.clone());
+            processes.push(command);
+            expanded_names.push(Some(name));
+            process_options.push(options.clone());
+            stages.push(stage);
+          }
+        }
+
+        // Settings of a wildcard entry apply to every process it expanded
+        // to, and depending on it means depending on all of them.
+        for entry in &selected {
+          // Dependencies on processes that weren't selected are dropped, so a
+          // subset can be run on its own.
+          let deps: Vec<String> = entry
+            .depends_on
+            .iter()
+            .flat_map(|dep| {
+              let (name, _) = dependency::split_condition(dep);
+              let suffix = &dep[name.len()..];
+              produced
+                .get(name.trim())
+                .into_iter()
+                .flatten()
+                .map(move |process| format!("{}{}", process, suffix))
+            })
+            .collect();
+          for name in produced.get(entry.name.as_str()).into_iter().flatten() {
+            if !deps.is_empty() {
+              depends_on.push(format!("{}={}", name, deps.join(",")));
+            }
+            if let Some(check) = &entry.ready {
+              ready.push(format!("{}={}", name, check));
+            }
+            for glob in &entry.watch {
+              watch.push(format!("{}={}", name, glob));
+            }
+          }
+        }
+        (processes, expanded_names, process_options, stages)
+      };
+
+    config::apply_cli_options(
+      &mut process_options,
+      &commands.cwd,
+      &commands.env,
+      &expanded_names,
+    )?;
+    let default_timeout = std::env::var("MLTI_TIMEOUT")
+      .ok()
+      .filter(|v| !v.is_empty())
+      .or_else(|| config.timeout.clone());
+    timeout::apply_timeouts(
+      &mut process_options,
+      &commands.timeout,
+      default_timeout.as_deref(),
+      &expanded_names,
+    )?;
+    // A missing directory would otherwise only show up as a failed spawn.
+    for (i, options) in process_options.iter().enumerate() {
+      if let Some(cwd) = options.cwd.as_ref().filter(|cwd| !cwd.is_dir()) {
+        let name = expanded_names[i].clone().unwrap_or_else(|| i.to_string());
+        return Err(format!(
+          "Working directory for \"{}\" does not exist: {}",
+          name,
+          cwd.display()
+        ));
+      }
+    }
+
+    // Files from the command line replace those in the config file.
+    let env_files: Vec<PathBuf> = if commands.env_file.is_empty() {
+      config.env_file.clone()
+    } else {
+      commands.env_file.iter().map(PathBuf::from).collect()
+    };
+    config::apply_env_files(&mut process_options, &env_files)?;
+
+    // A process's own `color` wins over its `--prefix-colors` entry.
+    for (i, options) in process_options.iter_mut().enumerate() {
+      let color = match options.color {
+        Some(color) => color.resolve(i),
+        None => color::color_for(&prefix_colors, i),
+      };
+      options.color = Some(color);
+    }
+
+    // Every process without a `log-file` of its own logs to `--log-dir`.
+    let log_dir = commands
+      .log_dir
+      .or_else(|| std::env::var("MLTI_LOG_DIR").ok())
+      .map(PathBuf::from)
+      .or_else(|| config.log_dir.clone());
+    process_log::assign_log_files(
+      &mut process_options,
+      log_dir.as_deref(),
+      &expanded_names,
+    )?;
+
+    let analyze_report = commands
+      .analyze_report
+      .or_else(|| std::env::var("MLTI_ANALYZE_REPORT").ok())
+      .map(PathBuf::from)
+      .or_else(|| config.analyze_report.clone());
+    let analyze = commands.analyze
+      || analyze_report.is_some()
+      || env_bool("MLTI_ANALYZE").or(config.analyze).unwrap_or(false);
+    let junit_report = commands
+      .junit_report
+      .or_else(|| std::env::var("MLTI_JUNIT_REPORT").ok())
+      .map(PathBuf::from)
+      .or_else(|| config.junit_report.clone());
+    let control_socket = commands
+      .control_socket
+      .or_else(|| std::env::var("MLTI_CONTROL_SOCKET").ok())
+      .map(PathBuf::from)
+      .or_else(|| config.control_socket.clone());
+    let show_output_on_failure = commands.show_output_on_failure
+      || env_bool("MLTI_SHOW_OUTPUT_ON_FAILURE")
+        .or(config.show_output_on_failure)
+        .unwrap_or(false);
+    let tail_lines = if commands.tail_lines != default_tail_lines() {
+      commands.tail_lines
+    } else {
+      env_parse::<usize>("MLTI_TAIL_LINES")
+        .or(config.tail_lines)
+        .unwrap_or(commands.tail_lines)
+    };
+    let tail_lines = if show_output_on_failure || junit_report.is_some() {
+      tail_lines
+    } else {
+      0
+    };
+
+    // Resolve dependencies now so unknown names and cycles are reported
+    // before anything starts.
+    let mut dependencies =
+      dependency::parse_depends_on(&depends_on, &expanded_names, processes.len())?;
+    let stages: Vec<usize> = if sequential {
+      (0..processes.len()).collect()
+    } else {
+      stages
+    };
+    if stages.iter().any(|&stage| stage > 0) {
+      let condition = if continue_on_error {
+        DependencyCondition::Completed
+      } else {
+        DependencyCondition::Success
+      };
+      dependency::add_stage_dependencies(
+        &mut dependencies,
+        &stages,
+        condition,
+        &expanded_names,
+      )?;
+    }
+    let ready_checks =
+      readiness::parse_ready_checks(&ready, &expanded_names, processes.len())?;
+    let cwds: Vec<Option<PathBuf>> =
+      process_options.iter().map(|o| o.cwd.clone()).collect();
+    let watch_sets = watch::parse_watch_specs(&watch, &expanded_names, &cwds)?;
+
+    // Without a shell we tokenize commands ourselves, so surface quoting
+    // mistakes now rather than when the process is spawned.
+    if shell.is_none() {
+      for process in &processes {
+        split_command(process)?;
+      }
+    }
 
     Ok(Self {
       names: expanded_names,
       processes,
-      default_input_target: commands.default_input_target,
+      dependencies,
+      stages,
+      ready_checks,
+      watch_sets,
+      process_options,
+      default_input_target,
+      success,
       success_condition,
       mlti_config: MltiConfig {
         group,
         kill_others,
         kill_others_on_fail,
+        kill_signal,
+        kill_timeout,
+        ready_timeout,
         restart_tries,
         restart_after,
+        restart_always,
+        timeout: None,
         prefix,
         prefix_length,
         max_processes: parse_max_processes(max_processes),
         raw,
+        output,
+        tui,
         no_color,
         timestamp_format,
-        pad_prefix: commands.pad_prefix,
-        timings: commands.timings,
-        hide_list: parse_hide_list(commands.hide),
+        timestamps,
+        pad_prefix,
+        timings,
+        analyze,
+        analyze_report,
+        junit_report,
+        control_socket,
+        show_output_on_failure,
+        tail_lines,
+        hide_list,
+        stderr_style,
         handle_input,
+        shell,
       },
     })
   }
@@ -309,11 +964,28 @@ impl CommandParser {
   pub fn get_mlti_config(&self) -> MltiConfig {
     self.mlti_config.clone()
   }
+  /// The `--success` condition as given.
+  pub fn success(&self) -> &str {
+    &self.success
+  }
 
   /// Compute the overall exit code from a collection of per-task
   /// `(index, code)` pairs, applying the configured success condition.
-  pub fn evaluate_exit_code(&self, exit_codes: &[(usize, i32)]) -> i32 {
-    self.success_condition.evaluate(exit_codes, &self.names)
+  /// `skipped` lists tasks that never ran because a dependency failed.
+  pub fn evaluate_exit_code(
+    &self,
+    exit_codes: &[(usize, i32)],
+    skipped: &[usize],
+  ) -> i32 {
+    let code = self.success_condition.evaluate(exit_codes, &self.names);
+    if code == 0
+      && self
+        .success_condition
+        .fails_on_skipped(skipped, &self.names)
+    {
+      return 1;
+    }
+    code
   }
 }
 
@@ -374,6 +1046,22 @@ impl SuccessCondition {
     }
   }
 
+  /// Whether any of the `skipped` tasks is one this condition requires to
+  /// succeed. `first`/`last` only look at tasks that actually finished.
+  fn fails_on_skipped(&self, skipped: &[usize], names: &[Option<String>]) -> bool {
+    let excluded = match self {
+      Self::All => None,
+      Self::NotCommandIndex(idx) => Some(*idx),
+      Self::NotCommandName(name) => names
+        .iter()
+        .position(|n| n.as_deref() == Some(name.as_str())),
+      // A skipped target is already missing from the exit codes, which
+      // `evaluate` treats as a failure.
+      _ => return false,
+    };
+    skipped.iter().any(|i| Some(*i) != excluded)
+  }
+
   fn evaluate(&self, exit_codes: &[(usize, i32)], names: &[Option<String>]) -> i32 {
     if exit_codes.is_empty() {
       return 1;
@@ -408,15 +1096,97 @@ impl SuccessCondition {
   }
 }
 
-fn pad_process_names(processes: &mut [Process]) {
-  let max_len = processes
+/// Stands in for `--then` while argh parses the command line: argh keeps
+/// positional arguments in order but can't say where a flag appeared among
+/// them. Arguments can't contain a NUL byte, so no command clashes with it.
+const STAGE_MARKER: &str = "\0then";
+/// Marks where `--` was among the commands, so the arguments after it can
+/// be told apart for `-P`.
+const PASSTHROUGH_MARKER: &str = "\0--";
+
+/// Parse the command line like `argh::from_env`, keeping each `--then` in
+/// place among the commands as a `STAGE_MARKER` and the arguments after
+/// `--` behind a `PASSTHROUGH_MARKER`.
+fn parse_commands(args: &[String]) -> Commands {
+  let command = std::path::Path::new(&args[0])
+    .file_name()
+    .and_then(|name| name.to_str())
+    .unwrap_or(&args[0]);
+  let end = args
     .iter()
-    .map(|p| p.name.chars().count())
-    .max()
-    .unwrap_or(0);
-  for p in processes.iter_mut() {
-    p.name = format!("{:<width$}", p.name, width = max_len);
+    .position(|arg| arg == "--")
+    .unwrap_or(args.len());
+  let options: Vec<&str> = args[1..end]
+    .iter()
+    .map(|arg| match arg.as_str() {
+      "--then" => STAGE_MARKER,
+      arg => arg,
+    })
+    .collect();
+  let mut commands =
+    Commands::from_args(&[command], &options).unwrap_or_else(|early_exit| {
+      std::process::exit(match early_exit.status {
+        Ok(()) => {
+          println!("{}", early_exit.output);
+          0
+        }
+        Err(()) => {
+          eprintln!(
+            "{}\nRun {} --help for more information.",
+            early_exit.output, command
+          );
+          1
+        }
+      })
+    });
+  if end < args.len() {
+    commands.processes.push(PASSTHROUGH_MARKER.to_string());
+    commands.processes.extend(args[end + 1..].iter().cloned());
+  }
+  commands
+}
+
+/// Split off the arguments after `PASSTHROUGH_MARKER` when `-P` is on.
+/// Without it they are commands like any other.
+fn split_passthrough(
+  args: Vec<String>,
+  enabled: bool,
+) -> (Vec<String>, Vec<String>) {
+  let Some(marker) = args.iter().position(|arg| arg == PASSTHROUGH_MARKER) else {
+    return (args, Vec::new());
+  };
+  let mut commands = args;
+  let rest = commands.split_off(marker + 1);
+  commands.pop();
+  if enabled {
+    (commands, rest)
+  } else {
+    commands.extend(rest);
+    (commands, Vec::new())
+  }
+}
+
+/// Split the positional arguments at each `STAGE_MARKER`, returning the
+/// commands and the stage each one belongs to.
+fn split_stages(args: Vec<String>) -> Result<(Vec<String>, Vec<usize>), String> {
+  let mut commands = Vec::new();
+  let mut stages = Vec::new();
+  let mut stage = 0;
+  for arg in args {
+    if arg == STAGE_MARKER {
+      if stages.last() != Some(&stage) {
+        return Err("--then needs commands before and after it".to_string());
+      }
+      stage += 1;
+    } else {
+      commands.push(arg);
+      stages.push(stage);
+    }
+  }
+  if stage > 0 && stages.last() != Some(&stage) {
+    return Err("--then needs commands before and after it".to_string());
   }
+  Ok((commands, stages))
 }
 
 pub fn parse_names(names: Option<String>, seperator: String) -> Vec<String> {
@@ -428,20 +1198,43 @@ pub fn parse_names(names: Option<String>, seperator: String) -> Vec<String> {
 }
 
 pub fn parse_hide_list(hide: Option<String>) -> Vec<HideTarget> {
+  let target = |s: &str| match s.parse::<usize>() {
+    Ok(i) => HideTarget::Index(i),
+    Err(_) => HideTarget::Name(s.to_string()),
+  };
   match hide {
     Some(h) => h
       .split(',')
       .map(|s| s.trim())
       .filter(|s| !s.is_empty())
-      .map(|s| match s.parse::<usize>() {
-        Ok(i) => HideTarget::Index(i),
-        Err(_) => HideTarget::Name(s.to_string()),
+      .map(|s| {
+        // `api:stdout` hides one stream; names may themselves contain `:`.
+        let stream = match s.rsplit_once(':') {
+          Some((t, "stdout")) => Some((t, Stream::Stdout)),
+          Some((t, "stderr")) => Some((t, Stream::Stderr)),
+          _ => None,
+        };
+        match stream {
+          Some((t, stream)) => {
+            HideTarget::Stream(Box::new(target(t.trim())), stream)
+          }
+          None => target(s),
+        }
       })
       .collect(),
     None => vec![],
   }
 }
 
+/// Tokenize a `--shell` value such as `"bash -c"` into its argv.
+fn parse_shell(shell: &str) -> Result<Vec<String>, String> {
+  let argv = split_command(shell)?;
+  if argv.is_empty() {
+    return Err("Invalid shell: value must not be empty".to_string());
+  }
+  Ok(argv)
+}
+
 pub fn parse_max_processes(max_processes: Option<String>) -> i32 {
   match max_processes {
     Some(max) => {
@@ -481,7 +1274,11 @@ fn resolve_default_target(
 
 #[tokio::main]
 async fn main() -> Result<()> {
-  let commands: Commands = argh::from_env();
+  let args: Vec<String> = std::env::args().collect();
+  if args.get(1).map(String::as_str) == Some("ctl") {
+    std::process::exit(control::ctl(&args[2..]).await);
+  }
+  let commands = parse_commands(&args);
   let red_style = Style::new().red();
   let bold_green_style = Style::new().bold().green();
 
@@ -490,47 +1287,109 @@ async fn main() -> Result<()> {
     std::process::exit(1);
   });
   let mlti_config = arg_parser.get_mlti_config();
+  if mlti_config.tui && !std::io::stdout().is_terminal() {
+    eprintln!("--tui needs an interactive terminal");
+    std::process::exit(1);
+  }
+  let started_at = chrono::Local::now();
+  let started = std::time::Instant::now();
+
+  // Open log files up front so a bad path is reported before anything runs.
+  let mut logs = arg_parser
+    .process_options
+    .iter()
+    .map(|options| {
+      options
+        .log_file
+        .as_ref()
+        .map(|path| ProcessLog::open(path, mlti_config.timestamp_format.clone()))
+        .transpose()
+    })
+    .collect::<Result<Vec<_>, _>>()
+    .unwrap_or_else(|e| {
+      eprintln!("{}", e);
+      std::process::exit(1);
+    });
 
+  let mut prefix = PrefixFormat {
+    template: mlti_config.prefix.clone(),
+    commands: arg_parser.processes.clone(),
+    command_length: mlti_config.prefix_length.max(0) as usize,
+    time_format: mlti_config.timestamp_format.clone(),
+    timestamps: mlti_config.timestamps,
+    width: 0,
+  };
+  if mlti_config.pad_prefix {
+    let names: Vec<String> = (0..arg_parser.len())
+      .map(|i| arg_parser.names[i].clone().unwrap_or_else(|| i.to_string()))
+      .collect();
+    prefix.pad_to_widest(&names);
+  }
   let mut shutdown_messenger = messenger::Messenger::new(
-    mlti_config.raw,
+    mlti_config.output,
     mlti_config.no_color,
     arg_parser.len(),
     false,
     vec![],
+    StderrStyle::default(),
+    PrefixFormat::default(),
   );
   let shutdown_tx = shutdown_messenger.get_sender();
   let mut messenger = messenger::Messenger::new(
-    mlti_config.raw,
+    mlti_config.output,
     mlti_config.no_color,
     arg_parser.len(),
     mlti_config.group,
     mlti_config.hide_list.clone(),
+    mlti_config.stderr_style.clone(),
+    prefix.clone(),
   );
-  let message_tx = messenger.get_sender();
+  // With a control socket, messages pass through its hub on their way to
+  // the messenger (see `Hub::tap`).
+  let output_tx = messenger.get_sender();
 
   let hide_list = mlti_config.hide_list.clone();
-  let messenger_handle = tokio::spawn(async move {
-    messenger
-      .listen(
-        |message: Message, raw: bool, no_color: bool| match message.type_ {
-          MessageType::Error | MessageType::Text => {
-            if !is_hidden_by(&hide_list, message.sender.index, &message.name) {
+  let stderr_style = mlti_config.stderr_style.clone();
+  let output = mlti_config.output;
+  let message_rx = messenger.get_receiver();
+  // With `--tui` the dashboard shows messages instead; it's started once
+  // the scheduler is up.
+  let messenger_handle = (!mlti_config.tui).then(|| {
+    tokio::spawn(async move {
+      messenger
+        .listen(|message: Message, raw: bool, no_color: bool| {
+          let hidden = is_hidden_by(
+            &hide_list,
+            message.sender.index,
+            &message.name,
+            message.sender.stream,
+          );
+          match message.type_ {
+            MessageType::Kill => 1,
+            _ if hidden => 0,
+            _ if output == OutputFormat::Json => {
+              print_json(&message);
+              0
+            }
+            MessageType::Error | MessageType::Text | MessageType::Ready => {
+              let stderr = stderr_style.for_message(&message).cloned();
+              let name = prefix.render(&message);
               print_message(
                 message.sender.type_,
-                message.name,
+                name,
                 message.data,
                 message.style,
+                stderr.as_ref(),
                 raw,
                 no_color,
               );
+              0
             }
-            0
+            _ => 0,
           }
-          MessageType::Kill => 1,
-          _ => 0,
-        },
-      )
-      .await;
+        })
+        .await;
+    })
   });
 
   let ctrlx_tx = shutdown_tx.clone();
@@ -547,34 +1406,83 @@ async fn main() -> Result<()> {
   })
   .expect("Error setting Ctrl-C handler");
 
+  // With `--output json` stdout is only JSON, so mlti's banners are left out.
+  let json = mlti_config.output == OutputFormat::Json;
+
   if arg_parser.is_empty() {
+    if !json {
+      print_message(
+        SenderType::Main,
+        "".into(),
+        "No processes to run. Goodbye! 👋".into(),
+        bold_green_style,
+        None,
+        mlti_config.raw,
+        mlti_config.no_color,
+      );
+    }
+    if let Some(handle) = messenger_handle {
+      handle.abort();
+    }
+    return Ok(());
+  }
+
+  if !json {
     print_message(
       SenderType::Main,
       "".into(),
-      "No processes to run. Goodbye! 👋".into(),
+      format!("\n{} {}\n", arg_parser.len(), "processes to run ✅"),
       bold_green_style,
+      None,
       mlti_config.raw,
       mlti_config.no_color,
     );
-    messenger_handle.abort();
-    return Ok(());
   }
 
-  print_message(
-    SenderType::Main,
-    "".into(),
-    format!("\n{} {}\n", arg_parser.len(), "processes to run ✅"),
-    bold_green_style,
-    mlti_config.raw,
-    mlti_config.no_color,
-  );
+  // Start watching before anything runs, so paths that can't be watched
+  // are reported up front.
+  let file_watcher = arg_parser
+    .watch_sets
+    .iter()
+    .any(Option::is_some)
+    .then(|| FileWatcher::new(arg_parser.watch_sets.clone()))
+    .transpose()
+    .unwrap_or_else(|e| {
+      eprintln!("{}", e);
+      std::process::exit(1);
+    });
+
+  let control_socket = mlti_config
+    .control_socket
+    .as_deref()
+    .map(ControlSocket::bind)
+    .transpose()
+    .unwrap_or_else(|e| {
+      eprintln!("{}", e);
+      std::process::exit(1);
+    });
+  let hub = control_socket
+    .as_ref()
+    .map(|_| control::Hub::new(arg_parser.len()));
+  let message_tx = match &hub {
+    Some(hub) => hub.tap(output_tx),
+    None => output_tx,
+  };
 
   let scheduler = std::sync::Arc::new(scheduler::Scheduler::new(
     shutdown_tx.clone(),
     mlti_config.max_processes,
     arg_parser.len() as i32,
+    arg_parser.dependencies.clone(),
+    // Watched processes and the dashboard can start processes again after
+    // they've all finished.
+    mlti_config.tui || file_watcher.is_some(),
   ));
 
+  if let Some(file_watcher) = file_watcher {
+    tokio::spawn(file_watcher.run(scheduler.get_control()));
+  }
+
   let input_router: Option<Arc<InputRouter>> = if mlti_config.handle_input {
     let default_target = resolve_default_target(
       &arg_parser.default_input_target,
@@ -593,8 +1501,6 @@ async fn main() -> Result<()> {
 
   // let mut unnamed_counter = -1;
 
-  let mut rng = rand::thread_rng();
-
   // let mut tasks: Vec<Task> = vec![];
   let task_queue = scheduler.get_task_queue();
   let kill_all = scheduler.get_kill_all();
@@ -604,37 +1510,88 @@ async fn main() -> Result<()> {
     scheduler_clone.run().await;
   });
 
+  if let (Some(socket), Some(hub)) = (control_socket, hub) {
+    tokio::spawn(socket.serve(control::Session {
+      names: arg_parser.names.clone(),
+      commands: arg_parser.processes.clone(),
+      hub,
+      control_tx: scheduler.get_control(),
+      input_router: input_router.clone(),
+    }));
+  }
+
+  let tui_handle = mlti_config.tui.then(|| {
+    let app = tui::App::new(
+      (0..arg_parser.len())
+        .map(|i| {
+          let name = arg_parser.names.get(i).cloned().flatten();
+          (
+            name.unwrap_or_else(|| i.to_string()),
+            arg_parser.processes[i].clone(),
+          )
+        })
+        .collect(),
+    );
+    let control_tx = scheduler.get_control();
+    let shutdown_tx = shutdown_tx.clone();
+    let input_router = input_router.clone();
+    tokio::spawn(async move {
+      let result = tui::run(
+        app,
+        message_rx,
+        control_tx,
+        shutdown_tx.clone(),
+        input_router,
+      )
+      .await;
+      if let Err(e) = result {
+        eprintln!("Could not run the dashboard: {}", e);
+        shutdown_tx
+          .send(Message::new(
+            MessageType::KillAll,
+            None,
+            None,
+            None,
+            build_message_sender(SenderType::Main, None, None),
+          ))
+          .ok();
+      }
+    })
+  });
+
+  let usage_monitor = mlti_config.analyze.then(UsageMonitor::start);
   let mut processes: Vec<Process> = Vec::with_capacity(arg_parser.len());
   for i in 0..arg_parser.len() {
-    let r = rng.gen_range(75..255);
-    let g = rng.gen_range(75..255);
-    let b = rng.gen_range(75..255);
     let name = arg_parser.names.get(i).cloned().flatten();
+    let options = &arg_parser.process_options[i];
 
-    let my_cmd = Process::new(
+    let mut my_cmd = Process::new(
       arg_parser.processes[i].clone(),
       name,
       i,
-      mlti_config.prefix.clone(),
-      mlti_config.prefix_length,
-      (r, g, b),
-      mlti_config.timestamp_format.clone(),
+      options.color.unwrap_or_default(),
+      mlti_config.shell.as_deref(),
     );
+    my_cmd.cwd = options.cwd.clone();
+    my_cmd.env.extend(options.env.iter().cloned());
     processes.push(my_cmd);
   }
 
-  if mlti_config.pad_prefix {
-    pad_process_names(&mut processes);
-  }
-
   for my_cmd in processes {
+    let ready_check = arg_parser.ready_checks[my_cmd.index].clone();
+    let log = logs[my_cmd.index].take();
+    let task_config =
+      mlti_config.for_process(&arg_parser.process_options[my_cmd.index]);
     task_queue
       .send_async(Task::new(
         my_cmd,
         message_tx.clone(),
         shutdown_tx.clone(),
-        mlti_config.to_owned(),
+        task_config,
         input_router.clone(),
+        ready_check,
+        log,
+        usage_monitor.clone(),
       ))
       .await
       .expect("Could not send task on channel.");
@@ -646,101 +1603,66 @@ async fn main() -> Result<()> {
   // In practice, interactive users may need to press Enter once after
   // all processes exit before mlti returns. This is a known tokio
   // limitation (tokio-rs/tokio#2466) and is acceptable for our use.
-  let stdin_reader_handle = if let Some(ref router) = input_router {
-    let router = router.clone();
-    Some(tokio::spawn(async move {
-      let stdin = tokio::io::stdin();
-      let mut reader = tokio::io::BufReader::new(stdin).lines();
-      while let Ok(Some(line)) = reader.next_line().await {
-        router.route(&line).await;
-      }
-    }))
-  } else {
-    None
+  let stdin_reader_handle =
+    if let Some(router) = input_router.as_ref().filter(|_| !mlti_config.tui) {
+      let router = router.clone();
+      Some(tokio::spawn(async move {
+        let stdin = tokio::io::stdin();
+        let mut reader = tokio::io::BufReader::new(stdin).lines();
+        while let Ok(Some(line)) = reader.next_line().await {
+          router.route(&line).await;
+        }
+      }))
+    } else {
+      None
+    };
+
+  // Kill requests only start the shutdown: the scheduler terminates every
+  // child and then reports `Complete`, which is what stops the messengers.
+  // That way "exited with signal" lines still make it to the output.
+  let stopping = Cell::new(false);
+  let request_stop = |reason: &str, raw: bool, no_color: bool| {
+    if stopping.replace(true) {
+      return;
+    }
+    if !json && !mlti_config.tui {
+      print_message(
+        SenderType::Main,
+        "".into(),
+        format!("\n{}", reason),
+        red_style,
+        None,
+        raw,
+        no_color,
+      );
+    }
+    kill_all
+      .send(())
+      .expect("Could not send kill signal on channel.");
   };
 
   shutdown_messenger
     .listen(
       |message: Message, raw: bool, no_color: bool| match message.type_ {
         MessageType::KillAll => {
-          print_message(
-            SenderType::Main,
-            "".into(),
-            format!("\n{}", "Killing all processes"),
-            red_style,
-            raw,
-            no_color,
-          );
-          message_tx
-            .send(message::Message::new(
-              message::MessageType::Kill,
-              None,
-              None,
-              None,
-              build_message_sender(SenderType::Main, None, None),
-            ))
-            .expect("Could not send kill signal on channel.");
-          kill_all
-            .send(())
-            .expect("Could not send kill signal on channel.");
-
-          1
+          request_stop("Killing all processes", raw, no_color);
+          0
         }
-
         MessageType::KillOthers => {
-          print_message(
-            SenderType::Main,
-            "".into(),
-            format!(
-              "\n{}",
-              "Kill others flag present, stopping other processes."
-            ),
-            red_style,
+          request_stop(
+            "Kill others flag present, stopping other processes.",
             raw,
             no_color,
           );
-          message_tx
-            .send(message::Message::new(
-              message::MessageType::Kill,
-              None,
-              None,
-              None,
-              build_message_sender(SenderType::Main, None, None),
-            ))
-            .expect("Could not send kill signal on channel.");
-          // messenger_handle.abort();
-          kill_all
-            .send(())
-            .expect("Could not send kill signal on channel.");
-
-          1
+          0
         }
         MessageType::KillAllOnError => {
-          print_message(
-            SenderType::Main,
-            "".into(),
-            format!(
-              "\n{}",
-              "Kill others on fail flag present, stopping other processes."
-            ),
-            red_style,
+          request_stop(
+            "Kill others on fail flag present, stopping other processes.",
             raw,
             no_color,
           );
-          message_tx
-            .send(message::Message::new(
-              message::MessageType::Kill,
-              None,
-              None,
-              None,
-              build_message_sender(SenderType::Main, None, None),
-            ))
-            .expect("Could not send kill signal on channel.");
-          // messenger_handle.abort();
-          kill_all
-            .send(())
-            .expect("Could not send kill signal on channel.");
-          1
+          0
         }
         MessageType::Complete => {
           message_tx
@@ -763,65 +1685,205 @@ async fn main() -> Result<()> {
   if let Some(handle) = stdin_reader_handle {
     handle.abort();
   }
-  messenger_handle.await.ok();
+  if let Some(handle) = messenger_handle {
+    handle.await.ok();
+  }
+  if let Some(handle) = tui_handle {
+    handle.await.ok();
+  }
   scheduler_handler.await.ok();
+  if let Some(path) = &mlti_config.control_socket {
+    control::remove_socket(path);
+  }
 
   let exit_codes = scheduler.get_exit_codes().await;
-  let exit_code = arg_parser.evaluate_exit_code(&exit_codes);
+  let skipped = scheduler.get_skipped().await;
+  let mut exit_code = arg_parser.evaluate_exit_code(&exit_codes, &skipped);
 
-  if mlti_config.timings {
+  if let Some(path) = &mlti_config.junit_report {
+    let mut timings = scheduler.get_timings().await;
+    timings.sort_by_key(|t| t.index);
+    let report = junit::junit_report(
+      &timings,
+      &arg_parser.names,
+      &started_at.format("%Y-%m-%dT%H:%M:%S").to_string(),
+      started.elapsed().as_secs_f64(),
+    );
+    // A missing report would otherwise go unnoticed in CI.
+    if let Err(e) = junit::write_junit_report(path, &report) {
+      eprintln!("{}", e);
+      exit_code = exit_code.max(1);
+    }
+  }
+
+  if let Some(path) = &mlti_config.analyze_report {
+    let mut timings = scheduler.get_timings().await;
+    timings.sort_by_key(|t| t.index);
+    let report = serde_json::json!({
+      "processes": process_summaries(&arg_parser, &timings),
+    });
+    if let Err(e) = analyze::write_analyze_report(path, &report) {
+      eprintln!("{}", e);
+      exit_code = exit_code.max(1);
+    }
+  }
+
+  if json {
     let mut timings = scheduler.get_timings().await;
-    let total_processes = arg_parser.len();
     timings.sort_by_key(|t| t.index);
+    print_json_summary(&arg_parser, &timings, exit_code);
+  } else if mlti_config.timings {
+    let mut timings = scheduler.get_timings().await;
+    let total_processes = arg_parser.len();
+    let staged = arg_parser.stages.iter().any(|&stage| stage > 0);
+    timings.sort_by_key(|t| (arg_parser.stages[t.index], t.index));
     print_message(
       SenderType::Main,
       "".into(),
       "\nTimings:".into(),
       bold_green_style,
+      None,
       mlti_config.raw,
       mlti_config.no_color,
     );
-    for t in &timings {
-      let style = if t.exit_code != 0 {
+    // With stages, each one gets a line of its own and its processes are
+    // listed under it.
+    let indent = if staged { "    " } else { "  " };
+    for (i, t) in timings.iter().enumerate() {
+      let stage = arg_parser.stages[t.index];
+      if staged && (i == 0 || arg_parser.stages[timings[i - 1].index] != stage) {
+        let members: Vec<&task::TaskTiming> = timings
+          .iter()
+          .filter(|t| arg_parser.stages[t.index] == stage)
+          .collect();
+        let (summary, passed) = stage_summary(&members);
+        print_message(
+          SenderType::Main,
+          "".into(),
+          format!("  Stage {}: {}", stage + 1, summary),
+          if passed { bold_green_style } else { red_style },
+          None,
+          mlti_config.raw,
+          mlti_config.no_color,
+        );
+      }
+      let status = match t.status {
+        TaskStatus::Exited => "",
+        TaskStatus::Killed => " (killed)",
+        TaskStatus::TimedOut => " (timed out)",
+        TaskStatus::Skipped => " (skipped)",
+      };
+      let style = if t.exit_code != 0 || t.status != TaskStatus::Exited {
         red_style
       } else {
         bold_green_style
       };
+      let restarts = match t.attempts.len().saturating_sub(1) {
+        0 => String::new(),
+        1 => " (1 restart)".to_string(),
+        n => format!(" ({} restarts)", n),
+      };
       print_message(
         SenderType::Main,
         "".into(),
         format!(
-          "  [{}] {} \u{2014} {:.2}s",
-          t.index, t.raw_cmd, t.duration_secs
+          "{}[{}] {} \u{2014} {:.2}s{}{}",
+          indent, t.index, t.raw_cmd, t.duration_secs, restarts, status
         ),
         style,
+        None,
         mlti_config.raw,
         mlti_config.no_color,
       );
+      if t.attempts.len() > 1 {
+        for (n, attempt) in t.attempts.iter().enumerate() {
+          print_message(
+            SenderType::Main,
+            "".into(),
+            format!(
+              "{}    attempt {}: exited with code {} after {:.2}s",
+              indent,
+              n + 1,
+              attempt.exit_code,
+              attempt.duration_secs
+            ),
+            style,
+            None,
+            mlti_config.raw,
+            mlti_config.no_color,
+          );
+        }
+      }
     }
-    if timings.len() < total_processes {
+    let completed = timings
+      .iter()
+      .filter(|t| t.status != TaskStatus::Killed)
+      .count();
+    if completed < total_processes {
       print_message(
         SenderType::Main,
         "".into(),
         format!(
           "  ({} process(es) killed before completion)",
-          total_processes - timings.len()
+          total_processes - completed
         ),
         red_style,
+        None,
         mlti_config.raw,
         mlti_config.no_color,
       );
     }
   }
 
-  print_message(
-    SenderType::Main,
-    "".into(),
-    format!("\n{}", "Goodbye! 👋"),
-    bold_green_style,
-    mlti_config.raw,
-    mlti_config.no_color,
-  );
+  if !json && mlti_config.analyze {
+    let mut timings = scheduler.get_timings().await;
+    timings.sort_by_key(|t| t.index);
+    let rows: Vec<(String, &ResourceUsage)> = timings
+      .iter()
+      .filter_map(|t| {
+        let usage = t.usage.as_ref()?;
+        // Named processes go by their name, others by their command cut
+        // short as in the `{command}` prefix.
+        let label = arg_parser.names[t.index].clone().unwrap_or_else(|| {
+          let length = mlti_config.prefix_length.max(0) as usize;
+          t.raw_cmd.chars().take(length).collect()
+        });
+        Some((format!("[{}] {}", t.index, label), usage))
+      })
+      .collect();
+    print_message(
+      SenderType::Main,
+      "".into(),
+      "\nResource usage:".into(),
+      bold_green_style,
+      None,
+      mlti_config.raw,
+      mlti_config.no_color,
+    );
+    for line in analyze::usage_table(&rows) {
+      print_message(
+        SenderType::Main,
+        "".into(),
+        line,
+        Style::new(),
+        None,
+        mlti_config.raw,
+        mlti_config.no_color,
+      );
+    }
+  }
+
+  if !json {
+    print_message(
+      SenderType::Main,
+      "".into(),
+      format!("\n{}", "Goodbye! 👋"),
+      bold_green_style,
+      None,
+      mlti_config.raw,
+      mlti_config.no_color,
+    );
+  }
 
   if exit_code != 0 {
     std::process::exit(exit_code);
@@ -830,79 +1892,75 @@ async fn main() -> Result<()> {
   Ok(())
 }
 
-#[cfg(test)]
-mod tests {
-  use super::*;
-  use command::Process;
-
-  // ---- pad_process_names ----
-
-  fn make_process(name: &str) -> Process {
-    Process::new(
-      "echo hello".to_string(),
-      Some(name.to_string()),
-      0,
-      None,
-      10,
-      (255, 255, 255),
-      "%Y-%m-%d %H:%M:%S".to_string(),
-    )
+/// How a stage went, for `--timings`, and whether it passed. Its
+/// processes run side by side, so it took as long as the slowest one.
+fn stage_summary(timings: &[&task::TaskTiming]) -> (String, bool) {
+  let duration = timings.iter().map(|t| t.duration_secs).fold(0.0, f64::max);
+  if timings.iter().all(|t| t.status == TaskStatus::Skipped) {
+    return ("skipped".to_string(), false);
   }
-
-  #[test]
-  fn pad_process_names_aligns_to_longest() {
-    let mut processes = vec![
-      make_process("api"),
-      make_process("frontend"),
-      make_process("db"),
-    ];
-    pad_process_names(&mut processes);
-    assert_eq!(processes[0].name, "api     ");
-    assert_eq!(processes[1].name, "frontend");
-    assert_eq!(processes[2].name, "db      ");
-    let widths: Vec<usize> =
-      processes.iter().map(|p| p.name.chars().count()).collect();
-    assert!(widths.windows(2).all(|w| w[0] == w[1]));
+  let failed = timings
+    .iter()
+    .filter(|t| {
+      !matches!(t.status, TaskStatus::Killed | TaskStatus::Skipped)
+        && t.exit_code != 0
+    })
+    .count();
+  if failed > 0 {
+    return (format!("{} failed after {:.2}s", failed, duration), false);
   }
-
-  #[test]
-  fn pad_process_names_single_process() {
-    let mut processes = vec![make_process("solo")];
-    pad_process_names(&mut processes);
-    assert_eq!(processes[0].name, "solo");
+  if timings.iter().any(|t| t.status != TaskStatus::Exited) {
+    return (format!("stopped after {:.2}s", duration), false);
   }
+  (format!("passed in {:.2}s", duration), true)
+}
 
-  #[test]
-  fn pad_process_names_empty_list() {
-    let mut processes: Vec<Process> = vec![];
-    pad_process_names(&mut processes);
-    assert!(processes.is_empty());
-  }
+/// The last line of `--output json`: how each process ended and whether
+/// the run met its `--success` condition.
+fn print_json_summary(
+  arg_parser: &CommandParser,
+  timings: &[task::TaskTiming],
+  exit_code: i32,
+) {
+  let summary = serde_json::json!({
+    "type": "summary",
+    "condition": arg_parser.success(),
+    "success": exit_code == 0,
+    "exit_code": exit_code,
+    "processes": process_summaries(arg_parser, timings),
+  });
+  println!("{}", summary);
+}
 
-  #[test]
-  fn pad_process_names_equal_length() {
-    let mut processes = vec![
-      make_process("aaa"),
-      make_process("bbb"),
-      make_process("ccc"),
-    ];
-    pad_process_names(&mut processes);
-    assert_eq!(processes[0].name, "aaa");
-    assert_eq!(processes[1].name, "bbb");
-    assert_eq!(processes[2].name, "ccc");
-  }
+/// How each process ended, as JSON, with what it used under `--analyze`.
+fn process_summaries(
+  arg_parser: &CommandParser,
+  timings: &[task::TaskTiming],
+) -> Vec<serde_json::Value> {
+  timings
+    .iter()
+    .map(|t| {
+      let name = arg_parser.names.get(t.index).cloned().flatten();
+      let mut summary = serde_json::json!({
+        "index": t.index,
+        "name": name.unwrap_or_else(|| t.index.to_string()),
+        "command": t.raw_cmd,
+        "status": t.status.name(),
+        "exit_code": t.exit_code,
+        "duration_secs": t.duration_secs,
+        "restarts": t.attempts.len().saturating_sub(1),
+      });
+      if let Some(usage) = &t.usage {
+        summary["usage"] = usage.to_json();
+      }
+      summary
+    })
+    .collect()
+}
 
-  #[test]
-  fn pad_process_names_unicode_chars() {
-    let mut processes = vec![make_process("café"), make_process("db")];
-    pad_process_names(&mut processes);
-    assert_eq!(processes[0].name, "café");
-    assert_eq!(processes[1].name, "db  ");
-    assert_eq!(
-      processes[0].name.chars().count(),
-      processes[1].name.chars().count()
-    );
-  }
+#[cfg(test)]
+mod tests {
+  use super::*;
 
   // ---- SuccessCondition::parse ----
 
@@ -1084,6 +2142,79 @@ mod tests {
     );
   }
 
+  #[test]
+  fn evaluate_counts_a_timeout_as_a_failure() {
+    // A process that handled the kill signal and exited 0 still timed out.
+    let exit_codes = codes(&[(0, 0), (1, task::TIMEOUT_EXIT_CODE)]);
+    assert_eq!(
+      SuccessCondition::All.evaluate(&exit_codes, &[]),
+      task::TIMEOUT_EXIT_CODE
+    );
+    assert_eq!(
+      SuccessCondition::NotCommandIndex(1).evaluate(&exit_codes, &[]),
+      0
+    );
+  }
+
+  // ---- stage_summary ----
+
+  fn stage_timing(exit_code: i32, status: TaskStatus) -> task::TaskTiming {
+    task::TaskTiming {
+      index: 0,
+      raw_cmd: "cmd".to_string(),
+      exit_code,
+      duration_secs: 1.0,
+      attempts: vec![],
+      status,
+      output: vec![],
+      usage: None,
+    }
+  }
+
+  #[test]
+  fn stage_summary_does_not_count_skipped_processes_as_failed() {
+    let failed = stage_timing(2, TaskStatus::Exited);
+    let skipped = stage_timing(1, TaskStatus::Skipped);
+    let (summary, passed) = stage_summary(&[&failed, &skipped]);
+    assert_eq!(summary, "1 failed after 1.00s");
+    assert!(!passed);
+
+    let passed_run = stage_timing(0, TaskStatus::Exited);
+    let (summary, passed) = stage_summary(&[&passed_run, &skipped]);
+    assert_eq!(summary, "stopped after 1.00s");
+    assert!(!passed);
+  }
+
+  // ---- SuccessCondition::fails_on_skipped ----
+
+  #[test]
+  fn fails_on_skipped_all_fails_on_any_skip() {
+    assert!(SuccessCondition::All.fails_on_skipped(&[2], &[]));
+    assert!(!SuccessCondition::All.fails_on_skipped(&[], &[]));
+  }
+
+  #[test]
+  fn fails_on_skipped_not_command_ignores_excluded() {
+    let names: Vec<Option<String>> = vec![Some("build".into()), Some("e2e".into())];
+    assert!(!SuccessCondition::NotCommandIndex(1).fails_on_skipped(&[1], &[]));
+    assert!(SuccessCondition::NotCommandIndex(1).fails_on_skipped(&[0, 1], &[]));
+    assert!(
+      !SuccessCondition::NotCommandName("e2e".into()).fails_on_skipped(&[1], &names)
+    );
+  }
+
+  #[test]
+  fn fails_on_skipped_first_last_and_command_ignore_skips() {
+    // `command-N` on a skipped task already fails via its missing exit code.
+    assert!(!SuccessCondition::First.fails_on_skipped(&[0], &[]));
+    assert!(!SuccessCondition::Last.fails_on_skipped(&[0], &[]));
+    assert!(!SuccessCondition::CommandIndex(0).fails_on_skipped(&[0], &[]));
+    assert_eq!(
+      SuccessCondition::CommandIndex(0).evaluate(&[(1, 0)], &[]),
+      1
+    );
+  }
+
   // ── env_bool ────────────────────────────────────────────────────────────────
 
   fn with_env_var<F: FnOnce()>(key: &str, value: &str, f: F) {
@@ -1183,6 +2314,58 @@ mod tests {
     });
   }
 
+  // ── split_stages ─────────────────────────────────────────────────────────────
+
+  #[test]
+  fn split_stages_numbers_each_command() {
+    let args = [
+      "lint",
+      STAGE_MARKER,
+      "build",
+      STAGE_MARKER,
+      "server",
+      "worker",
+    ]
+    .map(String::from)
+    .to_vec();
+    let (commands, stages) = split_stages(args).unwrap();
+    assert_eq!(commands, vec!["lint", "build", "server", "worker"]);
+    assert_eq!(stages, vec![0, 1, 2, 2]);
+
+    let (_, stages) = split_stages(vec!["a".into(), "b".into()]).unwrap();
+    assert_eq!(stages, vec![0, 0]);
+    for args in [
+      vec![STAGE_MARKER, "a"],
+      vec!["a", STAGE_MARKER],
+      vec!["a", STAGE_MARKER, STAGE_MARKER, "b"],
+    ] {
+      let args = args.into_iter().map(String::from).collect();
+      assert!(split_stages(args).is_err());
+    }
+  }
+
+  // ── split_passthrough ────────────────────────────────────────────────────────
+
+  #[test]
+  fn split_passthrough_only_with_the_flag() {
+    let args = || {
+      ["vite", "tsc", PASSTHROUGH_MARKER, "--port", "4000"]
+        .map(String::from)
+        .to_vec()
+    };
+    let (commands, rest) = split_passthrough(args(), true);
+    assert_eq!(commands, vec!["vite", "tsc"]);
+    assert_eq!(rest, vec!["--port", "4000"]);
+
+    let (commands, rest) = split_passthrough(args(), false);
+    assert_eq!(commands, vec!["vite", "tsc", "--port", "4000"]);
+    assert!(rest.is_empty());
+
+    let (commands, rest) = split_passthrough(vec!["vite".into()], true);
+    assert_eq!(commands, vec!["vite"]);
+    assert!(rest.is_empty());
+  }
+
   // ── parse_names ──────────────────────────────────────────────────────────────
 
   #[test]
@@ -1232,6 +2415,23 @@ mod tests {
     assert_eq!(default_timestamp_format(), "%Y-%m-%d %H:%M:%S");
   }
 
+  // ---- parse_shell ----
+
+  #[test]
+  fn parse_shell_splits_program_and_flags() {
+    assert_eq!(parse_shell("bash -c").unwrap(), vec!["bash", "-c"]);
+    assert_eq!(
+      parse_shell("'/opt/my shell/zsh' -lc").unwrap(),
+      vec!["/opt/my shell/zsh", "-lc"]
+    );
+  }
+
+  #[test]
+  fn parse_shell_rejects_empty() {
+    assert!(parse_shell("").is_err());
+    assert!(parse_shell("   ").is_err());
+  }
+
   // ---- parse_hide_list / HideTarget / is_hidden_by ----
 
   #[test]
@@ -1299,30 +2499,53 @@ mod tests {
   #[test]
   fn hide_target_index_matches_only_by_index() {
     let t = HideTarget::Index(1);
-    assert!(t.matches(Some(1), "anything"));
-    assert!(!t.matches(Some(0), "anything"));
-    assert!(!t.matches(None, "anything"));
+    assert!(t.matches(Some(1), "anything", None));
+    assert!(!t.matches(Some(0), "anything", None));
+    assert!(!t.matches(None, "anything", None));
   }
 
   #[test]
   fn hide_target_name_matches_only_by_name() {
     let t = HideTarget::Name("build".into());
-    assert!(t.matches(Some(5), "build"));
-    assert!(t.matches(None, "build"));
-    assert!(!t.matches(Some(5), "serve"));
+    assert!(t.matches(Some(5), "build", None));
+    assert!(t.matches(None, "build", None));
+    assert!(!t.matches(Some(5), "serve", None));
   }
 
   #[test]
   fn is_hidden_by_empty_list_hides_nothing() {
-    assert!(!is_hidden_by(&[], Some(0), "foo"));
+    assert!(!is_hidden_by(&[], Some(0), "foo", None));
   }
 
   #[test]
   fn is_hidden_by_matches_any_entry() {
     let list = vec![HideTarget::Index(0), HideTarget::Name("foo".into())];
-    assert!(is_hidden_by(&list, Some(0), "bar"));
-    assert!(is_hidden_by(&list, Some(2), "foo"));
-    assert!(!is_hidden_by(&list, Some(2), "bar"));
-    assert!(!is_hidden_by(&list, None, "bar"));
+    assert!(is_hidden_by(&list, Some(0), "bar", None));
+    assert!(is_hidden_by(&list, Some(2), "foo", None));
+    assert!(!is_hidden_by(&list, Some(2), "bar", None));
+    assert!(!is_hidden_by(&list, None, "bar", None));
+  }
+
+  #[test]
+  fn parse_hide_list_stream_suffix() {
+    assert_eq!(
+      parse_hide_list(Some("api:stdout, 1:stderr,web:dev".into())),
+      vec![
+        HideTarget::Stream(Box::new(HideTarget::Name("api".into())), Stream::Stdout),
+        HideTarget::Stream(Box::new(HideTarget::Index(1)), Stream::Stderr),
+        HideTarget::Name("web:dev".into()),
+      ]
+    );
+  }
+
+  #[test]
+  fn hide_target_stream_matches_only_that_stream() {
+    let t =
+      HideTarget::Stream(Box::new(HideTarget::Name("api".into())), Stream::Stdout);
+    assert!(t.matches(Some(0), "api", Some(Stream::Stdout)));
+    assert!(!t.matches(Some(0), "api", Some(Stream::Stderr)));
+    // mlti's own messages about the process stay visible.
+    assert!(!t.matches(Some(0), "api", None));
+    assert!(!t.matches(Some(1), "web", Some(Stream::Stdout)));
   }
 }
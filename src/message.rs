@@ -2,7 +2,9 @@ use std::time::{SystemTime, UNIX_EPOCH};
 
 use owo_colors::Style;
 
-#[derive(Debug)]
+use crate::color::PrefixColor;
+
+#[derive(Clone, Debug)]
 pub enum MessageType {
   Kill,
   Text,
@@ -11,15 +13,59 @@ pub enum MessageType {
   KillOthers,
   KillAllOnError,
   Complete,
+  /// A process passed its `--ready` check.
+  Ready,
+  /// A lifecycle event with no status line of its own; only reported by
+  /// `--output json`.
+  Lifecycle,
+  /// Output held back by `--show-output-on-failure`, in `Message::replay`,
+  /// to be printed as one block.
+  Replay,
+  /// A task is done for good: its process won't be restarted, or it was
+  /// skipped. Drives `--group` output.
+  Finished,
+}
+
+/// A change in a process's lifecycle. Status lines about it carry one so
+/// `--output json` can report it as data rather than text.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+  /// The child was started; its pid is on the sender.
+  Spawned,
+  /// The child exited, with the signal that killed it if there was one.
+  Exited { code: i32, signal: Option<String> },
+  /// The process will be started again after `delay_ms`.
+  Restarted { attempt: i64, delay_ms: i64 },
+  /// mlti sent the child's process group a signal to stop it.
+  Killed { signal: String },
+  /// The child ran longer than its `--timeout` and is being stopped.
+  TimedOut { after_ms: u64 },
+  /// The process will never run.
+  Skipped { reason: String },
 }
 
+/// Which of a child's output streams a line came from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stream {
+  Stdout,
+  Stderr,
+}
+
+#[derive(Clone)]
 pub struct MessageSender {
   pub index: Option<usize>,
   #[allow(dead_code)]
   pub name: String,
   pub type_: SenderType,
+  /// Set on output lines from a child; `None` for mlti's own messages.
+  pub stream: Option<Stream>,
+  /// The child's pid, once it has been spawned.
+  pub pid: Option<u32>,
+  /// How many times the process had been restarted.
+  pub restarts: i64,
 }
 
+#[derive(Clone)]
 pub enum SenderType {
   Process,
   Scheduler,
@@ -28,14 +74,19 @@ pub enum SenderType {
   Main,
 }
 
+#[derive(Clone)]
 pub struct Message {
   pub name: String,
-  #[allow(dead_code)]
+  /// When the message was created, in milliseconds since the epoch.
   pub timestamp: u64,
   pub data: String,
   pub style: Style,
   pub type_: MessageType,
   pub sender: MessageSender,
+  /// Set on status lines that report a lifecycle event.
+  pub event: Option<Event>,
+  /// The held back lines of a `Replay` message.
+  pub replay: Vec<Message>,
 }
 
 impl Message {
@@ -43,12 +94,10 @@ impl Message {
     type_: MessageType,
     name: Option<String>,
     data: Option<String>,
-    color: Option<(u8, u8, u8)>,
+    color: Option<PrefixColor>,
     sender: MessageSender,
   ) -> Self {
-    let color = color.unwrap_or((255, 255, 255));
-
-    let style = Style::new().truecolor(color.0, color.1, color.2);
+    let style = color.unwrap_or(PrefixColor::rgb((255, 255, 255))).style();
 
     Self {
       name: name.unwrap_or_default(),
@@ -60,6 +109,8 @@ impl Message {
       style,
       type_,
       sender,
+      event: None,
+      replay: Vec::new(),
     }
   }
 }
@@ -80,5 +131,8 @@ pub fn build_message_sender(
     index,
     name,
     type_: sender_type,
+    stream: None,
+    pid: None,
+    restarts: 0,
   }
 }
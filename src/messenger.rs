@@ -1,27 +1,206 @@
+use chrono::TimeZone;
 use flume::{Receiver, Sender};
 use owo_colors::{OwoColorize, Style};
+use serde_json::{json, Value};
 use std::collections::VecDeque;
 
-use crate::message::{Message, MessageType, SenderType};
+use crate::color::PrefixColor;
+use crate::message::{
+  build_message_sender, Event, Message, MessageType, SenderType, Stream,
+};
+use crate::process_log::strip_ansi;
 use crate::{is_hidden_by, HideTarget};
 
+/// How the output messenger writes messages.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+  /// `[name]: data` lines.
+  #[default]
+  Text,
+  /// Only the processes' own output (`--raw`).
+  Raw,
+  /// One JSON object per line and event (`--output json`).
+  Json,
+}
+
+impl OutputFormat {
+  /// Parse `--output`; `Raw` comes from `--raw` instead.
+  pub fn parse(s: &str) -> Result<Self, String> {
+    match s.trim().to_lowercase().as_str() {
+      "text" => Ok(Self::Text),
+      "json" => Ok(Self::Json),
+      _ => Err(format!(
+        "Invalid --output '{}'. Expected: text or json",
+        s.trim()
+      )),
+    }
+  }
+}
+
+/// How lines a process wrote to stderr are set apart from its stdout when
+/// prefixed output is printed.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StderrStyle {
+  /// Printed before each stderr line.
+  pub marker: Option<String>,
+  /// Applied to the marker and the line.
+  pub color: Option<PrefixColor>,
+}
+
+impl StderrStyle {
+  /// The style for a message: `Some` only for a child's stderr lines.
+  pub fn for_message(&self, message: &Message) -> Option<&StderrStyle> {
+    (message.sender.stream == Some(Stream::Stderr)).then_some(self)
+  }
+}
+
+/// Tokens understood in a `--prefix` template. A template that is just a
+/// token name, like `--prefix pid`, renders that token.
+const PREFIX_TOKENS: &[&str] = &[
+  "index", "command", "name", "pid", "time", "restarts", "stream", "none",
+];
+
+/// Renders the prefix of each line. A `--prefix` template is filled in per
+/// message, so values that change while a process runs (its pid across
+/// restarts, the time) are always current.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PrefixFormat {
+  /// The `--prefix` template; the process name is shown when `None`.
+  pub template: Option<String>,
+  /// Raw command of each process by index, for `{command}`.
+  pub commands: Vec<String>,
+  /// Most characters of `{command}` shown (`--prefix-length`).
+  pub command_length: usize,
+  /// chrono format for `{time}` and `--timestamps`.
+  pub time_format: String,
+  /// Put the time at the start of every prefix (`--timestamps`).
+  pub timestamps: bool,
+  /// Least number of characters in a prefix; shorter ones are padded with
+  /// spaces (`--pad-prefix`, see `pad_to_widest`).
+  pub width: usize,
+}
+
+impl PrefixFormat {
+  /// The prefix for a message. mlti's own banners have no name and so no
+  /// prefix.
+  pub fn render(&self, message: &Message) -> String {
+    if message.name.is_empty() {
+      return String::new();
+    }
+    let prefix = match self.template.as_deref() {
+      Some(token) if PREFIX_TOKENS.contains(&token) => self.token(token, message),
+      Some(template) => self.fill(template, message),
+      None => message.name.clone(),
+    };
+    let prefix = if self.timestamps {
+      format!("{} {}", self.time(message), prefix)
+    } else {
+      prefix
+    };
+    format!("{:<width$}", prefix, width = self.width)
+  }
+
+  /// Pad every prefix to the widest one rendered for the processes named
+  /// in `names`. mlti's own pid stands in for theirs, which aren't known
+  /// yet.
+  pub fn pad_to_widest(&mut self, names: &[String]) {
+    self.width = names
+      .iter()
+      .enumerate()
+      .map(|(index, name)| {
+        let mut sender =
+          build_message_sender(SenderType::Process, Some(index), None);
+        sender.pid = Some(std::process::id());
+        sender.stream = Some(Stream::Stdout);
+        let message =
+          Message::new(MessageType::Text, Some(name.clone()), None, None, sender);
+        self.render(&message).chars().count()
+      })
+      .max()
+      .unwrap_or(0);
+  }
+
+  /// Replace each `{token}` in the template. Anything else in braces is
+  /// printed as is.
+  fn fill(&self, template: &str, message: &Message) -> String {
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+      out.push_str(&rest[..start]);
+      let after = &rest[start + 1..];
+      match after.split_once('}') {
+        Some((token, tail)) if PREFIX_TOKENS.contains(&token) => {
+          out.push_str(&self.token(token, message));
+          rest = tail;
+        }
+        _ => {
+          out.push('{');
+          rest = after;
+        }
+      }
+    }
+    out.push_str(rest);
+    out
+  }
+
+  fn token(&self, token: &str, message: &Message) -> String {
+    let sender = &message.sender;
+    match token {
+      "index" => sender.index.map(|i| i.to_string()).unwrap_or_default(),
+      "command" => sender
+        .index
+        .and_then(|i| self.commands.get(i))
+        .map(|command| command.chars().take(self.command_length).collect())
+        .unwrap_or_default(),
+      "name" => message.name.clone(),
+      "pid" => sender.pid.map(|pid| pid.to_string()).unwrap_or_default(),
+      "time" => self.time(message),
+      "restarts" => sender.restarts.to_string(),
+      "stream" => match sender.stream {
+        Some(Stream::Stdout) => "stdout".to_string(),
+        Some(Stream::Stderr) => "stderr".to_string(),
+        None => String::new(),
+      },
+      _ => String::new(),
+    }
+  }
+
+  /// When the message was created, in `time_format`.
+  fn time(&self, message: &Message) -> String {
+    chrono::Local
+      .timestamp_millis_opt(message.timestamp as i64)
+      .single()
+      .unwrap_or_else(chrono::Local::now)
+      .format(&self.time_format)
+      .to_string()
+  }
+}
+
 pub struct Messenger {
   sender: Sender<Message>,
   receiver: Receiver<Message>,
-  raw: bool,
+  output: OutputFormat,
   no_color: bool,
   group: bool,
   message_queue: Vec<VecDeque<Message>>,
+  // In group mode: which tasks have finished, and the process whose output
+  // is printed as it arrives. Later ones are queued until it finishes.
+  finished: Vec<bool>,
+  current: usize,
   hide_list: Vec<HideTarget>,
+  stderr_style: StderrStyle,
+  prefix: PrefixFormat,
 }
 
 impl Messenger {
   pub fn new(
-    raw: bool,
+    output: OutputFormat,
     no_color: bool,
     num_commands: usize,
     group: bool,
     hide_list: Vec<HideTarget>,
+    stderr_style: StderrStyle,
+    prefix: PrefixFormat,
   ) -> Self {
     let (sender, receiver) = flume::unbounded::<Message>();
 
@@ -32,16 +211,25 @@ impl Messenger {
     Self {
       sender,
       receiver,
-      raw,
+      output,
       no_color,
       group,
       message_queue: message_queues,
+      finished: vec![false; num_commands],
+      current: 0,
       hide_list,
+      stderr_style,
+      prefix,
     }
   }
   pub fn get_sender(&self) -> Sender<Message> {
     self.sender.clone()
   }
+  /// The receiving end, for consumers that show messages some other way
+  /// than `listen`, like `--tui`.
+  pub fn get_receiver(&self) -> Receiver<Message> {
+    self.receiver.clone()
+  }
   pub async fn listen<F>(&mut self, handler: F)
   where
     F: Fn(Message, bool, bool) -> usize,
@@ -54,62 +242,141 @@ impl Messenger {
           match message.type_ {
             MessageType::Kill => {
               while let Ok(m) = self.receiver.try_recv() {
-                if let Some(i) = m.sender.index {
-                  self.message_queue[i].push_back(m);
-                }
+                self.group_message(m);
               }
               self.flush();
               break;
             }
-            _ => {
-              if let Some(i) = message.sender.index {
-                self.message_queue[i].push_back(message);
-              }
-            }
+            _ => self.group_message(message),
           }
         } else {
-          let val = handler(message, self.raw, self.no_color);
-          if val == 1 {
+          let raw = self.output == OutputFormat::Raw;
+          // A replay's lines are handled back to back so they stay together.
+          let stop = unpack_replay(message)
+            .into_iter()
+            .any(|message| handler(message, raw, self.no_color) == 1);
+          if stop {
             break;
           }
         }
-        // handler(message, self.raw, self.no_color);
       }
     }
   }
-  pub fn flush(&mut self) {
-    for queue in self.message_queue.iter_mut() {
-      while let Some(message) = queue.pop_front() {
-        if is_hidden_by(&self.hide_list, message.sender.index, &message.name) {
-          continue;
+  /// Group mode: print the current process's output straight away and
+  /// queue everyone else's until the processes before them have finished.
+  /// A process that was moved past and runs again (restarted by `--watch`
+  /// or the control socket) is printed straight away too.
+  fn group_message(&mut self, message: Message) {
+    for message in unpack_replay(message) {
+      let Some(i) = message.sender.index else {
+        continue;
+      };
+      match message.type_ {
+        MessageType::Finished => {
+          self.finished[i] = true;
+          self.advance();
         }
-        print_message(
-          message.sender.type_,
-          message.name,
-          message.data,
-          message.style,
-          self.raw,
-          self.no_color,
-        );
+        _ if i <= self.current => self.print(message),
+        _ => self.message_queue[i].push_back(message),
+      }
+    }
+  }
+  /// Move past every finished process in order, printing what the next
+  /// one has queued up so far.
+  fn advance(&mut self) {
+    while self.finished.get(self.current) == Some(&true) {
+      self.current += 1;
+      let queued = self
+        .message_queue
+        .get_mut(self.current)
+        .map(std::mem::take)
+        .unwrap_or_default();
+      for message in queued {
+        self.print(message);
+      }
+    }
+  }
+  /// Print whatever is still queued, in process order.
+  pub fn flush(&mut self) {
+    for queue in std::mem::take(&mut self.message_queue) {
+      for message in queue {
+        self.print(message);
       }
     }
   }
+  fn print(&self, message: Message) {
+    if is_hidden_by(
+      &self.hide_list,
+      message.sender.index,
+      &message.name,
+      message.sender.stream,
+    ) {
+      return;
+    }
+    if self.output == OutputFormat::Json {
+      print_json(&message);
+      return;
+    }
+    if let MessageType::Lifecycle = message.type_ {
+      return;
+    }
+    let stderr = self.stderr_style.for_message(&message).cloned();
+    let name = self.prefix.render(&message);
+    print_message(
+      message.sender.type_,
+      name,
+      message.data,
+      message.style,
+      stderr.as_ref(),
+      self.output == OutputFormat::Raw,
+      self.no_color,
+    );
+  }
 }
 
+/// The lines held in a `Replay` message, or just the message itself.
+pub fn unpack_replay(message: Message) -> Vec<Message> {
+  match message.type_ {
+    MessageType::Replay => message.replay,
+    _ => vec![message],
+  }
+}
+
+/// Print one message. `stderr` is set for lines a child wrote to stderr:
+/// in raw mode they go to mlti's own stderr, otherwise they're marked and
+/// colored as configured.
 pub fn print_message(
   sender_type: SenderType,
   name: String,
   data: String,
   style: Style,
+  stderr: Option<&StderrStyle>,
   raw: bool,
   no_color: bool,
 ) {
   let mut message = String::new();
   if raw {
     if let SenderType::Process = sender_type {
+      if stderr.is_some() {
+        eprintln!("{}", data);
+        return;
+      }
       message = data;
     }
   } else {
+    let data = match stderr {
+      Some(stderr) => {
+        let data = match &stderr.marker {
+          Some(marker) => format!("{} {}", marker, data),
+          None => data,
+        };
+        match &stderr.color {
+          Some(color) => print_color(data, color.style(), no_color),
+          None => data,
+        }
+      }
+      None => data,
+    };
     match sender_type {
       SenderType::Main => {
         message = print_color(data, style, no_color);
@@ -129,6 +396,58 @@ pub fn print_message(
   println!("{}", message);
 }
 
+/// A message as a `--output json` object: a process's output line, a
+/// lifecycle event or a status line. `None` for control messages.
+pub fn json_message(message: &Message) -> Option<Value> {
+  let sender = &message.sender;
+  let text = || strip_ansi(&message.data);
+  let mut value = match (&message.type_, &message.event) {
+    (MessageType::Text | MessageType::Lifecycle, Some(event)) => match event {
+      Event::Spawned => json!({ "type": "spawned", "pid": sender.pid }),
+      Event::Exited { code, signal } => {
+        json!({ "type": "exited", "code": code, "signal": signal })
+      }
+      Event::Restarted { attempt, delay_ms } => json!({
+        "type": "restarted",
+        "attempt": attempt,
+        "delay_ms": delay_ms,
+      }),
+      Event::Killed { signal } => json!({ "type": "killed", "signal": signal }),
+      Event::TimedOut { after_ms } => {
+        json!({ "type": "timed_out", "after_ms": after_ms })
+      }
+      Event::Skipped { reason } => json!({ "type": "skipped", "reason": reason }),
+    },
+    (MessageType::Text, None) => match sender.stream {
+      Some(stream) => json!({
+        "type": "line",
+        "stream": match stream {
+          Stream::Stdout => "stdout",
+          Stream::Stderr => "stderr",
+        },
+        "text": message.data,
+      }),
+      None => json!({ "type": "status", "text": text() }),
+    },
+    (MessageType::Ready, _) => json!({ "type": "ready" }),
+    (MessageType::Error, _) => json!({ "type": "error", "text": text() }),
+    _ => return None,
+  };
+  value["index"] = json!(sender.index);
+  if sender.index.is_some() {
+    value["name"] = json!(message.name);
+  }
+  value["timestamp"] = json!(message.timestamp);
+  Some(value)
+}
+
+/// Print a message as a line of JSON, if it has a JSON form.
+pub fn print_json(message: &Message) {
+  if let Some(value) = json_message(message) {
+    println!("{}", value);
+  }
+}
+
 pub fn print_color(text: String, style: Style, no_color: bool) -> String {
   if no_color {
     text
@@ -136,3 +455,141 @@ pub fn print_color(text: String, style: Style, no_color: bool) -> String {
     format!("{}", text.style(style))
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn message(name: &str, timestamp: u64) -> Message {
+    let mut sender = build_message_sender(SenderType::Process, Some(1), None);
+    sender.pid = Some(4242);
+    sender.restarts = 2;
+    sender.stream = Some(Stream::Stderr);
+    let mut message = Message::new(
+      MessageType::Text,
+      Some(name.to_string()),
+      None,
+      None,
+      sender,
+    );
+    message.timestamp = timestamp;
+    message
+  }
+
+  fn format(template: Option<&str>) -> PrefixFormat {
+    PrefixFormat {
+      template: template.map(str::to_string),
+      commands: vec!["echo a".to_string(), "npm run dev".to_string()],
+      command_length: 7,
+      time_format: "%s".to_string(),
+      timestamps: false,
+      width: 0,
+    }
+  }
+
+  #[test]
+  fn render_fills_in_every_token() {
+    let m = message("web", 1_700_000_000_500);
+    assert_eq!(
+      format(Some(
+        "{index}|{name}|{pid}|{restarts}|{stream}|{none}|{time}"
+      ))
+      .render(&m),
+      "1|web|4242|2|stderr||1700000000"
+    );
+    assert_eq!(format(Some("{command}")).render(&m), "npm run");
+    assert_eq!(format(Some("pid")).render(&m), "4242");
+    assert_eq!(format(Some("{name}:{foo} {")).render(&m), "web:{foo} {");
+  }
+
+  #[test]
+  fn render_defaults_to_the_name() {
+    let m = message("web", 0);
+    assert_eq!(format(None).render(&m), "web");
+    let mut unstarted = message("web", 0);
+    unstarted.sender.pid = None;
+    assert_eq!(format(Some("{name}:{pid}")).render(&unstarted), "web:");
+  }
+
+  #[test]
+  fn render_prepends_with_timestamps() {
+    let mut with_time = format(None);
+    with_time.timestamps = true;
+    assert_eq!(
+      with_time.render(&message("api", 1_700_000_000_000)),
+      "1700000000 api"
+    );
+    // mlti's own banners have no name and get no time.
+    assert_eq!(with_time.render(&message("", 0)), "");
+  }
+
+  #[test]
+  fn pad_to_widest_aligns_rendered_prefixes() {
+    let names = ["api".to_string(), "frontend".to_string()];
+    let mut by_name = format(None);
+    by_name.pad_to_widest(&names);
+    assert_eq!(by_name.render(&message("api", 0)), "api     ");
+    assert_eq!(by_name.render(&message("frontend", 0)), "frontend");
+
+    // Templates without `{name}` are padded too.
+    let mut by_command = format(Some("{index}-{command}"));
+    by_command.pad_to_widest(&names);
+    let mut first = message("api", 0);
+    first.sender.index = Some(0);
+    assert_eq!(by_command.render(&first), "0-echo a ");
+    assert_eq!(by_command.render(&message("web", 0)), "1-npm run");
+
+    // Width is counted in characters.
+    let mut unicode = format(None);
+    unicode.pad_to_widest(&["café".to_string(), "db".to_string()]);
+    assert_eq!(unicode.render(&message("db", 0)), "db  ");
+
+    let mut none = format(None);
+    none.pad_to_widest(&[]);
+    assert_eq!(none.render(&message("solo", 0)), "solo");
+  }
+
+  #[test]
+  fn json_message_covers_lines_and_events() {
+    let mut line = message("web", 42);
+    line.data = "boom".to_string();
+    assert_eq!(
+      json_message(&line).unwrap(),
+      json!({
+        "type": "line",
+        "index": 1,
+        "name": "web",
+        "stream": "stderr",
+        "timestamp": 42,
+        "text": "boom",
+      })
+    );
+
+    let mut exited = message("web", 42);
+    exited.sender.stream = None;
+    exited.data = "\u{1b}[1mnpm run dev exited with signal SIGTERM".to_string();
+    exited.event = Some(Event::Exited {
+      code: 143,
+      signal: Some("SIGTERM".to_string()),
+    });
+    let value = json_message(&exited).unwrap();
+    assert_eq!(value["type"], "exited");
+    assert_eq!(value["code"], 143);
+    assert_eq!(value["signal"], "SIGTERM");
+
+    exited.event = None;
+    let value = json_message(&exited).unwrap();
+    assert_eq!(value["type"], "status");
+    assert_eq!(value["text"], "npm run dev exited with signal SIGTERM");
+
+    exited.type_ = MessageType::Kill;
+    assert_eq!(json_message(&exited), None);
+  }
+
+  #[test]
+  fn output_format_parse() {
+    assert_eq!(OutputFormat::parse("JSON"), Ok(OutputFormat::Json));
+    assert_eq!(OutputFormat::parse("text"), Ok(OutputFormat::Text));
+    assert!(OutputFormat::parse("raw").is_err());
+  }
+}
@@ -0,0 +1,210 @@
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::config::ProcessOptions;
+use crate::message::Stream;
+
+/// A process's own log file (`--log-dir`, `log-file`). Every line the task
+/// produces is appended here, uncolored and timestamped, whatever the
+/// terminal output is doing.
+pub struct ProcessLog {
+  file: File,
+  time_format: String,
+}
+
+impl ProcessLog {
+  /// Open `path` for appending, creating it and its directory if needed.
+  pub fn open(path: &Path, time_format: String) -> Result<Self, String> {
+    let error = |e: std::io::Error| {
+      format!("Could not open log file {}: {}", path.display(), e)
+    };
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+      fs::create_dir_all(dir).map_err(error)?;
+    }
+    let file = OpenOptions::new()
+      .create(true)
+      .append(true)
+      .open(path)
+      .map_err(error)?;
+    Ok(Self { file, time_format })
+  }
+
+  /// Append one line. Output from the child is written as is, with stderr
+  /// tagged; `None` marks mlti's own status lines about the process.
+  pub fn write_line(&self, stream: Option<Stream>, line: &str) {
+    let tag = match stream {
+      Some(Stream::Stdout) => "",
+      Some(Stream::Stderr) => "[stderr] ",
+      None => "[mlti] ",
+    };
+    let entry = format!(
+      "{} {}{}\n",
+      chrono::Local::now().format(&self.time_format),
+      tag,
+      strip_ansi(line)
+    );
+    // Each entry goes out in a single write so lines never interleave, and
+    // a full disk doesn't take the process down with it.
+    (&self.file).write_all(entry.as_bytes()).ok();
+  }
+}
+
+/// File name for a process in `--log-dir`: its name with anything that
+/// isn't safe in a path replaced, e.g. `web:dev` becomes `web_dev.log`.
+pub fn log_file_name(name: &str) -> String {
+  let name: String = name
+    .trim()
+    .chars()
+    .map(|c| {
+      if c.is_alphanumeric() || matches!(c, '-' | '_' | '.') {
+        c
+      } else {
+        '_'
+      }
+    })
+    .collect();
+  format!("{}.log", name.trim_start_matches('.'))
+}
+
+/// Give every process without a `log-file` of its own a file in `log_dir`,
+/// named after it. Names that come out the same, like `web:dev` and
+/// `web_dev`, get a number added so each process still has a file to
+/// itself. Two processes given the same `log-file` are an error.
+pub fn assign_log_files(
+  options: &mut [ProcessOptions],
+  log_dir: Option<&Path>,
+  names: &[Option<String>],
+) -> Result<(), String> {
+  let name = |i: usize| names[i].clone().unwrap_or_else(|| i.to_string());
+  let mut taken: HashSet<PathBuf> = HashSet::new();
+  for (i, options) in options.iter().enumerate() {
+    let Some(path) = &options.log_file else {
+      continue;
+    };
+    if !taken.insert(path.clone()) {
+      return Err(format!(
+        "Process \"{}\" logs to {}, which another process already uses",
+        name(i),
+        path.display()
+      ));
+    }
+  }
+  let Some(log_dir) = log_dir else {
+    return Ok(());
+  };
+  for (i, options) in options.iter_mut().enumerate() {
+    if options.log_file.is_some() {
+      continue;
+    }
+    let file_name = log_file_name(&name(i));
+    let stem = file_name.trim_end_matches(".log");
+    let mut path = log_dir.join(&file_name);
+    let mut n = 1;
+    while taken.contains(&path) {
+      path = log_dir.join(format!("{}-{}.log", stem, n));
+      n += 1;
+    }
+    taken.insert(path.clone());
+    options.log_file = Some(path);
+  }
+  Ok(())
+}
+
+/// Drop ANSI escape sequences so colored output reads cleanly in a file.
+pub fn strip_ansi(line: &str) -> String {
+  let mut out = String::with_capacity(line.len());
+  let mut chars = line.chars();
+  while let Some(c) = chars.next() {
+    if c != '\u{1b}' {
+      out.push(c);
+      continue;
+    }
+    // CSI sequences (`ESC [ ... final`) end at the first byte in @..~;
+    // anything else is a two-character escape.
+    if chars.next() == Some('[') {
+      for c in chars.by_ref() {
+        if ('@'..='~').contains(&c) {
+          break;
+        }
+      }
+    }
+  }
+  out
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn strip_ansi_removes_colors() {
+    assert_eq!(strip_ansi("\u{1b}[31mred\u{1b}[0m plain"), "red plain");
+    assert_eq!(strip_ansi("\u{1b}[38;2;1;2;3mrgb"), "rgb");
+    assert_eq!(strip_ansi("no escapes"), "no escapes");
+  }
+
+  #[test]
+  fn log_file_name_is_path_safe() {
+    assert_eq!(log_file_name("api"), "api.log");
+    assert_eq!(log_file_name("web:dev"), "web_dev.log");
+    assert_eq!(log_file_name("../etc/x"), "_etc_x.log");
+    assert_eq!(log_file_name("0  "), "0.log");
+  }
+
+  #[test]
+  fn log_files_never_clash() {
+    let names = vec![
+      Some("web:dev".to_string()),
+      Some("web_dev".to_string()),
+      None,
+      Some("2".to_string()),
+      Some("api".to_string()),
+    ];
+    let mut options = vec![ProcessOptions::default(); 5];
+    options[4].log_file = Some(PathBuf::from("logs/web_dev-1.log"));
+    assign_log_files(&mut options, Some(Path::new("logs")), &names).unwrap();
+    let files: Vec<PathBuf> = options
+      .iter()
+      .map(|o| o.log_file.clone().unwrap())
+      .collect();
+    assert_eq!(
+      files,
+      [
+        "logs/web_dev.log",
+        "logs/web_dev-2.log",
+        "logs/2.log",
+        "logs/2-1.log",
+        "logs/web_dev-1.log",
+      ]
+      .map(PathBuf::from)
+    );
+
+    let mut options = vec![ProcessOptions::default(); 2];
+    options[0].log_file = Some(PathBuf::from("shared.log"));
+    options[1].log_file = Some(PathBuf::from("shared.log"));
+    let err = assign_log_files(&mut options, None, &names).unwrap_err();
+    assert!(err.contains("\"web_dev\" logs to shared.log"), "{}", err);
+  }
+
+  #[test]
+  fn write_line_appends_tagged_lines() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("logs/api.log");
+    let log = ProcessLog::open(&path, "T".to_string()).unwrap();
+    log.write_line(Some(Stream::Stdout), "hello");
+    log.write_line(Some(Stream::Stderr), "\u{1b}[31moops\u{1b}[0m");
+    log.write_line(None, "api exited with code 0");
+    drop(log);
+
+    // Reopening appends rather than truncating.
+    let log = ProcessLog::open(&path, "T".to_string()).unwrap();
+    log.write_line(Some(Stream::Stdout), "again");
+
+    assert_eq!(
+      fs::read_to_string(&path).unwrap(),
+      "T hello\nT [stderr] oops\nT [mlti] api exited with code 0\nT again\n"
+    );
+  }
+}
@@ -0,0 +1,309 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use regex::Regex;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+
+use crate::input_router::resolve_target;
+
+/// How long a single TCP/HTTP probe may take before it counts as a miss.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(1);
+/// Delay between polls of a TCP, HTTP or file check.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Whether a process has signalled that it's up, as seen by the scheduler.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Readiness {
+  Pending,
+  Ready,
+  /// `--ready-timeout` elapsed before the check passed.
+  TimedOut,
+}
+
+/// A check that decides when a running process is ready.
+#[derive(Clone, Debug)]
+pub enum ReadyCheck {
+  /// A line of stdout or stderr matches the pattern.
+  Log(Regex),
+  /// A TCP connection to `host:port` succeeds.
+  Tcp(String),
+  /// A GET request returns a 2xx status.
+  Http {
+    address: String,
+    host: String,
+    path: String,
+  },
+  /// The file exists.
+  File(PathBuf),
+}
+
+impl ReadyCheck {
+  /// Parse a check as accepted by `--ready`: `log:<regex>`,
+  /// `tcp:[host:]<port>`, `http://host[:port][/path]` or `file:<path>`.
+  pub fn parse(s: &str) -> Result<Self, String> {
+    if let Some(pattern) = s.strip_prefix("log:") {
+      return Regex::new(pattern)
+        .map(Self::Log)
+        .map_err(|e| format!("Invalid --ready pattern '{}': {}", pattern, e));
+    }
+    if let Some(address) = s.strip_prefix("tcp:") {
+      let address = match address.parse::<u16>() {
+        Ok(port) => format!("localhost:{}", port),
+        Err(_) => address.to_string(),
+      };
+      return match address.rsplit_once(':') {
+        Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => {
+          Ok(Self::Tcp(address))
+        }
+        _ => Err(format!(
+          "Invalid --ready address '{}': expected tcp:[host:]port",
+          s
+        )),
+      };
+    }
+    if let Some(rest) = s.strip_prefix("http://") {
+      let (authority, path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+      };
+      let host = authority
+        .rsplit_once(':')
+        .map_or(authority, |(host, _)| host);
+      if host.is_empty() {
+        return Err(format!("Invalid --ready URL '{}': missing host", s));
+      }
+      let address = match authority.rsplit_once(':') {
+        Some((_, port)) if port.parse::<u16>().is_ok() => authority.to_string(),
+        Some(_) => return Err(format!("Invalid --ready URL '{}': bad port", s)),
+        None => format!("{}:80", authority),
+      };
+      return Ok(Self::Http {
+        address,
+        host: authority.to_string(),
+        path: path.to_string(),
+      });
+    }
+    if s.starts_with("https://") {
+      return Err(format!(
+        "Invalid --ready URL '{}': https is not supported, use http:// or tcp:",
+        s
+      ));
+    }
+    if let Some(path) = s.strip_prefix("file:") {
+      if !path.is_empty() {
+        return Ok(Self::File(PathBuf::from(path)));
+      }
+    }
+    Err(format!(
+      "Invalid --ready check '{}'. Expected log:<regex>, tcp:[host:]port, \
+       http://host[:port]/path or file:<path>",
+      s
+    ))
+  }
+
+  /// Whether an output line satisfies a log check. Always false for the
+  /// other kinds of check.
+  pub fn matches_line(&self, line: &str) -> bool {
+    match self {
+      Self::Log(regex) => regex.is_match(line),
+      _ => false,
+    }
+  }
+
+  /// Whether this check is satisfied by polling rather than by output.
+  pub fn is_polled(&self) -> bool {
+    !matches!(self, Self::Log(_))
+  }
+
+  /// Run one probe. Log checks never pass here; see `matches_line`.
+  pub async fn probe(&self) -> bool {
+    match self {
+      Self::Log(_) => false,
+      Self::Tcp(address) => {
+        matches!(
+          tokio::time::timeout(PROBE_TIMEOUT, TcpStream::connect(address)).await,
+          Ok(Ok(_))
+        )
+      }
+      Self::Http {
+        address,
+        host,
+        path,
+      } => matches!(
+        tokio::time::timeout(PROBE_TIMEOUT, http_status(address, host, path)).await,
+        Ok(Ok(200..=299))
+      ),
+      Self::File(path) => path.exists(),
+    }
+  }
+
+  /// Poll the check until it passes.
+  pub async fn wait(self) {
+    while !self.probe().await {
+      tokio::time::sleep(POLL_INTERVAL).await;
+    }
+  }
+}
+
+/// Send a bare HTTP/1.0 GET and return the response status code.
+async fn http_status(address: &str, host: &str, path: &str) -> std::io::Result<u16> {
+  let mut stream = TcpStream::connect(address).await?;
+  let request = format!(
+    "GET {} HTTP/1.0\r\nHost: {}\r\nConnection: close\r\n\r\n",
+    path, host
+  );
+  stream.write_all(request.as_bytes()).await?;
+
+  let mut reader = BufReader::new(stream);
+  let mut status_line = String::new();
+  reader.read_line(&mut status_line).await?;
+  // Read the rest of the response so closing the socket doesn't reset the
+  // connection under the server's feet.
+  tokio::io::copy(&mut reader, &mut tokio::io::sink())
+    .await
+    .ok();
+  status_line
+    .split_whitespace()
+    .nth(1)
+    .and_then(|code| code.parse().ok())
+    .ok_or_else(|| std::io::Error::other("malformed HTTP response"))
+}
+
+/// Parse `--ready` specs of the form `target=check` into one optional
+/// check per process. `target` is a process name or index.
+pub fn parse_ready_checks(
+  specs: &[String],
+  names: &[Option<String>],
+  num_processes: usize,
+) -> Result<Vec<Option<ReadyCheck>>, String> {
+  let mut checks: Vec<Option<ReadyCheck>> = vec![None; num_processes];
+  for spec in specs {
+    let (target, check) = spec
+      .split_once('=')
+      .ok_or_else(|| format!("Invalid --ready '{}': expected target=check", spec))?;
+    let index =
+      resolve_target(target.trim(), names, num_processes).ok_or_else(|| {
+        format!(
+          "--ready: \"{}\" does not match any process name or index",
+          target.trim()
+        )
+      })?;
+    if checks[index].is_some() {
+      return Err(format!(
+        "--ready: more than one check given for \"{}\"",
+        target.trim()
+      ));
+    }
+    checks[index] = Some(ReadyCheck::parse(check)?);
+  }
+  Ok(checks)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tokio::io::AsyncReadExt;
+
+  #[test]
+  fn parse_each_kind() {
+    assert!(ReadyCheck::parse("log:listening on \\d+")
+      .unwrap()
+      .matches_line("listening on 3000"));
+    assert!(matches!(
+      ReadyCheck::parse("tcp:3000").unwrap(),
+      ReadyCheck::Tcp(a) if a == "localhost:3000"
+    ));
+    assert!(matches!(
+      ReadyCheck::parse("tcp:127.0.0.1:8080").unwrap(),
+      ReadyCheck::Tcp(a) if a == "127.0.0.1:8080"
+    ));
+    assert!(matches!(
+      ReadyCheck::parse("file:/tmp/ready").unwrap(),
+      ReadyCheck::File(p) if p == std::path::Path::new("/tmp/ready")
+    ));
+  }
+
+  #[test]
+  fn parse_http_urls() {
+    match ReadyCheck::parse("http://localhost:3000/health").unwrap() {
+      ReadyCheck::Http {
+        address,
+        host,
+        path,
+      } => {
+        assert_eq!(address, "localhost:3000");
+        assert_eq!(host, "localhost:3000");
+        assert_eq!(path, "/health");
+      }
+      other => panic!("unexpected {:?}", other),
+    }
+    match ReadyCheck::parse("http://example.test").unwrap() {
+      ReadyCheck::Http { address, path, .. } => {
+        assert_eq!(address, "example.test:80");
+        assert_eq!(path, "/");
+      }
+      other => panic!("unexpected {:?}", other),
+    }
+  }
+
+  #[test]
+  fn parse_rejects_bad_checks() {
+    assert!(ReadyCheck::parse("log:(").is_err());
+    assert!(ReadyCheck::parse("tcp:").is_err());
+    assert!(ReadyCheck::parse("tcp:host:port").is_err());
+    assert!(ReadyCheck::parse("https://localhost").is_err());
+    assert!(ReadyCheck::parse("http://:80").is_err());
+    assert!(ReadyCheck::parse("file:").is_err());
+    assert!(ReadyCheck::parse("3000").is_err());
+  }
+
+  #[test]
+  fn parse_specs_by_name_and_index() {
+    let names = vec![Some("api".to_string()), Some("web".to_string())];
+    let specs = vec!["api=tcp:3000".to_string(), "1=log:ready".to_string()];
+    let checks = parse_ready_checks(&specs, &names, 2).unwrap();
+    assert!(matches!(checks[0], Some(ReadyCheck::Tcp(_))));
+    assert!(matches!(checks[1], Some(ReadyCheck::Log(_))));
+
+    let dup = vec!["api=tcp:1".to_string(), "0=tcp:2".to_string()];
+    assert!(parse_ready_checks(&dup, &names, 2).is_err());
+    let unknown = vec!["db=tcp:1".to_string()];
+    assert!(parse_ready_checks(&unknown, &names, 2).is_err());
+  }
+
+  #[tokio::test]
+  async fn probes_tcp_http_and_file() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+      while let Ok((mut socket, _)) = listener.accept().await {
+        let mut buf = [0u8; 256];
+        let n = socket.read(&mut buf).await.unwrap_or(0);
+        let status = if buf[..n].starts_with(b"GET /ok ") {
+          200
+        } else {
+          503
+        };
+        let response = format!("HTTP/1.1 {} X\r\nContent-Length: 0\r\n\r\n", status);
+        socket.write_all(response.as_bytes()).await.ok();
+      }
+    });
+
+    let check = |s: String| ReadyCheck::parse(&s).unwrap();
+    assert!(check(format!("tcp:127.0.0.1:{}", port)).probe().await);
+    assert!(check(format!("http://127.0.0.1:{}/ok", port)).probe().await);
+    assert!(
+      !check(format!("http://127.0.0.1:{}/down", port))
+        .probe()
+        .await
+    );
+
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("ready");
+    let file_check = check(format!("file:{}", file.display()));
+    assert!(!file_check.probe().await);
+    std::fs::write(&file, "").unwrap();
+    assert!(file_check.probe().await);
+  }
+}
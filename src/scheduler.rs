@@ -1,13 +1,44 @@
 use std::sync::Arc;
 
 use flume::{Receiver, Sender};
-use tokio::sync::{Mutex, RwLock};
+use tokio::sync::{watch, Mutex, RwLock};
 use tokio::task::JoinSet;
 
+use crate::dependency::{
+  Dependency, DependencyCondition, DependencyState, ProcessState,
+};
 use crate::message::{build_message_sender, MessageType, SenderType};
-use crate::task::TaskTiming;
+use crate::readiness::Readiness;
+use crate::task::{TaskStatus, TaskTiming};
 use crate::{message::Message, task::Task};
 
+/// A request to act on one process, by index, while the session runs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Control {
+  /// Stop the process if it's running and start it again; start it again
+  /// if it already finished.
+  Restart(usize),
+  /// Stop the process without restarting it.
+  Stop(usize),
+  /// Start the process again if it already finished. A process that is
+  /// running or hasn't started yet is left alone.
+  Start(usize),
+  /// Restart the process because a file it watches changed; the string
+  /// names the file. Otherwise the same as `Restart`.
+  Changed(usize, String),
+}
+
+impl Control {
+  pub fn index(&self) -> usize {
+    match self {
+      Control::Restart(index)
+      | Control::Stop(index)
+      | Control::Start(index)
+      | Control::Changed(index, _) => *index,
+    }
+  }
+}
+
 pub(crate) struct Scheduler {
   pub tasks_rx: Receiver<Task>,
   pub tasks_tx: Sender<Task>,
@@ -17,6 +48,19 @@ pub(crate) struct Scheduler {
   number_of_tasks: i32,
   kill_all_tx: Sender<()>,
   kill_all_rx: Receiver<()>,
+  // Flipped to `true` once to tell every running task to terminate its
+  // child. A watch channel so each task sees the same signal.
+  terminate_tx: watch::Sender<bool>,
+  // Tasks report here once their process becomes ready or gives up on it,
+  // which is what `DependencyCondition::Ready` waits for.
+  ready_tx: Sender<(usize, Readiness)>,
+  ready_rx: Receiver<(usize, Readiness)>,
+  control_tx: Sender<Control>,
+  control_rx: Receiver<Control>,
+  // Keep waiting for `Control` requests once every task has finished,
+  // until a kill-all. Used by `--tui`.
+  keep_alive: bool,
+  dependencies: Vec<Vec<Dependency>>,
   exit_codes: Arc<Mutex<Vec<(usize, i32)>>>,
   timings: Arc<Mutex<Vec<TaskTiming>>>,
 }
@@ -26,9 +70,14 @@ impl Scheduler {
     shutdown_tx: Sender<Message>,
     max_processes: i32,
     number_of_tasks: i32,
+    dependencies: Vec<Vec<Dependency>>,
+    keep_alive: bool,
   ) -> Self {
     let (tasks_tx, tasks_rx) = flume::unbounded::<Task>();
     let (kill_all_tx, kill_all_rx) = flume::unbounded::<()>();
+    let (terminate_tx, _) = watch::channel(false);
+    let (ready_tx, ready_rx) = flume::unbounded::<(usize, Readiness)>();
+    let (control_tx, control_rx) = flume::unbounded::<Control>();
 
     Self {
       tasks_rx,
@@ -39,6 +88,13 @@ impl Scheduler {
       number_of_tasks,
       kill_all_tx,
       kill_all_rx,
+      terminate_tx,
+      ready_tx,
+      ready_rx,
+      control_tx,
+      control_rx,
+      keep_alive,
+      dependencies,
       exit_codes: Arc::new(Mutex::new(Vec::new())),
       timings: Arc::new(Mutex::new(Vec::new())),
     }
@@ -49,6 +105,9 @@ impl Scheduler {
   pub fn get_kill_all(&self) -> Sender<()> {
     self.kill_all_tx.clone()
   }
+  pub fn get_control(&self) -> Sender<Control> {
+    self.control_tx.clone()
+  }
 
   pub async fn get_exit_codes(&self) -> Vec<(usize, i32)> {
     self.exit_codes.lock().await.clone()
@@ -58,61 +117,251 @@ impl Scheduler {
     self.timings.lock().await.clone()
   }
 
+  /// Indices of tasks that never ran because a dependency failed.
+  pub async fn get_skipped(&self) -> Vec<usize> {
+    self
+      .timings
+      .lock()
+      .await
+      .iter()
+      .filter(|t| t.status == TaskStatus::Skipped)
+      .map(|t| t.index)
+      .collect()
+  }
+
+  fn dependency_states<'a>(
+    &'a self,
+    index: usize,
+    states: &'a [ProcessState],
+    readiness: &'a [Readiness],
+  ) -> impl Iterator<Item = (&'a Dependency, DependencyState)> + 'a {
+    self
+      .dependencies
+      .get(index)
+      .into_iter()
+      .flatten()
+      .map(|dep| {
+        let state = dep.state(states[dep.index], readiness[dep.index]);
+        (dep, state)
+      })
+  }
+
+  /// Run `task`, handing it back along with its timing so it can be
+  /// started again. A task that runs again replaces its earlier results.
+  fn spawn_task(
+    &self,
+    join_set: &mut JoinSet<(Task, TaskTiming)>,
+    mut task: Task,
+    control_rx: Receiver<Control>,
+  ) {
+    let exit_codes = self.exit_codes.clone();
+    let timings = self.timings.clone();
+    let task_index = task.index();
+    let (_, task_raw_cmd) = task.process_info();
+    let kill_rx = self.terminate_tx.subscribe();
+    let ready_tx = self.ready_tx.clone();
+    join_set.spawn(async move {
+      exit_codes.lock().await.retain(|(i, _)| *i != task_index);
+      timings.lock().await.retain(|t| t.index != task_index);
+      let timing = match task.start(kill_rx, control_rx, ready_tx).await {
+        Ok((code, timing)) => {
+          // Processes we killed don't count towards the success
+          // condition; they never got the chance to finish. Ones that
+          // timed out had their chance and fail it.
+          if matches!(timing.status, TaskStatus::Exited | TaskStatus::TimedOut) {
+            exit_codes.lock().await.push((task_index, code));
+          }
+          timing
+        }
+        Err(e) => {
+          println!("{}", e);
+          exit_codes.lock().await.push((task_index, 1));
+          TaskTiming {
+            index: task_index,
+            raw_cmd: task_raw_cmd,
+            exit_code: 1,
+            duration_secs: 0.0,
+            attempts: vec![],
+            status: TaskStatus::Exited,
+            output: vec![],
+            usage: None,
+          }
+        }
+      };
+      timings.lock().await.push(timing.clone());
+      (task, timing)
+    });
+  }
+
+  /// Record a task that will never run.
+  async fn skip_task(&self, task: Task, reason: String) {
+    let timing = task.skip(reason).await;
+    self.timings.lock().await.push(timing);
+  }
+
   pub async fn run(&self) {
+    let number_of_tasks = self.number_of_tasks.max(0) as usize;
     let mut completed_tasks = 0;
     let mut join_set = JoinSet::new();
 
+    // Collect every task up front: with dependencies, a later task may be
+    // able to start before an earlier one.
+    let mut pending: Vec<Option<Task>> =
+      (0..number_of_tasks).map(|_| None).collect();
+    let mut labels: Vec<String> =
+      (0..number_of_tasks).map(|i| i.to_string()).collect();
+    for _ in 0..number_of_tasks {
+      match self.tasks_rx.recv_async().await {
+        Ok(task) => {
+          let index = task.index();
+          labels[index] = task.name().to_string();
+          pending[index] = Some(task);
+        }
+        Err(_) => break,
+      }
+    }
+    let mut states = vec![ProcessState::Pending; number_of_tasks];
+    let mut readiness = vec![Readiness::Pending; number_of_tasks];
+    // Tasks that are done, kept so a `Control::Restart` can run them again.
+    let mut finished: Vec<Option<Task>> =
+      (0..number_of_tasks).map(|_| None).collect();
+    // Each running task listens for `Control` requests on its own channel.
+    let controls: Vec<(Sender<Control>, Receiver<Control>)> = (0..number_of_tasks)
+      .map(|_| flume::unbounded::<Control>())
+      .collect();
+
     loop {
       let mut running_processes = self.running_processes.write().await;
-      loop {
-        // If we cant run anything, shortcircuit
-        if completed_tasks == self.number_of_tasks
-          || *running_processes == self.number_of_tasks
-          || completed_tasks + *running_processes == self.number_of_tasks
-          || *running_processes >= self.max_processes
-        {
+
+      // Skip tasks whose dependencies can no longer be met. Skipping one
+      // may doom its own dependents, so repeat until nothing changes.
+      let mut skipped_any = true;
+      while skipped_any {
+        skipped_any = false;
+        for index in 0..number_of_tasks {
+          if pending[index].is_none() {
+            continue;
+          }
+          let failed = self
+            .dependency_states(index, &states, &readiness)
+            .find(|(_, state)| *state == DependencyState::Failed)
+            .map(|(dep, _)| dep.clone());
+          if let Some(dep) = failed {
+            let task = pending[index].take().expect("checked above");
+            let why = match dep.condition {
+              DependencyCondition::Completed => "did not complete",
+              DependencyCondition::Success => "did not succeed",
+              DependencyCondition::Ready => "never became ready",
+            };
+            self
+              .skip_task(task, format!("{} {}", labels[dep.index], why))
+              .await;
+            states[index] = ProcessState::Skipped;
+            completed_tasks += 1;
+            skipped_any = true;
+          }
+        }
+      }
+
+      // Start whatever is runnable, in order, while there's capacity.
+      for index in 0..number_of_tasks {
+        if *running_processes >= self.max_processes {
           break;
-        } else {
-          let task = self.tasks_rx.recv_async().await.ok();
-          if let Some(mut task) = task {
-            *running_processes += 1;
-            let exit_codes = self.exit_codes.clone();
-            let timings = self.timings.clone();
-            let task_index = task.index();
-            let (_, task_raw_cmd) = task.process_info();
-            join_set.spawn(async move {
-              match task.start().await {
-                Ok((code, timing)) => {
-                  exit_codes.lock().await.push((task_index, code));
-                  timings.lock().await.push(timing);
-                }
-                Err(e) => {
-                  println!("{}", e);
-                  exit_codes.lock().await.push((task_index, 1));
-                  timings.lock().await.push(TaskTiming {
-                    index: task_index,
-                    raw_cmd: task_raw_cmd,
-                    exit_code: 1,
-                    duration_secs: 0.0,
-                  });
-                }
-              }
-            });
+        }
+        if pending[index].is_none() {
+          continue;
+        }
+        let runnable = self
+          .dependency_states(index, &states, &readiness)
+          .all(|(_, state)| state == DependencyState::Satisfied);
+        if runnable {
+          let task = pending[index].take().expect("checked above");
+          states[index] = ProcessState::Running;
+          *running_processes += 1;
+          self.spawn_task(&mut join_set, task, controls[index].1.clone());
+        }
+      }
+
+      if completed_tasks == number_of_tasks && !self.keep_alive {
+        break;
+      }
+      if join_set.is_empty() && completed_tasks < number_of_tasks {
+        // Nothing is running and nothing can start. Cycles are rejected
+        // before we get here, so this only happens if a task vanished
+        // (e.g. panicked); don't wait forever on its dependents.
+        for (index, slot) in pending.iter_mut().enumerate() {
+          if let Some(task) = slot.take() {
+            self
+              .skip_task(task, "a dependency never finished".to_string())
+              .await;
+            states[index] = ProcessState::Skipped;
           }
         }
+        if !self.keep_alive {
+          break;
+        }
+        completed_tasks = number_of_tasks;
       }
+
       tokio::select! {
-        _ = join_set.join_next() => {
-            completed_tasks +=1;
+        result = join_set.join_next(), if !join_set.is_empty() => {
+            completed_tasks += 1;
             *running_processes -= 1;
-            if completed_tasks == self.number_of_tasks {
-                break;
+            // A task reports readiness before it finishes; make sure we've
+            // seen that before judging its dependents.
+            while let Ok((index, state)) = self.ready_rx.try_recv() {
+              readiness[index] = state;
+            }
+            if let Some(Ok((task, timing))) = result {
+              states[timing.index] = match timing.status {
+                TaskStatus::Exited | TaskStatus::TimedOut => {
+                  ProcessState::Exited(timing.exit_code)
+                }
+                TaskStatus::Killed => ProcessState::Killed,
+                TaskStatus::Skipped => ProcessState::Skipped,
+              };
+              finished[timing.index] = Some(task);
             }
         }
+        Ok(control) = self.control_rx.recv_async() => {
+            let index = control.index();
+            if index >= number_of_tasks {
+              continue;
+            }
+            let running = states[index] == ProcessState::Running;
+            match control {
+              Control::Start(_) if running => {}
+              _ if running => {
+                controls[index].0.send(control).ok();
+              }
+              Control::Restart(_) | Control::Start(_) | Control::Changed(..) => {
+                if let Some(task) = finished[index].take() {
+                  // Drop requests that arrived after the task finished.
+                  while controls[index].1.try_recv().is_ok() {}
+                  if let Control::Changed(_, path) = &control {
+                    let (_, raw_cmd) = task.process_info();
+                    task
+                      .send_text(format!("{} starting ({} changed)", raw_cmd, path))
+                      .await;
+                  }
+                  states[index] = ProcessState::Running;
+                  completed_tasks -= 1;
+                  *running_processes += 1;
+                  self.spawn_task(&mut join_set, task, controls[index].1.clone());
+                }
+              }
+              Control::Stop(_) => {}
+            }
+        }
+        Ok((index, state)) = self.ready_rx.recv_async() => {
+            readiness[index] = state;
+        }
         _ = self.kill_all_rx.recv_async() => {
-
-            join_set.shutdown().await;
-            return;
+            // Ask every running task to stop its child, then wait for them
+            // to wind down. Tasks still queued are simply never started.
+            self.terminate_tx.send_replace(true);
+            while join_set.join_next().await.is_some() {}
+            break;
         }
       }
     }
@@ -0,0 +1,99 @@
+/// Signals mlti can use to terminate a child process.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KillSignal {
+  Term,
+  Int,
+  Kill,
+  Hup,
+}
+
+impl KillSignal {
+  /// Parse a signal name as accepted by `--kill-signal`. The `SIG` prefix
+  /// is optional and matching is case-insensitive.
+  pub fn parse(s: &str) -> Result<Self, String> {
+    let upper = s.trim().to_uppercase();
+    match upper.strip_prefix("SIG").unwrap_or(&upper) {
+      "TERM" => Ok(Self::Term),
+      "INT" => Ok(Self::Int),
+      "KILL" => Ok(Self::Kill),
+      "HUP" => Ok(Self::Hup),
+      _ => Err(format!(
+        "Invalid kill signal: '{}'. Expected: SIGTERM, SIGINT, SIGKILL, SIGHUP",
+        s
+      )),
+    }
+  }
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      Self::Term => "SIGTERM",
+      Self::Int => "SIGINT",
+      Self::Kill => "SIGKILL",
+      Self::Hup => "SIGHUP",
+    }
+  }
+
+  #[cfg(unix)]
+  fn as_raw(&self) -> libc::c_int {
+    match self {
+      Self::Term => libc::SIGTERM,
+      Self::Int => libc::SIGINT,
+      Self::Kill => libc::SIGKILL,
+      Self::Hup => libc::SIGHUP,
+    }
+  }
+}
+
+/// Send `signal` to every process in the process group `pgid`. Errors
+/// (e.g. the group is already gone) are ignored — the caller only cares
+/// that it stops.
+#[cfg(unix)]
+pub fn signal_group(pgid: u32, signal: KillSignal) {
+  unsafe {
+    libc::kill(-(pgid as libc::pid_t), signal.as_raw());
+  }
+}
+
+/// Render the signal that terminated a process, e.g. `SIGTERM`, or just the
+/// number for signals mlti doesn't name.
+#[cfg(unix)]
+pub fn signal_name(signal: i32) -> String {
+  [
+    KillSignal::Term,
+    KillSignal::Int,
+    KillSignal::Kill,
+    KillSignal::Hup,
+  ]
+  .iter()
+  .find(|known| known.as_raw() == signal)
+  .map_or_else(|| signal.to_string(), |known| known.name().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parse_accepts_names_with_and_without_prefix() {
+    assert_eq!(KillSignal::parse("SIGTERM").unwrap(), KillSignal::Term);
+    assert_eq!(KillSignal::parse("term").unwrap(), KillSignal::Term);
+    assert_eq!(KillSignal::parse("SigInt").unwrap(), KillSignal::Int);
+    assert_eq!(KillSignal::parse("KILL").unwrap(), KillSignal::Kill);
+    assert_eq!(KillSignal::parse("sighup").unwrap(), KillSignal::Hup);
+  }
+
+  #[test]
+  fn parse_rejects_unknown() {
+    assert!(KillSignal::parse("").is_err());
+    assert!(KillSignal::parse("SIGUSR1").is_err());
+    assert!(KillSignal::parse("15").is_err());
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn signal_name_round_trips_known_signals() {
+    assert_eq!(signal_name(libc::SIGTERM), "SIGTERM");
+    assert_eq!(signal_name(libc::SIGKILL), "SIGKILL");
+    assert_eq!(signal_name(libc::SIGSEGV), libc::SIGSEGV.to_string());
+  }
+}
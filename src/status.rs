@@ -0,0 +1,156 @@
+use crate::message::{Event, Message, MessageType};
+
+/// Where a process is at, as far as its messages tell.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DisplayState {
+  /// Not started yet: waiting on a dependency or `--max-processes`.
+  #[default]
+  Queued,
+  Running,
+  /// Its `--ready` check passed.
+  Ready,
+  /// Exited and about to be started again.
+  Restarting,
+  Exited(i32),
+  /// Stopped by mlti.
+  Killed,
+  /// Stopped by mlti for running longer than its `--timeout`.
+  TimedOut,
+  Skipped,
+}
+
+impl DisplayState {
+  pub fn name(&self) -> &'static str {
+    match self {
+      DisplayState::Queued => "queued",
+      DisplayState::Running => "running",
+      DisplayState::Ready => "ready",
+      DisplayState::Restarting => "restarting",
+      DisplayState::Exited(_) => "exited",
+      DisplayState::Killed => "killed",
+      DisplayState::TimedOut => "timed_out",
+      DisplayState::Skipped => "skipped",
+    }
+  }
+
+  /// The name, with the exit code for a process that exited.
+  pub fn label(&self) -> String {
+    match self {
+      DisplayState::Exited(code) => format!("exited {}", code),
+      DisplayState::TimedOut => "timed out".to_string(),
+      state => state.name().to_string(),
+    }
+  }
+}
+
+/// A process's state, pid and restart count, kept up to date from the
+/// messages its task sends.
+#[derive(Clone, Debug, Default)]
+pub struct ProcessStatus {
+  pub state: DisplayState,
+  pub pid: Option<u32>,
+  pub restarts: i64,
+  // Set once mlti signals the process, so its exit reads as a kill.
+  stopping: bool,
+  // Set when that was because of `--timeout`.
+  timed_out: bool,
+}
+
+impl ProcessStatus {
+  /// Update from a message sent by this process's task.
+  pub fn update(&mut self, message: &Message) {
+    self.restarts = message.sender.restarts;
+    match &message.event {
+      Some(Event::Spawned) => {
+        self.state = DisplayState::Running;
+        self.pid = message.sender.pid;
+        self.stopping = false;
+        self.timed_out = false;
+      }
+      Some(Event::Restarted { .. }) => self.state = DisplayState::Restarting,
+      Some(Event::Killed { .. }) => self.stopping = true,
+      Some(Event::TimedOut { .. }) => self.timed_out = true,
+      Some(Event::Exited { code, .. }) => {
+        self.pid = None;
+        self.state = if self.timed_out {
+          DisplayState::TimedOut
+        } else if self.stopping {
+          DisplayState::Killed
+        } else {
+          DisplayState::Exited(*code)
+        };
+      }
+      Some(Event::Skipped { .. }) => self.state = DisplayState::Skipped,
+      None => {
+        if let MessageType::Ready = message.type_ {
+          self.state = DisplayState::Ready;
+        }
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::message::{build_message_sender, SenderType};
+
+  fn event(event: Event, pid: Option<u32>) -> Message {
+    let mut sender = build_message_sender(SenderType::Task, Some(0), None);
+    sender.pid = pid;
+    let mut message = Message::new(MessageType::Lifecycle, None, None, None, sender);
+    message.event = Some(event);
+    message
+  }
+
+  #[test]
+  fn events_drive_the_state() {
+    let mut status = ProcessStatus::default();
+    assert_eq!(status.state, DisplayState::Queued);
+
+    status.update(&event(Event::Spawned, Some(42)));
+    assert_eq!(status.state, DisplayState::Running);
+    assert_eq!(status.pid, Some(42));
+    status.update(&Message::new(
+      MessageType::Ready,
+      None,
+      None,
+      None,
+      build_message_sender(SenderType::Task, Some(0), None),
+    ));
+    assert_eq!(status.state, DisplayState::Ready);
+
+    let exited = Event::Exited {
+      code: 1,
+      signal: None,
+    };
+    status.update(&event(exited.clone(), Some(42)));
+    assert_eq!(status.state, DisplayState::Exited(1));
+    assert_eq!(status.state.label(), "exited 1");
+    assert_eq!(status.pid, None);
+    let restarted = Event::Restarted {
+      attempt: 1,
+      delay_ms: 0,
+    };
+    status.update(&event(restarted, None));
+    assert_eq!(status.state, DisplayState::Restarting);
+
+    status.update(&event(Event::Spawned, Some(43)));
+    let killed = Event::Killed {
+      signal: "SIGTERM".to_string(),
+    };
+    status.update(&event(killed, Some(43)));
+    status.update(&event(exited.clone(), Some(43)));
+    assert_eq!(status.state, DisplayState::Killed);
+
+    status.update(&event(Event::Spawned, Some(44)));
+    status.update(&event(Event::TimedOut { after_ms: 30000 }, Some(44)));
+    let killed = Event::Killed {
+      signal: "SIGTERM".to_string(),
+    };
+    status.update(&event(killed, Some(44)));
+    status.update(&event(exited, Some(44)));
+    assert_eq!(status.state, DisplayState::TimedOut);
+    assert_eq!(status.state.label(), "timed out");
+  }
+}
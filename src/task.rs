@@ -1,24 +1,79 @@
 use anyhow::Result;
-use chrono::{Duration, Local};
+use chrono::Duration;
 use chrono_humanize::{Accuracy, HumanTime, Tense};
-use flume::Sender;
+use flume::{Receiver, Sender};
 use owo_colors::OwoColorize;
+use std::collections::VecDeque;
+use std::process::ExitStatus;
 use std::time::Instant;
 use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio::process::Child;
+use tokio::sync::watch;
 
+use crate::analyze::{ResourceUsage, UsageMonitor};
 use crate::command::Process;
 use crate::input_router::InputRouter;
-use crate::message::{build_message_sender, Message, MessageType, SenderType};
+use crate::message::{
+  build_message_sender, Event, Message, MessageSender, MessageType, SenderType,
+  Stream,
+};
+use crate::process_log::ProcessLog;
+use crate::readiness::{Readiness, ReadyCheck};
+use crate::scheduler::Control;
+use crate::timeout::format_duration;
 use crate::MltiConfig;
 use std::sync::Arc;
 
+/// Outcome of a single run of a task's process. A task that restarts
+/// records one of these per spawn.
+#[derive(Clone)]
+pub struct AttemptTiming {
+  pub exit_code: i32,
+  pub duration_secs: f64,
+}
+
+/// The exit code recorded for a run stopped by `--timeout`, whatever the
+/// process itself exited with, as with coreutils' `timeout`.
+pub const TIMEOUT_EXIT_CODE: i32 = 124;
+
+/// How a task came to an end.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TaskStatus {
+  /// The process exited on its own, or could not be started at all.
+  Exited,
+  /// mlti terminated the process while shutting down.
+  Killed,
+  /// mlti stopped the process because it outlived its `--timeout`. Its
+  /// exit code is `TIMEOUT_EXIT_CODE`, so it counts as a failure.
+  TimedOut,
+  /// Never started because one of its dependencies failed.
+  Skipped,
+}
+
+impl TaskStatus {
+  pub fn name(&self) -> &'static str {
+    match self {
+      TaskStatus::Exited => "exited",
+      TaskStatus::Killed => "killed",
+      TaskStatus::TimedOut => "timed_out",
+      TaskStatus::Skipped => "skipped",
+    }
+  }
+}
+
 #[derive(Clone)]
 pub struct TaskTiming {
   pub index: usize,
   pub raw_cmd: String,
   pub exit_code: i32,
   pub duration_secs: f64,
+  pub attempts: Vec<AttemptTiming>,
+  pub status: TaskStatus,
+  /// The last `tail_lines` lines of output from the latest run, oldest
+  /// first.
+  pub output: Vec<String>,
+  /// What the process used over all its runs, with `--analyze`.
+  pub usage: Option<ResourceUsage>,
 }
 
 pub(crate) struct Task {
@@ -28,15 +83,39 @@ pub(crate) struct Task {
   mlti_config: MltiConfig,
   input_router: Option<Arc<InputRouter>>,
   exit_code: Option<i32>,
+  ready_check: Option<ReadyCheck>,
+  readiness: Readiness,
+  // When `--ready-timeout` runs out, counted from the first spawn.
+  ready_deadline: Option<tokio::time::Instant>,
+  // The running child's pid and how many restarts it took to get there,
+  // for `{pid}` and `{restarts}` in the prefix.
+  pid: Option<u32>,
+  restarts: i64,
+  log: Option<ProcessLog>,
+  // The most recent lines of output from the current run, up to
+  // `tail_lines`, and how many older ones didn't fit.
+  tail: VecDeque<Message>,
+  tail_dropped: usize,
+  // A stop or restart asked for through `Control` while the child ran.
+  requested: Option<Control>,
+  // Whether the latest run was stopped for outliving its `--timeout`.
+  timed_out: bool,
+  // Samples each run with `--analyze`, and what the runs so far used.
+  usage_monitor: Option<Arc<UsageMonitor>>,
+  usage: Option<ResourceUsage>,
 }
 
 impl Task {
+  #[allow(clippy::too_many_arguments)]
   pub fn new(
     process: Process,
     message_tx: Sender<Message>,
     shutdown_tx: Sender<Message>,
     mlti_config: MltiConfig,
     input_router: Option<Arc<InputRouter>>,
+    ready_check: Option<ReadyCheck>,
+    log: Option<ProcessLog>,
+    usage_monitor: Option<Arc<UsageMonitor>>,
   ) -> Self {
     Self {
       process,
@@ -45,6 +124,18 @@ impl Task {
       mlti_config,
       input_router,
       exit_code: None,
+      ready_check,
+      readiness: Readiness::Pending,
+      ready_deadline: None,
+      pid: None,
+      restarts: 0,
+      log,
+      tail: VecDeque::new(),
+      tail_dropped: 0,
+      requested: None,
+      timed_out: false,
+      usage_monitor,
+      usage: None,
     }
   }
   pub fn index(&self) -> usize {
@@ -53,17 +144,50 @@ impl Task {
   pub fn process_info(&self) -> (usize, String) {
     (self.process.index, self.process.raw_cmd.clone())
   }
+  pub fn name(&self) -> &str {
+    &self.process.name
+  }
 
-  fn make_timing(&self, exit_code: i32, duration_secs: f64) -> TaskTiming {
+  fn make_timing(
+    &self,
+    exit_code: i32,
+    duration_secs: f64,
+    attempts: Vec<AttemptTiming>,
+    status: TaskStatus,
+  ) -> TaskTiming {
     TaskTiming {
       index: self.process.index,
       raw_cmd: self.process.raw_cmd.clone(),
       exit_code,
       duration_secs,
+      attempts,
+      status,
+      output: self.tail.iter().map(|m| m.data.clone()).collect(),
+      usage: self.usage.clone(),
+    }
+  }
+
+  /// Sender details for a message about this task's process.
+  fn sender(&self, sender_type: SenderType) -> MessageSender {
+    let mut sender = build_message_sender(
+      sender_type,
+      Some(self.process.index),
+      Some(self.process.name.clone()),
+    );
+    sender.pid = self.pid;
+    sender.restarts = self.restarts;
+    sender
+  }
+
+  /// Append a line to the process's log file, if it has one.
+  fn log(&self, stream: Option<Stream>, line: &str) {
+    if let Some(log) = &self.log {
+      log.write_line(stream, line);
     }
   }
 
   pub async fn send_error(&self, error: String) {
+    self.log(None, &error);
     self
       .shutdown_tx
       .send_async(Message::new(
@@ -71,85 +195,429 @@ impl Task {
         Some(self.process.name.clone()),
         Some(error),
         None,
-        build_message_sender(SenderType::Task, None, None),
+        self.sender(SenderType::Task),
       ))
       .await
       .expect("Could not send message on channel.");
   }
-  pub async fn start(&mut self) -> Result<(i32, TaskTiming)> {
-    let mut child: Option<Child> = None;
+  pub async fn send_text(&self, data: String) {
+    self.log(None, &data);
+    self
+      .message_tx
+      .send_async(Message::new(
+        MessageType::Text,
+        Some(self.process.name.clone()),
+        Some(data),
+        Some(self.process.color),
+        self.sender(SenderType::Task),
+      ))
+      .await
+      .expect("Couldnt send message to main thread");
+  }
 
-    let mut restart_attempts = self.mlti_config.restart_tries - 1;
+  /// Report a lifecycle event, along with the status line describing it if
+  /// there is one. Events without a line only show up in `--output json`.
+  async fn send_event(&self, event: Event, data: Option<String>) {
+    let type_ = match &data {
+      Some(data) => {
+        self.log(None, data);
+        MessageType::Text
+      }
+      None => MessageType::Lifecycle,
+    };
+    let mut message = Message::new(
+      type_,
+      Some(self.process.name.clone()),
+      data,
+      Some(self.process.color),
+      self.sender(SenderType::Task),
+    );
+    message.event = Some(event);
+    self
+      .message_tx
+      .send_async(message)
+      .await
+      .expect("Couldnt send message to main thread");
+  }
 
-    loop {
+  /// Whether another restart is allowed after `restarts` have already
+  /// happened. A negative `restart_tries` means restart forever.
+  fn can_restart(&self, restarts: i64) -> bool {
+    self.mlti_config.restart_tries < 0 || restarts < self.mlti_config.restart_tries
+  }
+
+  fn attempt_label(&self, attempt: i64) -> String {
+    if self.mlti_config.restart_tries < 0 {
+      format!("attempt {}", attempt)
+    } else {
+      format!("attempt {}/{}", attempt, self.mlti_config.restart_tries)
+    }
+  }
+
+  /// Record a change in readiness and pass it on to the scheduler. With a
+  /// `--ready` check the outcome is reported in the output too.
+  async fn set_readiness(
+    &mut self,
+    readiness: Readiness,
+    ready_tx: &Sender<(usize, Readiness)>,
+  ) {
+    self.readiness = readiness;
+    ready_tx.send((self.process.index, readiness)).ok();
+    if self.ready_check.is_none() {
+      return;
+    }
+    match readiness {
+      Readiness::Ready => {
+        let data = format!("{} is ready", self.process.raw_cmd);
+        self.log(None, &data);
+        self
+          .message_tx
+          .send_async(Message::new(
+            MessageType::Ready,
+            Some(self.process.name.clone()),
+            Some(data),
+            Some(self.process.color),
+            self.sender(SenderType::Task),
+          ))
+          .await
+          .expect("Couldnt send message to main thread");
+      }
+      Readiness::TimedOut => {
+        self
+          .send_text(format!(
+            "{} did not become ready within {}",
+            self.process.raw_cmd,
+            get_relative_time_from_ms(self.mlti_config.ready_timeout as i64)
+          ))
+          .await;
+      }
+      Readiness::Pending => {}
+    }
+  }
+
+  /// Forward a line of output, checking it against a `log:` ready check.
+  async fn handle_line(
+    &mut self,
+    line: String,
+    stream: Stream,
+    ready_tx: &Sender<(usize, Readiness)>,
+  ) {
+    let ready = self.readiness == Readiness::Pending
+      && self
+        .ready_check
+        .as_ref()
+        .is_some_and(|check| check.matches_line(&line));
+    self.log(Some(stream), &line);
+    let mut sender = self.sender(SenderType::Process);
+    sender.stream = Some(stream);
+    let message = Message::new(
+      MessageType::Text,
+      Some(self.process.name.clone()),
+      Some(line),
+      Some(self.process.color),
+      sender,
+    );
+    if self.mlti_config.tail_lines > 0 {
+      if self.tail.len() == self.mlti_config.tail_lines {
+        self.tail.pop_front();
+        self.tail_dropped += 1;
+      }
+      self.tail.push_back(message.clone());
+    }
+    // With `--show-output-on-failure` lines are only printed if the
+    // process fails (see `replay_output`).
+    if !self.mlti_config.show_output_on_failure {
+      self
+        .message_tx
+        .send_async(message)
+        .await
+        .expect("Couldnt send message to main thread");
+    }
+    if ready {
+      self.set_readiness(Readiness::Ready, ready_tx).await;
+    }
+  }
+
+  /// Tell the output messenger this task is done for good.
+  async fn send_finished(&self) {
+    self
+      .message_tx
+      .send_async(Message::new(
+        MessageType::Finished,
+        Some(self.process.name.clone()),
+        None,
+        None,
+        self.sender(SenderType::Task),
+      ))
+      .await
+      .expect("Couldnt send message to main thread");
+  }
+
+  /// Print the output held back by `--show-output-on-failure` as a single
+  /// block, so other processes' lines can't end up in the middle of it.
+  async fn replay_output(&self) {
+    let mut lines = Vec::new();
+    if self.tail_dropped > 0 {
+      let note = Message::new(
+        MessageType::Text,
+        Some(self.process.name.clone()),
+        Some(format!(
+          "... {} earlier lines not shown, last {}:",
+          self.tail_dropped,
+          self.tail.len()
+        )),
+        Some(self.process.color),
+        self.sender(SenderType::Task),
+      );
+      lines.push(note);
+    }
+    lines.extend(self.tail.iter().cloned());
+    let mut replay = Message::new(
+      MessageType::Replay,
+      Some(self.process.name.clone()),
+      None,
+      Some(self.process.color),
+      self.sender(SenderType::Task),
+    );
+    replay.replay = lines;
+    self
+      .message_tx
+      .send_async(replay)
+      .await
+      .expect("Couldnt send message to main thread");
+  }
+
+  /// Report that this task will never run and produce its timing record.
+  pub async fn skip(&self, reason: String) -> TaskTiming {
+    let data = format!("{} skipped: {}", self.process.raw_cmd, reason);
+    self.send_event(Event::Skipped { reason }, Some(data)).await;
+    self.send_finished().await;
+    self.make_timing(1, 0.0, vec![], TaskStatus::Skipped)
+  }
+
+  /// Run the process to completion, restarting it as configured. Flipping
+  /// `kill_rx` to `true` terminates the running child (see `terminate`) and
+  /// stops any further restarts. `control_rx` does the same for just this
+  /// task, or restarts the child right away without counting towards
+  /// `restart_tries`. A run that outlives `timeout` is stopped and fails
+  /// with `TIMEOUT_EXIT_CODE`, restarting like any other failure. Readiness
+  /// is reported on `ready_tx`: as soon as the process spawns, or once its
+  /// `--ready` check passes. The check only has to pass once, not on every
+  /// restart.
+  pub async fn start(
+    &mut self,
+    mut kill_rx: watch::Receiver<bool>,
+    control_rx: Receiver<Control>,
+    ready_tx: Sender<(usize, Readiness)>,
+  ) -> Result<(i32, TaskTiming)> {
+    let start_time = Instant::now();
+    self.usage = None;
+    let mut attempts: Vec<AttemptTiming> = Vec::new();
+    // Restarts from `restart_tries`, as opposed to ones asked for.
+    let mut auto_restarts = 0;
+    let mut stopped = false;
+
+    let code = loop {
       // Deregister any previous stdin handle (no-op on first iteration)
       if let Some(ref router) = self.input_router {
         router.deregister(self.process.index).await;
       }
-      let attempt_child = self.process.run(self.mlti_config.handle_input);
-      match attempt_child {
-        Ok(mut c) => {
+      let attempt_start = Instant::now();
+      self.timed_out = false;
+      let exit_code = match self.process.run(self.mlti_config.handle_input) {
+        Ok(mut child) => {
+          self.pid = child.id();
+          self.tail.clear();
+          self.tail_dropped = 0;
+          self.send_event(Event::Spawned, None).await;
           // Register stdin with the input router if enabled
           if let Some(ref router) = self.input_router {
-            if let Some(stdin) = c.stdin.take() {
+            if let Some(stdin) = child.stdin.take() {
               router.register(self.process.index, stdin).await;
             }
           }
-          child = Some(c);
-          break;
+          if self.ready_deadline.is_none() {
+            self.ready_deadline = Some(
+              tokio::time::Instant::now()
+                + std::time::Duration::from_millis(self.mlti_config.ready_timeout),
+            );
+          }
+          if self.ready_check.is_none() && self.readiness == Readiness::Pending {
+            self.set_readiness(Readiness::Ready, &ready_tx).await;
+          }
+          Some(
+            self
+              .supervise(child, &mut kill_rx, &control_rx, &ready_tx)
+              .await,
+          )
         }
         Err(e) => {
+          self.pid = None;
           self
             .send_error(format!("{}: {}", "Encountered an Error".red(), e.red()))
             .await;
-          if restart_attempts <= 0 {
-            if self.mlti_config.kill_others_on_fail {
-              self
-                .shutdown_tx
-                .send_async(Message::new(
-                  MessageType::KillAllOnError,
-                  None,
-                  None,
-                  None,
-                  build_message_sender(SenderType::Task, None, None),
-                ))
-                .await
-                .expect("Could not send message on channel.");
+          None
+        }
+      };
+
+      let code = match exit_code {
+        _ if self.timed_out => TIMEOUT_EXIT_CODE,
+        Some(code) => code,
+        None => 1,
+      };
+      attempts.push(AttemptTiming {
+        exit_code: code,
+        duration_secs: attempt_start.elapsed().as_secs_f64(),
+      });
+
+      match self.requested.take() {
+        Some(Control::Stop(_)) => {
+          stopped = true;
+          break code;
+        }
+        Some(control @ (Control::Restart(_) | Control::Changed(..)))
+          if !*kill_rx.borrow() =>
+        {
+          self.restarts += 1;
+          let event = Event::Restarted {
+            attempt: self.restarts,
+            delay_ms: 0,
+          };
+          let data = match control {
+            Control::Changed(_, path) => {
+              format!("{} restarting ({} changed)", self.process.raw_cmd, path)
             }
-            break;
-          } else {
-            self
-              .send_error(format!(
-                "{}{}",
-                "Process failed to start, retrying in ".red(),
-                get_relative_time_from_ms(self.mlti_config.restart_after)
-              ))
-              .await;
-            restart_attempts -= 1;
-            tokio::time::sleep(std::time::Duration::from_millis(
-              self.mlti_config.restart_after as u64,
+            _ => format!("{} restarting (requested)", self.process.raw_cmd),
+          };
+          self.send_event(event, Some(data)).await;
+          continue;
+        }
+        _ => {}
+      }
+
+      let should_restart = code != 0 || self.mlti_config.restart_always;
+      if *kill_rx.borrow() || !should_restart || !self.can_restart(auto_restarts) {
+        if exit_code.is_none() {
+          self
+            .send_text(format!(
+              "{}",
+              "Encountered an Error: Could not start process.".red(),
             ))
             .await;
-          }
         }
+        break code;
       }
-    }
 
-    let mut child = match child {
-      Some(c) => c,
-      None => {
-        self
-          .send_error(format!(
-            "{}",
-            "Encountered an Error: Could not start process.".red(),
-          ))
-          .await;
-        return Ok((1, self.make_timing(1, 0.0)));
+      self.restarts += 1;
+      auto_restarts += 1;
+      let delay = if self.mlti_config.restart_after > 0 {
+        format!(
+          " in {}",
+          get_relative_time_from_ms(self.mlti_config.restart_after)
+        )
+      } else {
+        String::new()
+      };
+      let label = self.attempt_label(auto_restarts);
+      let event = Event::Restarted {
+        attempt: auto_restarts,
+        delay_ms: self.mlti_config.restart_after.max(0),
+      };
+      let data = if exit_code.is_some() {
+        format!("{} restarting{} ({})", self.process.raw_cmd, delay, label)
+      } else {
+        format!(
+          "{}{} ({})",
+          "Process failed to start, retrying".red(),
+          delay,
+          label
+        )
+      };
+      self.send_event(event, Some(data)).await;
+      let delay = tokio::time::sleep(std::time::Duration::from_millis(
+        self.mlti_config.restart_after.max(0) as u64,
+      ));
+      tokio::select! {
+        _ = delay => {}
+        Ok(()) = kill_rx.changed() => break code,
+        Ok(control) = control_rx.recv_async() => {
+          // A restart just skips the rest of the wait.
+          if let Control::Stop(_) = control {
+            stopped = true;
+            break code;
+          }
+        }
       }
     };
 
-    let start_time = Instant::now();
+    let killed = stopped || *kill_rx.borrow();
+    self.exit_code = Some(code);
+    // Deregister stdin from the input router
+    if let Some(ref router) = self.input_router {
+      router.deregister(self.process.index).await;
+    }
 
+    // A process we killed ourselves must not trigger another round of
+    // kill-others.
+    if !killed && code != 0 && self.mlti_config.kill_others_on_fail {
+      self
+        .shutdown_tx
+        .send_async(Message::new(
+          MessageType::KillAllOnError,
+          None,
+          None,
+          None,
+          build_message_sender(SenderType::Task, None, None),
+        ))
+        .await
+        .expect("Could not send message on channel.");
+    }
+    if !killed && self.mlti_config.kill_others {
+      self
+        .shutdown_tx
+        .send_async(Message::new(
+          MessageType::KillOthers,
+          None,
+          None,
+          None,
+          build_message_sender(SenderType::Task, None, None),
+        ))
+        .await
+        .expect("Could not send message on channel.");
+    }
+
+    self.send_finished().await;
+
+    let status = if killed {
+      TaskStatus::Killed
+    } else if self.timed_out {
+      TaskStatus::TimedOut
+    } else {
+      TaskStatus::Exited
+    };
+    Ok((
+      code,
+      self.make_timing(code, start_time.elapsed().as_secs_f64(), attempts, status),
+    ))
+  }
+
+  /// Stream a spawned child's output until it exits and both pipes close,
+  /// then report the exit code. A kill request terminates the child's
+  /// whole process group, escalating to SIGKILL if anything in the group is
+  /// still holding the pipes open after `kill_timeout`. Outliving `timeout`
+  /// does the same. Meanwhile a polled `--ready` check runs until it passes
+  /// or `ready_timeout` is up, and with `--analyze` the child's process
+  /// tree is sampled by the `UsageMonitor`.
+  async fn supervise(
+    &mut self,
+    mut child: Child,
+    kill_rx: &mut watch::Receiver<bool>,
+    control_rx: &Receiver<Control>,
+    ready_tx: &Sender<(usize, Readiness)>,
+  ) -> i32 {
+    // The child leads its own process group (see `Process::run`), so its
+    // pid doubles as the group id. Grab it now: `id()` is `None` once reaped.
+    let pgid = child.id();
     let stdout = child
       .stdout
       .take()
@@ -163,40 +631,90 @@ impl Task {
     let mut stdout_reader = BufReader::new(stdout).lines();
     let mut stderr_reader = BufReader::new(stderr).lines();
 
-    let handle = tokio::spawn(async move {
-      child
-        .wait()
-        .await
-        .expect("child process encountered an error")
-    });
-
     let mut stdout_open = true;
     let mut stderr_open = true;
+    let mut status: Option<ExitStatus> = None;
+    // Set once a kill has been requested: the point at which we give up on
+    // the configured signal and escalate to SIGKILL.
+    let mut kill_deadline: Option<tokio::time::Instant> = None;
+    let mut escalated = false;
+    let mut kill_rx_open = true;
+    let timeout = self.mlti_config.timeout;
+    let timeout_deadline =
+      timeout.map(|timeout| tokio::time::Instant::now() + timeout);
+
+    let polled_check = self.ready_check.clone().filter(ReadyCheck::is_polled);
+    let probe = async move {
+      match polled_check {
+        Some(check) => check.wait().await,
+        None => std::future::pending().await,
+      }
+    };
+    tokio::pin!(probe);
+
+    let monitor = self.usage_monitor.clone();
+    let sampled = monitor.as_ref().zip(pgid);
+    if let Some((monitor, session)) = sampled {
+      monitor.watch(session);
+    }
 
     loop {
-      if !stdout_open && !stderr_open {
+      if status.is_some() && ((!stdout_open && !stderr_open) || escalated) {
         break;
       }
 
       tokio::select! {
+        result = child.wait(), if status.is_none() => {
+          status = Some(result.expect("child process encountered an error"));
+        }
+        changed = kill_rx.changed(), if kill_rx_open && kill_deadline.is_none() => {
+          if changed.is_err() {
+            kill_rx_open = false;
+          } else if *kill_rx.borrow() {
+            kill_deadline = Some(self.stop_child(&mut child, pgid).await);
+          }
+        }
+        Ok(control) = control_rx.recv_async(), if kill_deadline.is_none() => {
+          self.requested = Some(control);
+          kill_deadline = Some(self.stop_child(&mut child, pgid).await);
+        }
+        _ = tokio::time::sleep_until(
+          timeout_deadline.unwrap_or_else(tokio::time::Instant::now)
+        ), if timeout_deadline.is_some() && kill_deadline.is_none() => {
+          self.timed_out = true;
+          let after = timeout.expect("deadline comes from the timeout");
+          let data = format!(
+            "{} timed out after {}",
+            self.process.raw_cmd,
+            format_duration(after)
+          );
+          let event = Event::TimedOut {
+            after_ms: after.as_millis() as u64,
+          };
+          self.send_event(event, Some(data)).await;
+          kill_deadline = Some(self.stop_child(&mut child, pgid).await);
+        }
+        _ = tokio::time::sleep_until(
+          kill_deadline.unwrap_or_else(tokio::time::Instant::now)
+        ), if kill_deadline.is_some() && !escalated => {
+          escalated = true;
+          self.force_kill(&mut child, pgid);
+          let signal = crate::signal::KillSignal::Kill.name().to_string();
+          self.send_event(Event::Killed { signal }, None).await;
+        }
+        _ = &mut probe, if self.readiness == Readiness::Pending => {
+          self.set_readiness(Readiness::Ready, ready_tx).await;
+        }
+        _ = tokio::time::sleep_until(
+          self.ready_deadline.unwrap_or_else(tokio::time::Instant::now)
+        ), if self.readiness == Readiness::Pending
+          && self.ready_deadline.is_some() => {
+          self.set_readiness(Readiness::TimedOut, ready_tx).await;
+        }
         result = stdout_reader.next_line(), if stdout_open => {
           match result {
             Ok(Some(line)) => {
-              self
-                .message_tx
-                .send_async(Message::new(
-                  MessageType::Text,
-                  Some(self.process.name.clone()),
-                  Some(line),
-                  Some(self.process.color),
-                  build_message_sender(
-                    SenderType::Process,
-                    Some(self.process.index),
-                    Some(self.process.name.clone()),
-                  ),
-                ))
-                .await
-                .expect("Couldnt send message to main thread");
+              self.handle_line(line, Stream::Stdout, ready_tx).await
             }
             Ok(None) => stdout_open = false,
             Err(_) => stdout_open = false,
@@ -205,21 +723,7 @@ impl Task {
         result = stderr_reader.next_line(), if stderr_open => {
           match result {
             Ok(Some(line)) => {
-              self
-                .message_tx
-                .send_async(Message::new(
-                  MessageType::Text,
-                  Some(self.process.name.clone()),
-                  Some(line),
-                  Some(self.process.color),
-                  build_message_sender(
-                    SenderType::Process,
-                    Some(self.process.index),
-                    Some(self.process.name.clone()),
-                  ),
-                ))
-                .await
-                .expect("Couldnt send message to main thread");
+              self.handle_line(line, Stream::Stderr, ready_tx).await
             }
             Ok(None) => stderr_open = false,
             Err(_) => stderr_open = false,
@@ -227,52 +731,91 @@ impl Task {
         }
       }
     }
-    let status = handle.await.unwrap();
-    let code = status.code().unwrap_or(-1);
-    self.exit_code = Some(code);
-    // Deregister stdin from the input router
-    if let Some(ref router) = self.input_router {
-      router.deregister(self.process.index).await;
+    if let Some((monitor, session)) = sampled {
+      self
+        .usage
+        .get_or_insert_default()
+        .add(&monitor.finish(session));
+    }
+    let (code, signal) = describe_exit(status.expect("loop exits after wait"));
+    let reason = match &signal {
+      Some(signal) => format!("signal {}", signal),
+      None => format!("code {}", code),
+    };
+    // A process mlti killed didn't fail on its own; don't dump its output.
+    // One that timed out did.
+    let failed = (code != 0 && kill_deadline.is_none()) || self.timed_out;
+    if failed && self.mlti_config.show_output_on_failure {
+      self.replay_output().await;
     }
+    let data = format!("{} exited with {}", self.process.raw_cmd, reason);
     self
-      .message_tx
-      .send_async(Message::new(
-        MessageType::Text,
-        Some(self.process.name.clone()),
-        Some(format!(
-          "{} exited with code {}",
-          self.process.raw_cmd, code
-        )),
-        Some(self.process.color),
-        build_message_sender(SenderType::Task, None, None),
-      ))
-      .await
-      .expect("Couldnt send message to main thread");
-    if self.mlti_config.kill_others {
-      self
-        .shutdown_tx
-        .send_async(Message::new(
-          MessageType::KillOthers,
-          None,
-          None,
-          None,
-          build_message_sender(SenderType::Task, None, None),
-        ))
-        .await
-        .expect("Could not send message on channel.");
+      .send_event(Event::Exited { code, signal }, Some(data))
+      .await;
+    code
+  }
+
+  /// Start stopping the child and report it, returning when to give up
+  /// and SIGKILL it instead.
+  async fn stop_child(
+    &self,
+    child: &mut Child,
+    pgid: Option<u32>,
+  ) -> tokio::time::Instant {
+    self.terminate(child, pgid);
+    let signal = self.mlti_config.kill_signal.name().to_string();
+    self.send_event(Event::Killed { signal }, None).await;
+    tokio::time::Instant::now()
+      + std::time::Duration::from_millis(self.mlti_config.kill_timeout)
+  }
+
+  /// Ask the child's process group to stop using the configured kill
+  /// signal. The group id stays valid after the leader exits for as long as
+  /// any member is alive, which is exactly when we still need it.
+  fn terminate(&self, child: &mut Child, pgid: Option<u32>) {
+    match pgid {
+      #[cfg(unix)]
+      Some(pgid) => crate::signal::signal_group(pgid, self.mlti_config.kill_signal),
+      _ => {
+        child.start_kill().ok();
+      }
     }
+  }
 
-    let code = self.exit_code.unwrap_or(1);
-    Ok((
-      code,
-      self.make_timing(code, start_time.elapsed().as_secs_f64()),
-    ))
+  /// SIGKILL the child's whole process group.
+  fn force_kill(&self, child: &mut Child, pgid: Option<u32>) {
+    match pgid {
+      #[cfg(unix)]
+      Some(pgid) => {
+        crate::signal::signal_group(pgid, crate::signal::KillSignal::Kill)
+      }
+      _ => {
+        child.start_kill().ok();
+      }
+    }
+  }
+}
+
+/// Turn an exit status into an exit code and, if a signal ended the
+/// process, that signal's name, e.g. `(0, None)` or
+/// `(143, Some("SIGTERM"))`. Processes killed by a signal get the shell
+/// convention of `128 + signal`.
+fn describe_exit(status: ExitStatus) -> (i32, Option<String>) {
+  if let Some(code) = status.code() {
+    return (code, None);
+  }
+  #[cfg(unix)]
+  {
+    use std::os::unix::process::ExitStatusExt;
+    if let Some(signal) = status.signal() {
+      return (128 + signal, Some(crate::signal::signal_name(signal)));
+    }
   }
+  (-1, None)
 }
 
 fn get_relative_time_from_ms(ms: i64) -> String {
-  let dt = Local::now() + Duration::milliseconds(ms);
-  let ht = HumanTime::from(dt);
+  let ht = HumanTime::from(Duration::milliseconds(ms));
 
   ht.to_text_en(Accuracy::Precise, Tense::Present)
 }
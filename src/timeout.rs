@@ -0,0 +1,164 @@
+use std::time::Duration;
+
+use crate::config::ProcessOptions;
+use crate::input_router::resolve_target;
+
+/// Parse a duration such as `30s`, `500ms`, `10m`, `1h` or `1m30s`. A bare
+/// number is seconds.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+  let invalid = || {
+    format!(
+      "Invalid duration '{}': expected a number with ms, s, m or h, e.g. 30s",
+      s
+    )
+  };
+  let s = s.trim();
+  if s.is_empty() {
+    return Err(invalid());
+  }
+  if let Ok(secs) = s.parse::<u64>() {
+    return Ok(Duration::from_secs(secs));
+  }
+  let mut total = Duration::ZERO;
+  let mut rest = s;
+  while !rest.is_empty() {
+    let digits = rest
+      .find(|c: char| !c.is_ascii_digit())
+      .unwrap_or(rest.len());
+    let value: u64 = rest[..digits].parse().map_err(|_| invalid())?;
+    rest = &rest[digits..];
+    let unit = rest
+      .find(|c: char| c.is_ascii_digit())
+      .unwrap_or(rest.len());
+    let secs = |size: u64| value.checked_mul(size).map(Duration::from_secs);
+    let part = match &rest[..unit] {
+      "ms" => Some(Duration::from_millis(value)),
+      "s" => Some(Duration::from_secs(value)),
+      "m" => secs(60),
+      "h" => secs(3600),
+      _ => None,
+    };
+    total = part
+      .and_then(|part| total.checked_add(part))
+      .ok_or_else(invalid)?;
+    rest = &rest[unit..];
+  }
+  Ok(total)
+}
+
+/// The shortest way to write `duration` that `parse_duration` reads back,
+/// e.g. `1m30s` or `500ms`.
+pub fn format_duration(duration: Duration) -> String {
+  let mut secs = duration.as_secs();
+  let millis = duration.subsec_millis();
+  let mut text = String::new();
+  for (unit, size) in [("h", 3600), ("m", 60)] {
+    if secs >= size {
+      text.push_str(&format!("{}{}", secs / size, unit));
+      secs %= size;
+    }
+  }
+  if secs > 0 || (text.is_empty() && millis == 0) {
+    text.push_str(&format!("{}s", secs));
+  }
+  if millis > 0 {
+    text.push_str(&format!("{}ms", millis));
+  }
+  text
+}
+
+/// Apply `--timeout` specs on top of each process's own `timeout` from the
+/// config file. A spec is either `target=duration`, for one process, or a
+/// bare duration that replaces `default` for every process without one of
+/// its own. A timeout of 0 means no limit.
+pub fn apply_timeouts(
+  options: &mut [ProcessOptions],
+  specs: &[String],
+  default: Option<&str>,
+  names: &[Option<String>],
+) -> Result<(), String> {
+  let num_processes = options.len();
+  let parse = |duration: &str| {
+    parse_duration(duration).map_err(|e| format!("--timeout: {}", e))
+  };
+  let mut default = default.map(parse).transpose()?;
+  for spec in specs {
+    match spec.split_once('=') {
+      Some((target, duration)) => {
+        let index =
+          resolve_target(target.trim(), names, num_processes).ok_or_else(|| {
+            format!(
+              "--timeout: \"{}\" does not match any process name or index",
+              target.trim()
+            )
+          })?;
+        options[index].timeout = Some(parse(duration)?);
+      }
+      None => default = Some(parse(spec)?),
+    }
+  }
+  for options in options.iter_mut() {
+    if options.timeout.is_none() {
+      options.timeout = default;
+    }
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn durations_round_trip() {
+    let secs = Duration::from_secs;
+    assert_eq!(parse_duration("30s").unwrap(), secs(30));
+    assert_eq!(parse_duration("45").unwrap(), secs(45));
+    assert_eq!(parse_duration("10m").unwrap(), secs(600));
+    assert_eq!(parse_duration("1h").unwrap(), secs(3600));
+    assert_eq!(parse_duration("1m30s").unwrap(), secs(90));
+    assert_eq!(
+      parse_duration("1s500ms").unwrap(),
+      Duration::from_millis(1500)
+    );
+    for bad in ["", "s", "10x", "1.5s", "-3s", "ten", "99999999999999999h"] {
+      assert!(parse_duration(bad).is_err(), "{}", bad);
+    }
+
+    assert_eq!(format_duration(secs(30)), "30s");
+    assert_eq!(format_duration(secs(90)), "1m30s");
+    assert_eq!(format_duration(secs(7200)), "2h");
+    assert_eq!(format_duration(Duration::from_millis(500)), "500ms");
+    assert_eq!(format_duration(Duration::ZERO), "0s");
+  }
+
+  #[test]
+  fn specs_override_the_default_and_the_config() {
+    let names = vec![
+      Some("tests".to_string()),
+      Some("lint".to_string()),
+      Some("server".to_string()),
+    ];
+    let mut options = vec![ProcessOptions::default(); 3];
+    options[1].timeout = Some(Duration::from_secs(60));
+    let specs = vec!["tests=5m".to_string(), "2=0".to_string()];
+    apply_timeouts(&mut options, &specs, Some("30s"), &names).unwrap();
+    assert_eq!(options[0].timeout, Some(Duration::from_secs(300)));
+    assert_eq!(options[1].timeout, Some(Duration::from_secs(60)));
+    assert_eq!(options[2].timeout, Some(Duration::ZERO));
+
+    // A bare duration on the command line replaces the default.
+    let mut options = vec![ProcessOptions::default(); 3];
+    let specs = vec!["10s".to_string()];
+    apply_timeouts(&mut options, &specs, Some("30s"), &names).unwrap();
+    assert_eq!(options[2].timeout, Some(Duration::from_secs(10)));
+
+    let mut options = vec![ProcessOptions::default(); 3];
+    let err = apply_timeouts(&mut options, &["web=1s".to_string()], None, &names)
+      .unwrap_err();
+    assert!(err.contains("does not match"), "{}", err);
+    let err = apply_timeouts(&mut options, &["lint=soon".to_string()], None, &names)
+      .unwrap_err();
+    assert!(err.contains("Invalid duration 'soon'"), "{}", err);
+  }
+}
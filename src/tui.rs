@@ -0,0 +1,552 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use flume::{Receiver, Sender};
+use ratatui::crossterm::event::{
+  self, Event as TermEvent, KeyCode, KeyEvent, KeyEventKind, KeyModifiers,
+};
+use ratatui::layout::{Constraint, Layout, Position};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph};
+use ratatui::Frame;
+
+use crate::input_router::InputRouter;
+use crate::message::{
+  build_message_sender, Message, MessageType, SenderType, Stream,
+};
+use crate::process_log::strip_ansi;
+use crate::scheduler::Control;
+use crate::status::{DisplayState, ProcessStatus};
+
+/// How many lines of output each process keeps to scroll back through.
+const MAX_LINES: usize = 5000;
+
+/// Most messages drawn in one go, so a flood of output can't hold up keys.
+const MAX_BATCH: usize = 1000;
+
+const HELP: &str =
+  "↑/↓ select  r restart  x stop  i input  PgUp/PgDn scroll  q quit";
+
+fn state_color(state: DisplayState) -> Color {
+  match state {
+    DisplayState::Queued | DisplayState::Skipped => Color::DarkGray,
+    DisplayState::Running | DisplayState::Restarting => Color::Yellow,
+    DisplayState::Ready | DisplayState::Exited(0) => Color::Green,
+    DisplayState::Exited(_) | DisplayState::Killed | DisplayState::TimedOut => {
+      Color::Red
+    }
+  }
+}
+
+struct LogLine {
+  text: String,
+  style: Style,
+}
+
+struct ProcessView {
+  name: String,
+  command: String,
+  status: ProcessStatus,
+  lines: VecDeque<LogLine>,
+  // How many lines up from the bottom the log pane is scrolled.
+  scroll: usize,
+}
+
+impl ProcessView {
+  fn push(&mut self, text: &str, style: Style) {
+    for line in strip_ansi(text).lines() {
+      // Stay on the same lines when scrolled back.
+      if self.scroll > 0 {
+        self.scroll += 1;
+      }
+      self.lines.push_back(LogLine {
+        text: line.to_string(),
+        style,
+      });
+      if self.lines.len() > MAX_LINES {
+        self.lines.pop_front();
+      }
+    }
+    self.scroll = self.scroll.min(self.lines.len());
+  }
+}
+
+/// What a key press asks the rest of mlti to do.
+#[derive(Debug, PartialEq)]
+pub enum Action {
+  Control(Control),
+  /// Send a line to a process's stdin.
+  Input(usize, String),
+  Quit,
+}
+
+/// The dashboard's state. Messages and key presses update it; `draw`
+/// renders it.
+pub struct App {
+  processes: Vec<ProcessView>,
+  focused: usize,
+  // The line being typed for the focused process, while the input box is
+  // open.
+  input: Option<String>,
+  // The latest message from mlti itself, such as input feedback.
+  status: String,
+  // How many lines the log pane showed last time, to page by.
+  page: usize,
+}
+
+impl App {
+  /// A dashboard for processes with these names and commands, by index.
+  pub fn new(processes: Vec<(String, String)>) -> Self {
+    Self {
+      processes: processes
+        .into_iter()
+        .map(|(name, command)| ProcessView {
+          name,
+          command,
+          status: ProcessStatus::default(),
+          lines: VecDeque::new(),
+          scroll: 0,
+        })
+        .collect(),
+      focused: 0,
+      input: None,
+      status: String::new(),
+      page: 10,
+    }
+  }
+
+  /// Update the dashboard with a message from the output channel.
+  pub fn apply(&mut self, message: Message) {
+    if let MessageType::Replay = message.type_ {
+      for message in message.replay {
+        self.apply(message);
+      }
+      return;
+    }
+    let shown = matches!(
+      message.type_,
+      MessageType::Text | MessageType::Error | MessageType::Ready
+    );
+    let Some(view) = message
+      .sender
+      .index
+      .and_then(|index| self.processes.get_mut(index))
+    else {
+      if shown && !message.data.is_empty() {
+        self.status = strip_ansi(&message.data).trim().to_string();
+      }
+      return;
+    };
+
+    view.status.update(&message);
+    if shown {
+      let style = match (&message.sender.type_, message.sender.stream) {
+        (_, Some(Stream::Stderr)) => Style::new().fg(Color::Red),
+        (SenderType::Process, _) => Style::new(),
+        // mlti's own status lines.
+        _ => Style::new().add_modifier(Modifier::DIM),
+      };
+      view.push(&message.data, style);
+    }
+  }
+
+  /// Tell the user everything is being stopped.
+  pub fn quit(&mut self) {
+    self.input = None;
+    self.status = "Stopping all processes...".to_string();
+  }
+
+  /// Handle a key press, returning what it asks for, if anything beyond
+  /// changing the view.
+  pub fn handle_key(&mut self, key: KeyEvent) -> Option<Action> {
+    if key.kind != KeyEventKind::Press {
+      return None;
+    }
+    if key.modifiers.contains(KeyModifiers::CONTROL)
+      && key.code == KeyCode::Char('c')
+    {
+      return Some(Action::Quit);
+    }
+
+    if let Some(input) = &mut self.input {
+      match key.code {
+        KeyCode::Enter => {
+          let line = std::mem::take(input);
+          return Some(Action::Input(self.focused, line));
+        }
+        KeyCode::Esc => self.input = None,
+        KeyCode::Backspace => {
+          input.pop();
+        }
+        KeyCode::Char(c) => input.push(c),
+        _ => {}
+      }
+      return None;
+    }
+
+    let last = self.processes.len().saturating_sub(1);
+    match key.code {
+      KeyCode::Char('q') => return Some(Action::Quit),
+      KeyCode::Char('r') => {
+        return Some(Action::Control(Control::Restart(self.focused)))
+      }
+      KeyCode::Char('x') => {
+        return Some(Action::Control(Control::Stop(self.focused)))
+      }
+      KeyCode::Up | KeyCode::Char('k') => {
+        self.focused = self.focused.saturating_sub(1)
+      }
+      KeyCode::Down | KeyCode::Char('j') => {
+        self.focused = (self.focused + 1).min(last)
+      }
+      KeyCode::Char('i') | KeyCode::Enter => self.input = Some(String::new()),
+      KeyCode::PageUp => self.scroll_by(self.page as isize),
+      KeyCode::PageDown => self.scroll_by(-(self.page as isize)),
+      KeyCode::Home => self.scroll_by(isize::MAX),
+      KeyCode::End => self.scroll_by(isize::MIN),
+      _ => {}
+    }
+    None
+  }
+
+  /// Scroll the focused log pane up by `lines`, or down if negative.
+  fn scroll_by(&mut self, lines: isize) {
+    let page = self.page;
+    if let Some(view) = self.processes.get_mut(self.focused) {
+      let top = view.lines.len().saturating_sub(page);
+      view.scroll = view.scroll.saturating_add_signed(lines).min(top);
+    }
+  }
+
+  pub fn draw(&mut self, frame: &mut Frame) {
+    let footer_height = if self.input.is_some() { 3 } else { 1 };
+    let [main, footer] =
+      Layout::vertical([Constraint::Min(1), Constraint::Length(footer_height)])
+        .areas(frame.area());
+
+    let sidebar_width = self
+      .processes
+      .iter()
+      .map(|p| p.name.chars().count() + p.status.state.label().len() + 5)
+      .max()
+      .unwrap_or(0)
+      .clamp(20, 40) as u16;
+    let [sidebar, pane] =
+      Layout::horizontal([Constraint::Length(sidebar_width), Constraint::Min(1)])
+        .areas(main);
+
+    let items: Vec<ListItem> = self
+      .processes
+      .iter()
+      .map(|p| {
+        ListItem::new(Line::from(vec![
+          Span::raw(p.name.clone()),
+          Span::raw(" "),
+          Span::styled(
+            p.status.state.label(),
+            Style::new().fg(state_color(p.status.state)),
+          ),
+        ]))
+      })
+      .collect();
+    let list = List::new(items)
+      .block(Block::bordered().title(" processes "))
+      .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+    let mut list_state = ListState::default().with_selected(Some(self.focused));
+    frame.render_stateful_widget(list, sidebar, &mut list_state);
+
+    self.page = pane.height.saturating_sub(2).max(1) as usize;
+    if let Some(view) = self.processes.get(self.focused) {
+      let end = view.lines.len() - view.scroll;
+      let start = end.saturating_sub(self.page);
+      let lines: Vec<Line> = view
+        .lines
+        .range(start..end)
+        .map(|l| Line::styled(l.text.as_str(), l.style))
+        .collect();
+      let mut title = format!(" {} ", view.command);
+      if view.scroll > 0 {
+        title.push_str(&format!("(+{} below) ", view.scroll));
+      }
+      frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title(title)),
+        pane,
+      );
+    }
+
+    match (&self.input, self.processes.get(self.focused)) {
+      (Some(input), Some(view)) => {
+        let title = format!(" input for {} (Enter sends, Esc closes) ", view.name);
+        frame.render_widget(
+          Paragraph::new(input.as_str()).block(Block::bordered().title(title)),
+          footer,
+        );
+        let x = footer.x + 1 + input.chars().count() as u16;
+        frame.set_cursor_position(Position::new(
+          x.min(footer.right().saturating_sub(2)),
+          footer.y + 1,
+        ));
+      }
+      _ => {
+        let mut help = vec![Span::styled(HELP, Style::new().fg(Color::DarkGray))];
+        if !self.status.is_empty() {
+          help.push(Span::raw("  "));
+          help.push(Span::raw(self.status.as_str()));
+        }
+        frame.render_widget(Paragraph::new(Line::from(help)), footer);
+      }
+    }
+  }
+}
+
+/// Show the dashboard until `messages` delivers a `Kill`, meaning every
+/// process has stopped. Restarts and stops go to the scheduler on
+/// `control_tx`, quitting goes out as a `KillAll` on `shutdown_tx`.
+pub async fn run(
+  mut app: App,
+  messages: Receiver<Message>,
+  control_tx: Sender<Control>,
+  shutdown_tx: Sender<Message>,
+  input_router: Option<Arc<InputRouter>>,
+) -> std::io::Result<()> {
+  let mut terminal = ratatui::try_init()?;
+
+  // crossterm only offers a blocking read, so keys are read on their own
+  // thread. It polls so it can notice when the dashboard closes.
+  let (term_tx, term_rx) = flume::unbounded::<TermEvent>();
+  let done = Arc::new(AtomicBool::new(false));
+  let reader = {
+    let done = done.clone();
+    std::thread::spawn(move || {
+      while !done.load(Ordering::Relaxed) {
+        match event::poll(Duration::from_millis(100)) {
+          Ok(true) => {
+            let sent = event::read().is_ok_and(|event| term_tx.send(event).is_ok());
+            if !sent {
+              break;
+            }
+          }
+          Ok(false) => {}
+          Err(_) => break,
+        }
+      }
+    })
+  };
+
+  // Writing to a child's stdin may block; do it off the draw loop, in the
+  // order the lines were typed.
+  let (input_tx, input_rx) = flume::unbounded::<(usize, String)>();
+  let input_handle = input_router.map(|router| {
+    tokio::spawn(async move {
+      while let Ok((index, line)) = input_rx.recv_async().await {
+        router.route_to(index, &line).await;
+      }
+    })
+  });
+
+  let result = 'draw: loop {
+    if let Err(e) = terminal.draw(|frame| app.draw(frame)) {
+      break Err(e);
+    }
+    tokio::select! {
+      Ok(message) = messages.recv_async() => {
+        let mut next = Some(message);
+        for _ in 0..MAX_BATCH {
+          let Some(message) = next.take() else { break };
+          if let MessageType::Kill = message.type_ {
+            break 'draw Ok(());
+          }
+          app.apply(message);
+          next = messages.try_recv().ok();
+        }
+      }
+      Ok(event) = term_rx.recv_async() => {
+        // Anything other than a key, like a resize, just needs a redraw.
+        let TermEvent::Key(key) = event else { continue };
+        match app.handle_key(key) {
+          Some(Action::Control(control)) => {
+            control_tx.send(control).ok();
+          }
+          Some(Action::Input(index, line)) => {
+            input_tx.send((index, line)).ok();
+          }
+          Some(Action::Quit) => {
+            app.quit();
+            shutdown_tx
+              .send(Message::new(
+                MessageType::KillAll,
+                None,
+                None,
+                None,
+                build_message_sender(SenderType::Other, None, None),
+              ))
+              .ok();
+          }
+          None => {}
+        }
+      }
+      else => break Ok(()),
+    }
+  };
+
+  done.store(true, Ordering::Relaxed);
+  ratatui::restore();
+  reader.join().ok();
+  if let Some(handle) = input_handle {
+    handle.abort();
+  }
+  result
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::message::Event;
+  use ratatui::backend::TestBackend;
+  use ratatui::Terminal;
+
+  fn app() -> App {
+    App::new(vec![
+      ("api".to_string(), "cargo run".to_string()),
+      ("web".to_string(), "npm start".to_string()),
+    ])
+  }
+
+  fn event(index: usize, event: Event, data: Option<&str>) -> Message {
+    let type_ = if data.is_some() {
+      MessageType::Text
+    } else {
+      MessageType::Lifecycle
+    };
+    let mut message = Message::new(
+      type_,
+      None,
+      data.map(str::to_string),
+      None,
+      build_message_sender(SenderType::Task, Some(index), None),
+    );
+    message.event = Some(event);
+    message
+  }
+
+  fn line(index: usize, data: &str) -> Message {
+    Message::new(
+      MessageType::Text,
+      None,
+      Some(data.to_string()),
+      None,
+      build_message_sender(SenderType::Process, Some(index), None),
+    )
+  }
+
+  fn key(code: KeyCode) -> KeyEvent {
+    KeyEvent::new(code, KeyModifiers::NONE)
+  }
+
+  fn render(app: &mut App) -> String {
+    let mut terminal = Terminal::new(TestBackend::new(80, 8)).unwrap();
+    terminal.draw(|frame| app.draw(frame)).unwrap();
+    let buffer = terminal.backend().buffer();
+    (0..buffer.area.height)
+      .map(|y| {
+        (0..buffer.area.width)
+          .map(|x| buffer[(x, y)].symbol())
+          .collect::<String>()
+      })
+      .collect::<Vec<_>>()
+      .join("\n")
+  }
+
+  #[test]
+  fn output_goes_to_its_process_and_the_rest_to_the_status() {
+    let mut app = app();
+    app.apply(line(1, "\u{1b}[32mlistening\u{1b}[0m"));
+    app.apply(line(5, "out of range"));
+    app.apply(Message::new(
+      MessageType::Text,
+      None,
+      Some("[mlti] -> web: hi".to_string()),
+      None,
+      build_message_sender(SenderType::Main, None, None),
+    ));
+
+    assert!(app.processes[0].lines.is_empty());
+    assert_eq!(app.processes[1].lines[0].text, "listening");
+    assert_eq!(app.status, "[mlti] -> web: hi");
+  }
+
+  #[test]
+  fn keys_focus_control_and_send_input() {
+    let mut app = app();
+    assert_eq!(app.handle_key(key(KeyCode::Down)), None);
+    assert_eq!(app.handle_key(key(KeyCode::Char('j'))), None);
+    assert_eq!(app.focused, 1);
+    assert_eq!(
+      app.handle_key(key(KeyCode::Char('r'))),
+      Some(Action::Control(Control::Restart(1)))
+    );
+    app.handle_key(key(KeyCode::Char('k')));
+    assert_eq!(
+      app.handle_key(key(KeyCode::Char('x'))),
+      Some(Action::Control(Control::Stop(0)))
+    );
+
+    app.handle_key(key(KeyCode::Char('i')));
+    for c in "q!x".chars() {
+      assert_eq!(app.handle_key(key(KeyCode::Char(c))), None);
+    }
+    app.handle_key(key(KeyCode::Backspace));
+    assert_eq!(
+      app.handle_key(key(KeyCode::Enter)),
+      Some(Action::Input(0, "q!".to_string()))
+    );
+    app.handle_key(key(KeyCode::Esc));
+    assert_eq!(app.handle_key(key(KeyCode::Char('q'))), Some(Action::Quit));
+    assert_eq!(
+      app.handle_key(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL)),
+      Some(Action::Quit)
+    );
+  }
+
+  #[test]
+  fn scrolling_keeps_position_as_output_arrives() {
+    let mut app = app();
+    for n in 0..20 {
+      app.apply(line(0, &format!("line {}", n)));
+    }
+    let screen = render(&mut app);
+    assert!(screen.contains("line 19"), "{}", screen);
+
+    app.handle_key(key(KeyCode::PageUp));
+    app.apply(line(0, "line 20"));
+    let screen = render(&mut app);
+    assert!(!screen.contains("line 20"), "{}", screen);
+    assert!(screen.contains("line 14"), "{}", screen);
+    assert!(screen.contains("below"), "{}", screen);
+
+    app.handle_key(key(KeyCode::End));
+    assert!(render(&mut app).contains("line 20"));
+  }
+
+  #[test]
+  fn sidebar_lists_every_process_with_its_state() {
+    let mut app = app();
+    app.apply(event(0, Event::Spawned, None));
+    app.apply(event(
+      1,
+      Event::Exited {
+        code: 2,
+        signal: None,
+      },
+      Some("npm start exited with code 2"),
+    ));
+    let screen = render(&mut app);
+    assert!(screen.contains("api running"), "{}", screen);
+    assert!(screen.contains("web exited 2"), "{}", screen);
+    assert!(screen.contains("cargo run"), "{}", screen);
+    assert!(screen.contains("q quit"), "{}", screen);
+  }
+}
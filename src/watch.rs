@@ -0,0 +1,297 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use flume::{Receiver, Sender};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use notify::event::ModifyKind;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::input_router::resolve_target;
+use crate::scheduler::Control;
+
+/// How long the watched files must stay untouched before the processes
+/// they belong to restart, so a burst of saves restarts them once.
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// The files one process is restarted for.
+#[derive(Clone, Debug)]
+pub struct WatchSet {
+  /// The process's working directory. Relative globs are resolved
+  /// against it and changed files are reported relative to it.
+  base: PathBuf,
+  globs: GlobSet,
+  /// Directories to watch: the existing part of each glob before its
+  /// first wildcard.
+  roots: Vec<PathBuf>,
+}
+
+impl WatchSet {
+  fn new(patterns: &[String], base: PathBuf) -> Result<Self, String> {
+    let mut builder = GlobSetBuilder::new();
+    let mut roots = Vec::new();
+    for pattern in patterns {
+      let path = base.join(pattern);
+      let text = path.to_string_lossy();
+      add_glob(&mut builder, &text, pattern)?;
+      // A plain directory stands for everything in it.
+      if !has_wildcard(pattern) {
+        let everything = format!("{}/**", text.trim_end_matches('/'));
+        add_glob(&mut builder, &everything, pattern)?;
+      }
+      roots.push(root_of(&path));
+    }
+    let globs = builder.build().map_err(|e| format!("--watch: {}", e))?;
+    Ok(Self { base, globs, roots })
+  }
+
+  pub fn matches(&self, path: &Path) -> bool {
+    self.globs.is_match(path)
+  }
+
+  /// `path` relative to the process's working directory when it's inside
+  /// it.
+  fn display(&self, path: &Path) -> String {
+    path
+      .strip_prefix(&self.base)
+      .unwrap_or(path)
+      .display()
+      .to_string()
+  }
+}
+
+fn add_glob(
+  builder: &mut GlobSetBuilder,
+  glob: &str,
+  pattern: &str,
+) -> Result<(), String> {
+  let glob = GlobBuilder::new(glob)
+    .literal_separator(true)
+    .build()
+    .map_err(|e| format!("Invalid --watch glob '{}': {}", pattern, e))?;
+  builder.add(glob);
+  Ok(())
+}
+
+fn has_wildcard(text: &str) -> bool {
+  text.contains(['*', '?', '[', '{'])
+}
+
+/// The deepest existing directory that holds everything `glob` can match.
+fn root_of(glob: &Path) -> PathBuf {
+  let mut root: PathBuf = glob
+    .components()
+    .take_while(|c| !has_wildcard(&c.as_os_str().to_string_lossy()))
+    .collect();
+  while !root.is_dir() {
+    if !root.pop() {
+      return PathBuf::from(".");
+    }
+  }
+  root
+}
+
+/// Group `--watch target=glob` specs by process. Relative globs are
+/// resolved against the process's working directory, from `cwds`.
+pub fn parse_watch_specs(
+  specs: &[String],
+  names: &[Option<String>],
+  cwds: &[Option<PathBuf>],
+) -> Result<Vec<Option<WatchSet>>, String> {
+  let num_processes = cwds.len();
+  let mut patterns: Vec<Vec<String>> = vec![Vec::new(); num_processes];
+  for spec in specs {
+    let (target, glob) = spec
+      .split_once('=')
+      .ok_or_else(|| format!("Invalid --watch '{}': expected target=glob", spec))?;
+    let index =
+      resolve_target(target.trim(), names, num_processes).ok_or_else(|| {
+        format!(
+          "--watch: \"{}\" does not match any process name or index",
+          target.trim()
+        )
+      })?;
+    patterns[index].push(glob.trim().to_string());
+  }
+  if patterns.iter().all(Vec::is_empty) {
+    return Ok(vec![None; num_processes]);
+  }
+
+  let current_dir = std::env::current_dir()
+    .map_err(|e| format!("--watch: could not read the current directory: {}", e))?;
+  patterns
+    .iter()
+    .zip(cwds)
+    .map(|(patterns, cwd)| {
+      if patterns.is_empty() {
+        return Ok(None);
+      }
+      let base = match cwd {
+        Some(cwd) => current_dir.join(cwd),
+        None => current_dir.clone(),
+      };
+      WatchSet::new(patterns, base).map(Some)
+    })
+    .collect()
+}
+
+/// Whether an event means a file's contents or presence changed.
+fn is_change(kind: &EventKind) -> bool {
+  match kind {
+    EventKind::Create(_) | EventKind::Remove(_) => true,
+    EventKind::Modify(kind) => !matches!(kind, ModifyKind::Metadata(_)),
+    _ => false,
+  }
+}
+
+/// Watches the files of every process with a `WatchSet` and asks the
+/// scheduler to restart a process when its files change.
+pub struct FileWatcher {
+  // Dropping the watcher stops the events.
+  _watcher: RecommendedWatcher,
+  changes: Receiver<PathBuf>,
+  sets: Vec<Option<WatchSet>>,
+}
+
+impl FileWatcher {
+  /// Start watching, so a missing directory or an exhausted inotify limit
+  /// is reported before anything runs.
+  pub fn new(sets: Vec<Option<WatchSet>>) -> Result<Self, String> {
+    let (change_tx, changes) = flume::unbounded();
+    let mut watcher =
+      notify::recommended_watcher(move |result: notify::Result<notify::Event>| {
+        match result {
+          Ok(event) if is_change(&event.kind) => {
+            for path in event.paths {
+              change_tx.send(path).ok();
+            }
+          }
+          _ => {}
+        }
+      })
+      .map_err(|e| format!("Could not watch files: {}", e))?;
+
+    let mut roots: Vec<&PathBuf> =
+      sets.iter().flatten().flat_map(|set| &set.roots).collect();
+    roots.sort();
+    roots.dedup();
+    for root in roots {
+      watcher
+        .watch(root, RecursiveMode::Recursive)
+        .map_err(|e| format!("Could not watch {}: {}", root.display(), e))?;
+    }
+    Ok(Self {
+      _watcher: watcher,
+      changes,
+      sets,
+    })
+  }
+
+  /// Wait for changes and, once they settle for `DEBOUNCE`, send a
+  /// `Control::Changed` for each process they touch, naming the first
+  /// file that changed.
+  pub async fn run(self, control_tx: Sender<Control>) {
+    while let Ok(path) = self.changes.recv_async().await {
+      let mut changed: Vec<Option<String>> = vec![None; self.sets.len()];
+      self.note(&mut changed, &path);
+      loop {
+        match tokio::time::timeout(DEBOUNCE, self.changes.recv_async()).await {
+          Ok(Ok(path)) => self.note(&mut changed, &path),
+          Ok(Err(_)) => return,
+          Err(_) => break,
+        }
+      }
+      for (index, path) in changed.into_iter().enumerate() {
+        let Some(path) = path else { continue };
+        if control_tx.send(Control::Changed(index, path)).is_err() {
+          return;
+        }
+      }
+    }
+  }
+
+  /// Record `path` against every process watching it that hasn't seen a
+  /// change yet.
+  fn note(&self, changed: &mut [Option<String>], path: &Path) {
+    for (slot, set) in changed.iter_mut().zip(&self.sets) {
+      match set {
+        Some(set) if slot.is_none() && set.matches(path) => {
+          *slot = Some(set.display(path));
+        }
+        _ => {}
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn names(names: &[&str]) -> Vec<Option<String>> {
+    names.iter().map(|n| Some(n.to_string())).collect()
+  }
+
+  #[test]
+  fn globs_resolve_against_the_working_directory() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(dir.path().join("api/src")).unwrap();
+    let specs = vec![
+      "api=src/**/*.rs".to_string(),
+      "api=Cargo.toml".to_string(),
+      "1=api/src".to_string(),
+    ];
+    let cwds = vec![Some(dir.path().join("api")), Some(dir.path().to_path_buf())];
+    let sets = parse_watch_specs(&specs, &names(&["api", "web"]), &cwds).unwrap();
+
+    let api = sets[0].as_ref().unwrap();
+    assert!(api.matches(&dir.path().join("api/src/main.rs")));
+    assert!(api.matches(&dir.path().join("api/src/bin/tool.rs")));
+    assert!(api.matches(&dir.path().join("api/Cargo.toml")));
+    assert!(!api.matches(&dir.path().join("api/src/notes.md")));
+    assert!(!api.matches(&dir.path().join("Cargo.toml")));
+    assert_eq!(
+      api.display(&dir.path().join("api/src/main.rs")),
+      Path::new("src").join("main.rs").display().to_string()
+    );
+
+    // A directory matches everything inside it.
+    let web = sets[1].as_ref().unwrap();
+    assert!(web.matches(&dir.path().join("api/src/lib.rs")));
+    assert!(!web.matches(&dir.path().join("api/Cargo.toml")));
+  }
+
+  #[test]
+  fn roots_stop_at_the_first_wildcard_that_exists() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(dir.path().join("src/bin")).unwrap();
+    assert_eq!(
+      root_of(&dir.path().join("src/**/*.rs")),
+      dir.path().join("src")
+    );
+    assert_eq!(
+      root_of(&dir.path().join("src/bin")),
+      dir.path().join("src/bin")
+    );
+    // Files and directories that don't exist yet are watched from the
+    // nearest existing parent.
+    assert_eq!(
+      root_of(&dir.path().join("src/main.rs")),
+      dir.path().join("src")
+    );
+    assert_eq!(root_of(&dir.path().join("gen/*.rs")), dir.path());
+  }
+
+  #[test]
+  fn rejects_bad_specs() {
+    let cwds = vec![None];
+    let err =
+      parse_watch_specs(&["src".to_string()], &names(&["a"]), &cwds).unwrap_err();
+    assert!(err.contains("expected target=glob"), "{}", err);
+    let err =
+      parse_watch_specs(&["b=src".to_string()], &names(&["a"]), &cwds).unwrap_err();
+    assert!(err.contains("does not match"), "{}", err);
+    let err = parse_watch_specs(&["a=src/[".to_string()], &names(&["a"]), &cwds)
+      .unwrap_err();
+    assert!(err.contains("Invalid --watch glob"), "{}", err);
+  }
+}
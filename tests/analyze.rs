@@ -0,0 +1,74 @@
+use std::path::Path;
+
+mod common;
+use common::run_mlti_in;
+
+/// Holds 30 MB in a shell variable for a moment.
+const MEMORY_HOG: &str = "x=$(head -c 30000000 /dev/zero | tr '\\0' a); sleep 1";
+/// Keeps a CPU busy from a background process it starts.
+const CPU_HOG: &str = "yes > /dev/null & sleep 1; kill $!";
+
+#[test]
+fn prints_the_biggest_memory_user_first() {
+  if !Path::new("/proc/self/stat").exists() {
+    return;
+  }
+  let dir = tempfile::tempdir().unwrap();
+  let (code, output) = run_mlti_in(
+    dir.path(),
+    &["--analyze", "-n", "cpu,memory", CPU_HOG, MEMORY_HOG],
+  );
+  assert_eq!(code, 0, "{}", output);
+  let table: Vec<&str> = output
+    .lines()
+    .skip_while(|l| !l.contains("Resource usage:"))
+    .skip(1)
+    .take(3)
+    .collect();
+  assert_eq!(table.len(), 3, "{}", output);
+  assert!(table[0].contains("peak mem"), "{}", output);
+  assert!(table[1].starts_with("  [1] memory"), "{}", output);
+  assert!(table[1].contains(" MiB"), "{}", output);
+  assert!(table[2].starts_with("  [0] cpu"), "{}", output);
+}
+
+#[test]
+fn writes_a_json_report() {
+  if !Path::new("/proc/self/stat").exists() {
+    return;
+  }
+  let dir = tempfile::tempdir().unwrap();
+  let (code, output) = run_mlti_in(
+    dir.path(),
+    &[
+      "--analyze-report",
+      "usage.json",
+      "-n",
+      "cpu,memory",
+      CPU_HOG,
+      MEMORY_HOG,
+    ],
+  );
+  assert_eq!(code, 0, "{}", output);
+  let report = std::fs::read_to_string(dir.path().join("usage.json")).unwrap();
+  let report: serde_json::Value = serde_json::from_str(&report).unwrap();
+  let processes = report["processes"].as_array().unwrap();
+  assert_eq!(processes.len(), 2);
+
+  let cpu = &processes[0]["usage"];
+  assert_eq!(processes[0]["name"], "cpu");
+  // `yes` runs in the background, so this only adds up if descendants
+  // are counted.
+  let cpu_secs =
+    cpu["user_secs"].as_f64().unwrap() + cpu["sys_secs"].as_f64().unwrap();
+  assert!(cpu_secs > 0.2, "{}", report);
+  assert!(cpu["cpu_percent"].as_f64().unwrap() > 20.0, "{}", report);
+  assert!(cpu["wall_secs"].as_f64().unwrap() >= 1.0, "{}", report);
+
+  let memory = &processes[1]["usage"];
+  assert!(
+    memory["peak_rss_bytes"].as_u64().unwrap() > 25_000_000,
+    "{}",
+    report
+  );
+}
@@ -0,0 +1,41 @@
+//! Helpers shared by the integration tests. Each test binary uses only
+//! some of them.
+#![allow(dead_code)]
+
+use std::path::Path;
+use std::process::Command;
+
+/// The mlti binary, unaffected by an `MLTI_CONFIG` set for the test run.
+pub fn mlti() -> Command {
+  let mut command = Command::new(env!("CARGO_BIN_EXE_mlti"));
+  command.env_remove("MLTI_CONFIG");
+  command
+}
+
+/// Run `command` to completion and return (exit code, combined
+/// stdout+stderr).
+pub fn run(command: &mut Command) -> (i32, String) {
+  let output = command.output().expect("failed to run mlti");
+  let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+  text.push_str(&String::from_utf8_lossy(&output.stderr));
+  (output.status.code().unwrap_or(-1), text)
+}
+
+/// Run mlti to completion with colors disabled and return
+/// (exit code, combined stdout+stderr).
+pub fn run_mlti(args: &[&str]) -> (i32, String) {
+  run(mlti().arg("--no-color").args(args))
+}
+
+/// Like `run_mlti`, but in `dir`.
+pub fn run_mlti_in(dir: &Path, args: &[&str]) -> (i32, String) {
+  run(mlti().current_dir(dir).arg("--no-color").args(args))
+}
+
+/// Position of the first line containing `needle`, for ordering checks.
+pub fn line_of(output: &str, needle: &str) -> usize {
+  output
+    .lines()
+    .position(|l| l.contains(needle))
+    .unwrap_or_else(|| panic!("missing {:?} in output:\n{}", needle, output))
+}
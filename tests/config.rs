@@ -0,0 +1,136 @@
+use std::process::Command;
+
+mod common;
+use common::{mlti, run, run_mlti_in};
+
+const CONFIG: &str = r#"
+success = "command-web"
+
+[[processes]]
+name = "api"
+command = "echo api-in-$(basename \"$PWD\") port=$PORT"
+cwd = "backend"
+env = { PORT = "4000" }
+
+[[processes]]
+name = "web"
+command = "echo web-started"
+depends-on = ["api"]
+
+[[processes]]
+name = "lint"
+command = "exit 3"
+"#;
+
+fn project() -> tempfile::TempDir {
+  let dir = tempfile::tempdir().unwrap();
+  std::fs::create_dir(dir.path().join("backend")).unwrap();
+  std::fs::write(dir.path().join("mlti.toml"), CONFIG).unwrap();
+  dir
+}
+
+#[test]
+fn runs_every_configured_process_with_its_cwd_and_env() {
+  let dir = project();
+  let (code, output) = run_mlti_in(dir.path(), &[]);
+  assert!(output.contains("3 processes to run"), "{}", output);
+  assert!(output.contains("api-in-backend port=4000"), "{}", output);
+  assert!(output.contains("web-started"), "{}", output);
+  // `success = "command-web"` comes from the file, so lint failing is fine.
+  assert_eq!(code, 0, "Got: {}", output);
+}
+
+#[test]
+fn cli_flags_override_the_config_file() {
+  let dir = project();
+  let (code, _) = run_mlti_in(dir.path(), &["--success", "command-lint"]);
+  assert_eq!(code, 3);
+}
+
+#[test]
+fn positional_names_select_a_subset() {
+  let dir = project();
+  let (code, output) = run_mlti_in(dir.path(), &["web"]);
+  assert!(output.contains("1 processes to run"), "{}", output);
+  // `web` depends on `api`, but only on runs that include it.
+  assert!(output.contains("web-started"), "{}", output);
+  assert!(!output.contains("api-in"), "{}", output);
+  assert_eq!(code, 0, "Got: {}", output);
+
+  let (code, output) = run_mlti_in(dir.path(), &["nope"]);
+  assert_eq!(code, 1);
+  assert!(output.contains("Unknown process \"nope\""), "{}", output);
+}
+
+#[test]
+fn explicit_config_path_and_global_options_only() {
+  let dir = tempfile::tempdir().unwrap();
+  let path = dir.path().join("ci.toml");
+  std::fs::write(&path, "kill-others = true\n").unwrap();
+  // A config without processes only supplies defaults; positionals are
+  // still commands.
+  let (_, output) = run_mlti_in(
+    dir.path(),
+    &["--config", "ci.toml", "echo quick", "sleep 30"],
+  );
+  assert!(output.contains("Kill others flag present"), "{}", output);
+}
+
+#[test]
+fn invalid_config_is_reported() {
+  let dir = tempfile::tempdir().unwrap();
+  std::fs::write(dir.path().join("mlti.toml"), "kill-everything = 1\n").unwrap();
+  let (code, output) = run_mlti_in(dir.path(), &["echo hi"]);
+  assert_eq!(code, 1);
+  assert!(output.contains("Invalid config file"), "{}", output);
+}
+
+#[test]
+fn depending_on_a_wildcard_entry_waits_for_every_script() {
+  if Command::new("npm").arg("--version").output().is_err() {
+    return;
+  }
+  let dir = tempfile::tempdir().unwrap();
+  std::fs::write(
+    dir.path().join("package.json"),
+    r#"{"scripts": {"build:a": "echo built-a", "build:b": "sleep 1; echo built-b"}}"#,
+  )
+  .unwrap();
+  std::fs::write(
+    dir.path().join("mlti.toml"),
+    r#"
+[[processes]]
+name = "build"
+command = "npm:build:*"
+
+[[processes]]
+name = "deploy"
+command = "echo deploying"
+depends-on = ["build"]
+"#,
+  )
+  .unwrap();
+  let (code, output) = run_mlti_in(dir.path(), &[]);
+  assert_eq!(code, 0, "{}", output);
+  assert!(output.contains("[build:b]: built-b"), "{}", output);
+  let deployed = output.find("[deploy]: deploying").unwrap();
+  assert!(output.find("built-a").unwrap() < deployed, "{}", output);
+  assert!(output.find("built-b").unwrap() < deployed, "{}", output);
+}
+
+#[test]
+fn names_from_the_environment_do_not_conflict_with_config_processes() {
+  let dir = project();
+  let (code, output) = run(
+    mlti()
+      .current_dir(dir.path())
+      .env("MLTI_NAMES", "one,two")
+      .args(["--no-color", "web"]),
+  );
+  assert_eq!(code, 0, "{}", output);
+  assert!(output.contains("[web]: web-started"), "{}", output);
+
+  let (code, output) = run_mlti_in(dir.path(), &["--names", "one", "web"]);
+  assert_eq!(code, 1);
+  assert!(output.contains("--names can't be used"), "{}", output);
+}
@@ -0,0 +1,167 @@
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+/// Start an mlti session with colors disabled and a control socket at
+/// `socket`, and wait for it to listen.
+fn start_session(socket: &Path, args: &[&str]) -> Child {
+  let child = Command::new(env!("CARGO_BIN_EXE_mlti"))
+    .arg("--no-color")
+    .arg("--control-socket")
+    .arg(socket)
+    .args(args)
+    .stdout(Stdio::piped())
+    .spawn()
+    .expect("failed to run mlti");
+  wait_for(|| socket.exists());
+  child
+}
+
+/// Run `mlti ctl` against `socket` and return (exit code, stdout+stderr).
+fn ctl(socket: &Path, args: &[&str]) -> (i32, String) {
+  let output = Command::new(env!("CARGO_BIN_EXE_mlti"))
+    .arg("ctl")
+    .arg("--socket")
+    .arg(socket)
+    .args(args)
+    .output()
+    .expect("failed to run mlti ctl");
+  let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+  text.push_str(&String::from_utf8_lossy(&output.stderr));
+  (output.status.code().unwrap_or(-1), text)
+}
+
+fn wait_for(mut condition: impl FnMut() -> bool) {
+  let start = Instant::now();
+  while !condition() {
+    assert!(start.elapsed() < Duration::from_secs(10), "timed out");
+    sleep(Duration::from_millis(50));
+  }
+}
+
+/// The state `mlti ctl list` reports for process `name`.
+fn state(socket: &Path, name: &str) -> String {
+  let (code, output) = ctl(socket, &["--json", "list"]);
+  assert_eq!(code, 0, "{}", output);
+  let response: serde_json::Value = serde_json::from_str(&output).unwrap();
+  let processes = response["processes"].as_array().unwrap();
+  let process = processes.iter().find(|p| p["name"] == name).unwrap();
+  process["state"].as_str().unwrap().to_string()
+}
+
+#[test]
+fn restart_and_stop_processes_by_name() {
+  let dir = tempfile::tempdir().unwrap();
+  let socket = dir.path().join("mlti.sock");
+  let mut session = start_session(
+    &socket,
+    &["-n", "api,worker", "echo api-up; sleep 30", "sleep 30"],
+  );
+
+  wait_for(|| state(&socket, "api") == "running");
+  wait_for(|| state(&socket, "worker") == "running");
+  let (code, output) = ctl(&socket, &["list"]);
+  assert_eq!(code, 0, "{}", output);
+  assert!(output.contains("api     running (pid "), "{}", output);
+  assert!(output.contains("echo api-up; sleep 30"), "{}", output);
+
+  let (code, output) = ctl(&socket, &["restart", "nope"]);
+  assert_eq!(code, 1, "{}", output);
+  assert!(output.contains("Unknown process \"nope\""), "{}", output);
+
+  // Follow the session while restarting `api`.
+  let mut subscriber = Command::new(env!("CARGO_BIN_EXE_mlti"))
+    .args(["ctl", "--socket"])
+    .arg(&socket)
+    .arg("subscribe")
+    .stdout(Stdio::piped())
+    .spawn()
+    .unwrap();
+  let mut events = BufReader::new(subscriber.stdout.take().unwrap()).lines();
+  sleep(Duration::from_millis(300));
+
+  let (code, output) = ctl(&socket, &["restart", "api"]);
+  assert_eq!(code, 0, "{}", output);
+  let restarted = events
+    .by_ref()
+    .map(|line| line.unwrap())
+    .find(|line| line.contains("\"type\":\"restarted\""))
+    .expect("no restarted event");
+  assert!(restarted.contains("\"name\":\"api\""), "{}", restarted);
+  wait_for(|| state(&socket, "api") == "running");
+
+  // Stopping a process doesn't restart it or end the session.
+  let (code, output) = ctl(&socket, &["stop", "1"]);
+  assert_eq!(code, 0, "{}", output);
+  wait_for(|| state(&socket, "worker") == "killed");
+  assert_eq!(state(&socket, "api"), "running");
+
+  // Once the last one is stopped the session ends, taking the
+  // subscription with it.
+  ctl(&socket, &["stop", "api"]);
+  let status = session.wait().unwrap();
+  assert!(!socket.exists());
+  assert!(subscriber.wait().unwrap().success());
+
+  let mut output = String::new();
+  std::io::Read::read_to_string(&mut session.stdout.take().unwrap(), &mut output)
+    .unwrap();
+  assert!(
+    output.contains("sleep 30 restarting (requested)"),
+    "{}",
+    output
+  );
+  assert_eq!(output.matches("]: api-up").count(), 2, "{}", output);
+  // Stopped processes count as killed, not as failures or successes.
+  assert_ne!(status.code(), Some(0), "{}", output);
+}
+
+#[test]
+fn start_runs_a_finished_process_again() {
+  let dir = tempfile::tempdir().unwrap();
+  let socket = dir.path().join("mlti.sock");
+  let mut session =
+    start_session(&socket, &["-n", "once,server", "echo once-ran", "sleep 30"]);
+
+  wait_for(|| state(&socket, "once") == "exited");
+  let (code, output) = ctl(&socket, &["--json", "start", "once"]);
+  assert_eq!(code, 0, "{}", output);
+  assert_eq!(output.trim(), r#"{"ok":true}"#);
+  // Starting a process that is already running does nothing.
+  ctl(&socket, &["start", "server"]);
+
+  ctl(&socket, &["stop", "server"]);
+  session.wait().unwrap();
+  let mut output = String::new();
+  std::io::Read::read_to_string(&mut session.stdout.take().unwrap(), &mut output)
+    .unwrap();
+  assert_eq!(output.matches("]: once-ran").count(), 2, "{}", output);
+  assert_eq!(output.matches("sleep 30 exited").count(), 1, "{}", output);
+}
+
+#[test]
+fn refuses_a_socket_another_session_is_using() {
+  let dir = tempfile::tempdir().unwrap();
+  let socket = dir.path().join("mlti.sock");
+  let mut session = start_session(&socket, &["sleep 30"]);
+
+  let output = Command::new(env!("CARGO_BIN_EXE_mlti"))
+    .arg("--control-socket")
+    .arg(&socket)
+    .arg("echo should-not-run")
+    .output()
+    .unwrap();
+  assert_eq!(output.status.code(), Some(1));
+  let stderr = String::from_utf8_lossy(&output.stderr);
+  assert!(
+    stderr.contains("Could not listen on control socket"),
+    "{}",
+    stderr
+  );
+  assert!(!String::from_utf8_lossy(&output.stdout).contains("should-not-run"));
+
+  ctl(&socket, &["stop", "0"]);
+  session.wait().unwrap();
+}
@@ -0,0 +1,100 @@
+mod common;
+use common::run_mlti;
+
+#[test]
+fn cwd_and_env_apply_per_process() {
+  let dir = tempfile::tempdir().unwrap();
+  let service = dir.path().join("service");
+  std::fs::create_dir(&service).unwrap();
+  let cwd = format!("api={}", service.display());
+
+  let (code, output) = run_mlti(&[
+    "-n",
+    "api,web",
+    "--cwd",
+    &cwd,
+    "--env",
+    "api:PORT=3001",
+    "--env",
+    "api:GREETING=hello  world",
+    "--env",
+    "web:PORT=3002",
+    "echo \"api in $(basename \"$PWD\") on $PORT says $GREETING\"",
+    "echo \"web on $PORT says ${GREETING:-nothing}\"",
+  ]);
+  assert_eq!(code, 0, "Got: {}", output);
+  assert!(
+    output.contains("api in service on 3001 says hello  world"),
+    "{}",
+    output
+  );
+  assert!(output.contains("web on 3002 says nothing"), "{}", output);
+}
+
+#[test]
+fn every_child_gets_its_index_and_name() {
+  let (code, output) = run_mlti(&[
+    "-n",
+    "first",
+    "echo \"id=$MLTI_INDEX:$MLTI_NAME\"",
+    "echo \"id=$MLTI_INDEX:$MLTI_NAME\"",
+  ]);
+  assert_eq!(code, 0, "Got: {}", output);
+  assert!(output.contains("id=0:first"), "{}", output);
+  // Unnamed processes are known by their index, as in the prefix.
+  assert!(output.contains("id=1:1"), "{}", output);
+}
+
+#[test]
+fn missing_working_directory_is_reported_up_front() {
+  let (code, output) = run_mlti(&[
+    "-n",
+    "api",
+    "--cwd",
+    "api=/nonexistent/dir",
+    "echo should-not-run",
+  ]);
+  assert_eq!(code, 1);
+  assert!(
+    output.contains("Working directory for \"api\" does not exist"),
+    "{}",
+    output
+  );
+  assert!(!output.contains("should-not-run\n"), "{}", output);
+}
+
+#[test]
+fn env_files_load_under_explicit_env() {
+  let dir = tempfile::tempdir().unwrap();
+  let base = dir.path().join(".env");
+  let local = dir.path().join(".env.local");
+  std::fs::write(&base, "# shared\nexport HOST=localhost\nPORT=3000\n").unwrap();
+  std::fs::write(&local, "PORT=3001\nURL=\"http://${HOST}:${PORT}\"\n").unwrap();
+  let base = base.display().to_string();
+  let local = local.display().to_string();
+
+  let (code, output) = run_mlti(&[
+    "-n",
+    "api,web",
+    "--env-file",
+    &base,
+    "--env-file",
+    &local,
+    "--env",
+    "web:PORT=4000",
+    "echo \"api $URL on $PORT\"",
+    "echo \"web on $PORT\"",
+  ]);
+  assert_eq!(code, 0, "Got: {}", output);
+  assert!(
+    output.contains("api http://localhost:3001 on 3001"),
+    "{}",
+    output
+  );
+  assert!(output.contains("web on 4000"), "{}", output);
+
+  let (code, output) =
+    run_mlti(&["--env-file", "/nonexistent/.env", "echo should-not-run"]);
+  assert_eq!(code, 1);
+  assert!(output.contains("Could not read env file"), "{}", output);
+}
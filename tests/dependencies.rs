@@ -0,0 +1,85 @@
+mod common;
+use common::{line_of, run_mlti};
+
+#[test]
+fn dependents_wait_for_successful_completion() {
+  let (code, output) = run_mlti(&[
+    "-n",
+    "build,codegen",
+    "--depends-on",
+    "build=codegen",
+    "echo building",
+    "sleep 0.3; echo generated",
+  ]);
+  // `build` is listed first but may only start once `codegen` succeeded.
+  assert_eq!(code, 0, "Got: {}", output);
+  assert!(line_of(&output, "generated") < line_of(&output, "building"));
+}
+
+#[test]
+fn dependents_of_failed_tasks_are_skipped() {
+  let (code, output) = run_mlti(&[
+    "-n",
+    "build,server,tests",
+    "--depends-on",
+    "server=build",
+    "--depends-on",
+    "tests=server@ready",
+    "exit 3",
+    "echo serving",
+    "echo testing",
+  ]);
+  assert_eq!(code, 3, "Got: {}", output);
+  assert!(
+    output.contains("skipped: build did not succeed"),
+    "{}",
+    output
+  );
+  assert!(
+    output.contains("skipped: server never became ready"),
+    "{}",
+    output
+  );
+  assert!(!output.contains("serving\n"), "{}", output);
+}
+
+#[test]
+fn skipped_tasks_fail_the_run_even_if_the_failure_is_excluded() {
+  let (code, output) = run_mlti(&[
+    "-n",
+    "build,deploy",
+    "--success",
+    "!command-build",
+    "--depends-on",
+    "deploy=build",
+    "exit 1",
+    "echo deploying",
+  ]);
+  assert!(
+    output.contains("skipped: build did not succeed"),
+    "{}",
+    output
+  );
+  assert_ne!(code, 0, "Got: {}", output);
+}
+
+#[test]
+fn cycles_are_rejected_before_anything_runs() {
+  let (code, output) = run_mlti(&[
+    "-n",
+    "a,b",
+    "--depends-on",
+    "a=b",
+    "--depends-on",
+    "b=a",
+    "echo should-not-run",
+    "echo should-not-run",
+  ]);
+  assert_eq!(code, 1);
+  assert!(
+    output.contains("Dependency cycle detected: a -> b -> a"),
+    "{}",
+    output
+  );
+  assert!(!output.contains("should-not-run\n"), "{}", output);
+}
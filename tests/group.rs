@@ -0,0 +1,72 @@
+use std::io::{BufRead, BufReader};
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+
+mod common;
+use common::mlti;
+
+/// Run mlti to completion with colors disabled and return
+/// (exit code, stdout), along with how long after the start each line of
+/// stdout arrived.
+fn run_mlti_timed(args: &[&str]) -> (i32, Vec<(Duration, String)>) {
+  let start = Instant::now();
+  let mut child = mlti()
+    .arg("--no-color")
+    .args(args)
+    .stdout(Stdio::piped())
+    .spawn()
+    .expect("failed to run mlti");
+  let lines = BufReader::new(child.stdout.take().unwrap())
+    .lines()
+    .map(|line| (start.elapsed(), line.unwrap()))
+    .collect();
+  let status = child.wait().unwrap();
+  (status.code().unwrap_or(-1), lines)
+}
+
+fn position(lines: &[(Duration, String)], text: &str) -> usize {
+  lines
+    .iter()
+    .position(|(_, line)| line.contains(text))
+    .unwrap_or_else(|| panic!("no line with {:?} in {:?}", text, lines))
+}
+
+#[test]
+fn first_process_streams_while_others_run() {
+  let (code, lines) = run_mlti_timed(&[
+    "--group",
+    "-n",
+    "fast,slow",
+    "echo fast-out",
+    "echo slow-start; sleep 1.5; echo slow-end",
+  ]);
+  assert_eq!(code, 0, "{:?}", lines);
+  // `fast` is printed as soon as it's done, not after `slow` is.
+  let (fast_at, _) = &lines[position(&lines, "[fast]: fast-out")];
+  assert!(*fast_at < Duration::from_millis(1000), "{:?}", lines);
+  // Once `fast` has finished, `slow` streams live too.
+  let (start_at, _) = &lines[position(&lines, "[slow]: slow-start")];
+  assert!(*start_at < Duration::from_millis(1000), "{:?}", lines);
+}
+
+#[test]
+fn later_processes_wait_for_earlier_ones() {
+  let (code, lines) = run_mlti_timed(&[
+    "-g",
+    "-n",
+    "a,b,c",
+    "echo a-1; sleep 0.5; echo a-2",
+    "echo b-1",
+    "sleep 0.2; echo c-1",
+  ]);
+  assert_eq!(code, 0, "{:?}", lines);
+  let a_done = position(&lines, "a-2 exited with code 0");
+  let b_out = position(&lines, "[b]: b-1");
+  let c_out = position(&lines, "[c]: c-1");
+  assert!(position(&lines, "[a]: a-2") < a_done);
+  assert!(a_done < b_out, "{:?}", lines);
+  assert!(b_out < c_out, "{:?}", lines);
+  // `b` and `c` finished long before `a`, so they come out all at once.
+  let (b_at, _) = &lines[b_out];
+  assert!(*b_at >= Duration::from_millis(500), "{:?}", lines);
+}
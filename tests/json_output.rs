@@ -0,0 +1,91 @@
+use serde_json::Value;
+
+mod common;
+use common::mlti;
+
+/// Run mlti with `--output json` and return (exit code, one JSON value per
+/// stdout line). Fails the test if any line isn't JSON.
+fn run_mlti_json(args: &[&str]) -> (i32, Vec<Value>) {
+  let output = mlti()
+    .args(["--output", "json"])
+    .args(args)
+    .output()
+    .expect("failed to run mlti");
+  let stdout = String::from_utf8_lossy(&output.stdout);
+  let events = stdout
+    .lines()
+    .map(|line| {
+      serde_json::from_str(line)
+        .unwrap_or_else(|e| panic!("not JSON ({}): {}", e, line))
+    })
+    .collect();
+  (output.status.code().unwrap_or(-1), events)
+}
+
+fn of_type<'a>(events: &'a [Value], type_: &str) -> Vec<&'a Value> {
+  events.iter().filter(|e| e["type"] == type_).collect()
+}
+
+#[test]
+fn lines_and_lifecycle_are_json() {
+  let (code, events) = run_mlti_json(&[
+    "-n",
+    "api",
+    "--restart-tries",
+    "1",
+    "echo out; echo err >&2; exit 2",
+  ]);
+  assert_eq!(code, 2);
+
+  let lines = of_type(&events, "line");
+  assert_eq!(lines.len(), 4, "{:?}", events);
+  assert_eq!(lines[0]["name"], "api");
+  assert_eq!(lines[0]["index"], 0);
+  assert!(lines[0]["timestamp"].is_u64());
+  assert!(lines
+    .iter()
+    .any(|l| l["stream"] == "stderr" && l["text"] == "err"));
+
+  let spawned = of_type(&events, "spawned");
+  assert_eq!(spawned.len(), 2);
+  assert!(spawned.iter().all(|e| e["pid"].is_u64()));
+  let exited = of_type(&events, "exited");
+  assert_eq!(exited.len(), 2);
+  assert_eq!(exited[0]["code"], 2);
+  assert_eq!(of_type(&events, "restarted")[0]["attempt"], 1);
+
+  let summary = events.last().unwrap();
+  assert_eq!(summary["type"], "summary");
+  assert_eq!(summary["condition"], "all");
+  assert_eq!(summary["success"], false);
+  assert_eq!(summary["exit_code"], 2);
+  assert_eq!(summary["processes"][0]["restarts"], 1);
+  assert_eq!(summary["processes"][0]["status"], "exited");
+}
+
+#[test]
+fn killed_processes_report_the_signal() {
+  let (code, events) = run_mlti_json(&[
+    "-k",
+    "-n",
+    "quick,slow",
+    "--success",
+    "first",
+    "true",
+    "sleep 10",
+  ]);
+  assert_eq!(code, 0, "{:?}", events);
+  let killed = of_type(&events, "killed");
+  assert_eq!(killed.len(), 1, "{:?}", events);
+  assert_eq!(killed[0]["name"], "slow");
+  assert_eq!(killed[0]["signal"], "SIGTERM");
+  let exited = of_type(&events, "exited");
+  assert!(exited
+    .iter()
+    .any(|e| e["name"] == "slow" && e["signal"] == "SIGTERM"));
+
+  let summary = events.last().unwrap();
+  assert_eq!(summary["condition"], "first");
+  assert_eq!(summary["success"], true);
+  assert_eq!(summary["processes"][1]["status"], "killed");
+}
@@ -0,0 +1,49 @@
+use std::fs;
+
+mod common;
+use common::run_mlti;
+
+#[test]
+fn report_has_a_testcase_per_process() {
+  let dir = tempfile::tempdir().unwrap();
+  let report = dir.path().join("report.xml");
+  let (code, output) = run_mlti(&[
+    "--junit-report",
+    report.to_str().unwrap(),
+    "-n",
+    "lint,test",
+    "echo lint ok",
+    "for i in $(seq 1 60); do echo line $i; done; echo '<boom>'; exit 3",
+  ]);
+  assert_eq!(code, 3, "{}", output);
+
+  let xml = fs::read_to_string(&report).unwrap();
+  assert!(
+    xml.contains("tests=\"2\" failures=\"1\" errors=\"0\" skipped=\"0\""),
+    "{}",
+    xml
+  );
+  assert!(
+    xml.contains("<testcase name=\"lint\" classname=\"mlti\" time=\""),
+    "{}",
+    xml
+  );
+  assert!(xml.contains("exited with code 3\""), "{}", xml);
+  // Only the last 50 lines of output are kept.
+  assert!(xml.contains("\">line 12\n"), "{}", xml);
+  assert!(xml.contains("line 60\n&lt;boom&gt;</failure>"), "{}", xml);
+}
+
+#[test]
+fn unwritable_report_fails_the_run() {
+  let dir = tempfile::tempdir().unwrap();
+  let report = dir.path().join("missing/report.xml");
+  let (code, output) =
+    run_mlti(&["--junit-report", report.to_str().unwrap(), "true"]);
+  assert_eq!(code, 1, "{}", output);
+  assert!(
+    output.contains("Could not write JUnit report"),
+    "{}",
+    output
+  );
+}
@@ -0,0 +1,75 @@
+use std::time::{Duration, Instant};
+
+mod common;
+use common::run_mlti;
+
+#[test]
+fn kill_others_sends_configured_signal() {
+  let (_, output) = run_mlti(&[
+    "-k",
+    "--kill-signal",
+    "SIGINT",
+    "sleep 0.2",
+    "exec sleep 30",
+  ]);
+  assert!(
+    output.contains("exec sleep 30 exited with signal SIGINT"),
+    "Expected the long-running process to be interrupted. Got: {}",
+    output
+  );
+}
+
+#[test]
+fn kill_timeout_escalates_to_sigkill() {
+  let start = Instant::now();
+  let (_, output) = run_mlti(&[
+    "-k",
+    "--kill-timeout",
+    "300",
+    "sleep 0.2",
+    "trap '' TERM; while true; do sleep 0.05; done",
+  ]);
+  assert!(
+    output.contains("exited with signal SIGKILL"),
+    "Expected SIGTERM to be ignored and escalated. Got: {}",
+    output
+  );
+  assert!(
+    start.elapsed() < Duration::from_secs(10),
+    "mlti should not wait for the ignored signal. Got: {}",
+    output
+  );
+}
+
+/// Whether `pid` is a live process. Zombies count as dead: the process has
+/// exited, it just hasn't been reaped by whoever inherited it.
+#[cfg(target_os = "linux")]
+fn is_running(pid: &str) -> bool {
+  match std::fs::read_to_string(format!("/proc/{}/stat", pid)) {
+    // The state follows the parenthesised command name.
+    Ok(stat) => stat
+      .rsplit_once(") ")
+      .is_some_and(|(_, rest)| !rest.starts_with('Z')),
+    Err(_) => false,
+  }
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn kill_reaches_grandchildren() {
+  // The shell backgrounds a sleep and records its pid; killing the shell
+  // alone would orphan it.
+  let dir = tempfile::tempdir().unwrap();
+  let pid_file = dir.path().join("grandchild.pid");
+  let cmd = format!("sleep 30 & echo $! > '{}'; wait", pid_file.display());
+  let (_, output) = run_mlti(&["-k", "sleep 0.3", &cmd]);
+
+  let pid = std::fs::read_to_string(&pid_file)
+    .unwrap_or_else(|_| panic!("grandchild never started. Got: {}", output));
+  assert!(
+    !is_running(pid.trim()),
+    "grandchild {} survived mlti. Got: {}",
+    pid.trim(),
+    output
+  );
+}
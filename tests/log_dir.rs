@@ -0,0 +1,146 @@
+use std::fs;
+
+mod common;
+use common::run_mlti;
+
+#[test]
+fn each_process_logs_to_its_own_file() {
+  let dir = tempfile::tempdir().unwrap();
+  let logs = dir.path().join("logs");
+  let (code, output) = run_mlti(&[
+    "--log-dir",
+    logs.to_str().unwrap(),
+    "--group",
+    "--hide",
+    "api",
+    "-t",
+    "%Y",
+    "-n",
+    "api,web:dev",
+    "echo api-out; echo api-err >&2",
+    "echo web-out",
+  ]);
+  assert_eq!(code, 0, "{}", output);
+  // Hiding a process on the terminal doesn't keep it out of its log.
+  assert!(!output.contains("api-out"), "{}", output);
+
+  let api = fs::read_to_string(logs.join("api.log")).unwrap();
+  let lines: Vec<&str> = api.lines().collect();
+  assert!(lines
+    .iter()
+    .all(|l| l.len() > 5 && l[..4].parse::<u32>().is_ok()));
+  assert!(lines.iter().any(|l| l.ends_with(" api-out")), "{}", api);
+  assert!(
+    lines.iter().any(|l| l.ends_with(" [stderr] api-err")),
+    "{}",
+    api
+  );
+  assert!(
+    lines
+      .iter()
+      .any(|l| l.contains("[mlti] ") && l.contains("exited")),
+    "{}",
+    api
+  );
+  assert!(!api.contains("web-out"), "{}", api);
+
+  let web = fs::read_to_string(logs.join("web_dev.log")).unwrap();
+  assert!(web.contains(" web-out\n"), "{}", web);
+}
+
+#[test]
+fn config_log_file_overrides_log_dir() {
+  let dir = tempfile::tempdir().unwrap();
+  let config = dir.path().join("mlti.toml");
+  fs::write(
+    &config,
+    r#"
+log-dir = "logs"
+
+[[processes]]
+name = "api"
+command = "echo from-api"
+log-file = "api-server.log"
+
+[[processes]]
+name = "web"
+command = "echo from-web"
+"#,
+  )
+  .unwrap();
+
+  let (code, output) = run_mlti(&["--raw", "--config", config.to_str().unwrap()]);
+  assert_eq!(code, 0, "{}", output);
+  let api = fs::read_to_string(dir.path().join("api-server.log")).unwrap();
+  assert!(api.contains(" from-api\n"), "{}", api);
+  assert!(!dir.path().join("logs/api.log").exists());
+  let web = fs::read_to_string(dir.path().join("logs/web.log")).unwrap();
+  assert!(web.contains(" from-web\n"), "{}", web);
+}
+
+#[test]
+fn unwritable_log_dir_fails_before_running() {
+  let dir = tempfile::tempdir().unwrap();
+  let file = dir.path().join("not-a-dir");
+  fs::write(&file, "").unwrap();
+  let marker = dir.path().join("ran");
+  let cmd = format!("touch {}", marker.display());
+
+  let (code, output) = run_mlti(&["--log-dir", file.to_str().unwrap(), &cmd]);
+  assert_eq!(code, 1, "{}", output);
+  assert!(output.contains("Could not open log file"), "{}", output);
+  assert!(!marker.exists());
+}
+
+#[test]
+fn names_that_map_to_the_same_file_get_one_each() {
+  let dir = tempfile::tempdir().unwrap();
+  let logs = dir.path().join("logs");
+  let (code, output) = run_mlti(&[
+    "--log-dir",
+    logs.to_str().unwrap(),
+    "-n",
+    "web:dev,web_dev",
+    "echo one",
+    "echo two",
+  ]);
+  assert_eq!(code, 0, "{}", output);
+  let first = fs::read_to_string(logs.join("web_dev.log")).unwrap();
+  assert!(
+    first.contains(" one\n") && !first.contains(" two\n"),
+    "{}",
+    first
+  );
+  let second = fs::read_to_string(logs.join("web_dev-1.log")).unwrap();
+  assert!(
+    second.contains(" two\n") && !second.contains(" one\n"),
+    "{}",
+    second
+  );
+}
+
+#[test]
+fn a_shared_log_file_is_rejected() {
+  let dir = tempfile::tempdir().unwrap();
+  let config = dir.path().join("mlti.toml");
+  fs::write(
+    &config,
+    r#"
+[[processes]]
+name = "api"
+command = "echo should-not-run"
+log-file = "shared.log"
+
+[[processes]]
+name = "web"
+command = "echo should-not-run"
+log-file = "shared.log"
+"#,
+  )
+  .unwrap();
+
+  let (code, output) = run_mlti(&["--config", config.to_str().unwrap()]);
+  assert_eq!(code, 1, "{}", output);
+  assert!(output.contains("Process \"web\" logs to"), "{}", output);
+  assert!(!output.contains("should-not-run"), "{}", output);
+}
@@ -0,0 +1,50 @@
+mod common;
+use common::run_mlti;
+
+const FAILING: &str = "for i in 1 2 3 4 5; do echo bad-$i; sleep 0.05; done; exit 2";
+const PASSING: &str = "for i in 1 2 3 4 5; do echo good-$i; sleep 0.05; done";
+
+#[test]
+fn only_failed_output_is_printed_in_one_block() {
+  let (code, output) =
+    run_mlti(&["--show-output-on-failure", "-n", "ok,bad", PASSING, FAILING]);
+  assert_eq!(code, 2, "{}", output);
+  assert!(!output.contains("]: good-"), "{}", output);
+  assert!(output.contains("[ok]: "), "{}", output);
+  assert!(output.contains("exited with code 0"), "{}", output);
+
+  let block: Vec<&str> = output
+    .lines()
+    .filter(|l| l.starts_with("[bad]: bad-"))
+    .collect();
+  assert_eq!(block.len(), 5, "{}", output);
+  // The lines are printed back to back, before the exit line.
+  let start = output.find("[bad]: bad-1").unwrap();
+  let end = output.find("[bad]: bad-5").unwrap();
+  assert!(!output[start..end].contains("[ok]"), "{}", output);
+  assert!(
+    end < output.find("exited with code 2").unwrap(),
+    "{}",
+    output
+  );
+}
+
+#[test]
+fn tail_lines_limits_the_block() {
+  let (code, output) = run_mlti(&[
+    "--show-output-on-failure",
+    "--tail-lines",
+    "2",
+    "-n",
+    "bad",
+    FAILING,
+  ]);
+  assert_eq!(code, 2, "{}", output);
+  assert!(output.contains("3 earlier lines not shown"), "{}", output);
+  assert!(!output.contains("]: bad-3"), "{}", output);
+  assert!(
+    output.contains("[bad]: bad-4\n[bad]: bad-5\n"),
+    "{}",
+    output
+  );
+}
@@ -0,0 +1,35 @@
+mod common;
+use common::run_mlti;
+
+#[test]
+fn arguments_after_the_separator_fill_the_placeholders() {
+  let (code, output) = run_mlti(&[
+    "-P",
+    "printf '<%s>' {@}; echo",
+    "printf '<%s>' {*}; echo",
+    "printf '<%s>' {2} {5}; echo",
+    "echo untouched",
+    "--",
+    "--port",
+    "4000",
+    "it's here",
+  ]);
+  assert_eq!(code, 0, "{}", output);
+  assert!(
+    output.contains("]: <--port><4000><it's here>"),
+    "{}",
+    output
+  );
+  assert!(output.contains("]: <--port 4000 it's here>"), "{}", output);
+  // A placeholder past the last argument is dropped.
+  assert!(output.contains("]: <4000>\n"), "{}", output);
+  assert!(output.contains("]: untouched"), "{}", output);
+}
+
+#[test]
+fn without_passthrough_arguments_are_commands() {
+  let (code, output) = run_mlti(&["echo {1}", "--", "echo second"]);
+  assert_eq!(code, 0, "{}", output);
+  assert!(output.contains("]: {1}"), "{}", output);
+  assert!(output.contains("]: second"), "{}", output);
+}
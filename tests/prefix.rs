@@ -0,0 +1,59 @@
+mod common;
+use common::run_mlti;
+
+#[test]
+fn pid_is_the_childs_and_changes_across_restarts() {
+  // `$$` in the shell mlti spawns is the child's own pid.
+  let (code, output) = run_mlti(&[
+    "-p",
+    "{name}:{pid}:{restarts}",
+    "-n",
+    "api",
+    "--restart-tries",
+    "1",
+    "echo \"pid=$$\"; exit 1",
+  ]);
+  assert_eq!(code, 1, "{}", output);
+  let runs: Vec<(&str, &str)> = output
+    .lines()
+    .filter_map(|line| line.split_once("]: pid="))
+    .map(|(prefix, pid)| (prefix.trim_start_matches('['), pid))
+    .collect();
+  assert_eq!(runs.len(), 2, "{}", output);
+  for (n, (prefix, pid)) in runs.iter().enumerate() {
+    assert_eq!(*prefix, format!("api:{}:{}", pid, n), "{}", output);
+  }
+  assert_ne!(runs[0].1, runs[1].1, "{}", output);
+}
+
+#[test]
+fn name_index_stream_and_command_tokens() {
+  let (code, output) = run_mlti(&[
+    "-p",
+    "{index}|{name}|{stream}|{command}",
+    "-l",
+    "4",
+    "-n",
+    "web",
+    "echo out; echo err >&2",
+  ]);
+  assert_eq!(code, 0, "{}", output);
+  assert!(output.contains("[0|web|stdout|echo]: out"), "{}", output);
+  assert!(output.contains("[0|web|stderr|echo]: err"), "{}", output);
+  // mlti's own lines about the process come from neither stream.
+  assert!(output.contains("[0|web||echo]: "), "{}", output);
+}
+
+#[test]
+fn pad_prefix_aligns_templates_without_a_name() {
+  let (code, output) = run_mlti(&[
+    "--pad-prefix",
+    "-p",
+    "{index}-{command}",
+    "echo a",
+    "echo bbbbbbbbb",
+  ]);
+  assert_eq!(code, 0, "{}", output);
+  assert!(output.contains("[0-echo a    ]: a\n"), "{}", output);
+  assert!(output.contains("[1-echo bbbbb]: bbbbbbbbb\n"), "{}", output);
+}
@@ -0,0 +1,70 @@
+mod common;
+use common::{mlti, run};
+
+/// Run mlti to completion with colors enabled and return
+/// (exit code, combined stdout+stderr).
+fn run_mlti(args: &[&str]) -> (i32, String) {
+  run(
+    mlti()
+      .args(args)
+      .env_remove("NO_COLOR")
+      .env_remove("MLTI_PREFIX_COLORS"),
+  )
+}
+
+#[test]
+fn prefix_colors_apply_in_order_and_repeat_the_last() {
+  let (code, output) = run_mlti(&[
+    "-c",
+    "red.bold,bgBlue.#00ff00",
+    "echo first",
+    "echo second",
+    "echo third",
+  ]);
+  assert_eq!(code, 0, "Got: {}", output);
+  assert!(
+    output.contains("[\x1b[38;2;205;49;49;1m0\x1b[0m]: first"),
+    "{:?}",
+    output
+  );
+  let bg_blue_green = "\x1b[38;2;0;255;0;48;2;36;114;200m";
+  assert!(
+    output.contains(&format!("[{}1\x1b[0m]: second", bg_blue_green)),
+    "{:?}",
+    output
+  );
+  assert!(
+    output.contains(&format!("[{}2\x1b[0m]: third", bg_blue_green)),
+    "{:?}",
+    output
+  );
+}
+
+#[test]
+fn auto_colors_are_the_same_every_run() {
+  let prefixes = |output: &str| {
+    let mut lines: Vec<String> = output
+      .lines()
+      .filter(|l| l.ends_with(": hello"))
+      .map(str::to_string)
+      .collect();
+    lines.sort();
+    lines
+  };
+  let args = ["echo hello", "echo hello", "echo hello"];
+  let (_, first) = run_mlti(&args);
+  let (_, second) = run_mlti(&args);
+  assert_eq!(prefixes(&first).len(), 3, "{:?}", first);
+  assert_eq!(prefixes(&first), prefixes(&second));
+  // Neighbours never share a color.
+  let first = prefixes(&first);
+  assert_ne!(first[0].split('m').next(), first[1].split('m').next());
+}
+
+#[test]
+fn invalid_prefix_color_is_rejected() {
+  let (code, output) = run_mlti(&["-c", "red,mauve", "echo a", "echo b"]);
+  assert_eq!(code, 1);
+  assert!(output.contains("--prefix-colors"), "{}", output);
+  assert!(!output.contains("]: a"), "{}", output);
+}
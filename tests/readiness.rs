@@ -0,0 +1,72 @@
+mod common;
+use common::{line_of, run_mlti};
+
+#[test]
+fn log_check_gates_ready_dependents() {
+  let (code, output) = run_mlti(&[
+    "-n",
+    "server,tests",
+    "--ready",
+    r"server=log:listening on \d+",
+    "--depends-on",
+    "tests=server@ready",
+    "echo booting; sleep 0.3; echo listening on 3000; sleep 0.5",
+    "echo running-tests",
+  ]);
+  assert_eq!(code, 0, "Got: {}", output);
+  let ready = line_of(&output, "is ready");
+  assert!(line_of(&output, "listening on 3000") < ready, "{}", output);
+  assert!(ready < line_of(&output, "running-tests"), "{}", output);
+}
+
+#[test]
+fn file_check_passes_once_the_file_exists() {
+  let dir = tempfile::tempdir().unwrap();
+  let marker = dir.path().join("ready");
+  let check = format!("server=file:{}", marker.display());
+  let cmd = format!("sleep 0.3; touch '{}'; sleep 0.5", marker.display());
+  let (code, output) = run_mlti(&[
+    "-n",
+    "server,tests",
+    "--ready",
+    &check,
+    "--depends-on",
+    "tests=server@ready",
+    &cmd,
+    "echo running-tests",
+  ]);
+  assert_eq!(code, 0, "Got: {}", output);
+  assert!(line_of(&output, "is ready") < line_of(&output, "running-tests"));
+}
+
+#[test]
+fn ready_timeout_skips_dependents() {
+  let (code, output) = run_mlti(&[
+    "-n",
+    "server,tests",
+    "--ready",
+    "server=log:never printed",
+    "--ready-timeout",
+    "200",
+    "--depends-on",
+    "tests=server@ready",
+    "sleep 1",
+    "echo running-tests",
+  ]);
+  assert!(output.contains("did not become ready within"), "{}", output);
+  assert!(
+    output.contains("skipped: server never became ready"),
+    "{}",
+    output
+  );
+  assert!(!output.contains("running-tests\n"), "{}", output);
+  assert_ne!(code, 0, "Got: {}", output);
+}
+
+#[test]
+fn invalid_checks_are_rejected_up_front() {
+  let (code, output) = run_mlti(&["--ready", "0=tcp:nope", "echo hi"]);
+  assert_eq!(code, 1);
+  assert!(output.contains("Invalid --ready address"), "{}", output);
+  assert!(!output.contains("hi\n"), "{}", output);
+}
@@ -0,0 +1,62 @@
+mod common;
+use common::run_mlti;
+
+#[test]
+fn restarts_on_non_zero_exit_until_success() {
+  // The child fails until a marker file exists, so it needs exactly one
+  // restart to succeed.
+  let dir = tempfile::tempdir().unwrap();
+  let marker = dir.path().join("ran-once");
+  let cmd = format!(
+    "if [ -f '{0}' ]; then echo recovered; else touch '{0}'; exit 3; fi",
+    marker.display()
+  );
+  let (code, output) = run_mlti(&["--restart-tries", "3", &cmd]);
+  assert_eq!(code, 0, "Expected recovery to succeed. Got: {}", output);
+  assert!(
+    output.contains("restarting (attempt 1/3)"),
+    "Expected a restart message. Got: {}",
+    output
+  );
+  assert!(output.contains("recovered"), "Got: {}", output);
+  assert!(
+    !output.contains("attempt 2/3"),
+    "Should stop restarting after a clean exit. Got: {}",
+    output
+  );
+}
+
+#[test]
+fn gives_up_after_restart_tries() {
+  let (code, output) = run_mlti(&["--restart-tries", "2", "exit 4"]);
+  assert_eq!(code, 4, "Got: {}", output);
+  assert!(output.contains("attempt 2/2"), "Got: {}", output);
+  assert_eq!(output.matches("exited with code 4").count(), 3);
+}
+
+#[test]
+fn restart_always_restarts_clean_exits() {
+  let (code, output) =
+    run_mlti(&["--restart-tries", "1", "--restart-always", "echo tick"]);
+  assert_eq!(code, 0, "Got: {}", output);
+  let ticks = output.lines().filter(|l| *l == "[0]: tick").count();
+  assert_eq!(ticks, 2, "Got: {}", output);
+}
+
+#[test]
+fn reports_each_failed_start() {
+  let (code, output) = run_mlti(&[
+    "--no-shell",
+    "--restart-tries",
+    "1",
+    "mlti-test-no-such-program",
+  ]);
+  assert_eq!(code, 1, "{}", output);
+  assert!(
+    output.contains("Process failed to start, retrying"),
+    "{}",
+    output
+  );
+  assert!(output.contains("(attempt 1/1)"), "{}", output);
+  assert!(output.contains("Could not start process."), "{}", output);
+}
@@ -0,0 +1,129 @@
+mod common;
+use common::{line_of, run_mlti_in};
+
+#[test]
+fn each_stage_waits_for_the_one_before() {
+  let dir = tempfile::tempdir().unwrap();
+  let (code, output) = run_mlti_in(
+    dir.path(),
+    &[
+      "--timings",
+      "sleep 0.3; echo linted",
+      "echo typechecked",
+      "--then",
+      "echo built",
+      "--then",
+      "echo served",
+      "echo worked",
+    ],
+  );
+  assert_eq!(code, 0, "{}", output);
+  assert!(line_of(&output, "]: linted") < line_of(&output, "]: built"));
+  assert!(line_of(&output, "]: built") < line_of(&output, "]: served"));
+  assert!(line_of(&output, "]: built") < line_of(&output, "]: worked"));
+
+  assert!(output.contains("  Stage 1: passed in 0.3"), "{}", output);
+  assert!(
+    output.contains("    [0] sleep 0.3; echo linted"),
+    "{}",
+    output
+  );
+  assert!(output.contains("  Stage 3: passed in "), "{}", output);
+  assert!(line_of(&output, "Stage 2:") < line_of(&output, "    [2] echo built"));
+}
+
+#[test]
+fn a_failed_stage_skips_the_rest() {
+  let dir = tempfile::tempdir().unwrap();
+  let (code, output) = run_mlti_in(
+    dir.path(),
+    &[
+      "--timings",
+      "exit 3",
+      "echo passed",
+      "--then",
+      "echo never-built",
+    ],
+  );
+  assert_eq!(code, 3, "{}", output);
+  assert!(output.contains("]: passed"), "{}", output);
+  assert!(!output.contains("]: never-built"), "{}", output);
+  assert!(output.contains("  Stage 1: 1 failed after"), "{}", output);
+  assert!(output.contains("  Stage 2: skipped"), "{}", output);
+
+  // The next stage still runs with --continue-on-error.
+  let (code, output) = run_mlti_in(
+    dir.path(),
+    &["--continue-on-error", "exit 3", "--then", "echo built"],
+  );
+  assert_eq!(code, 3, "{}", output);
+  assert!(output.contains("]: built"), "{}", output);
+}
+
+#[test]
+fn then_needs_commands_on_both_sides() {
+  let dir = tempfile::tempdir().unwrap();
+  let (code, output) = run_mlti_in(dir.path(), &["echo a", "--then"]);
+  assert_eq!(code, 1);
+  assert!(output.contains("--then needs commands before and after it"));
+  let (code, _) = run_mlti_in(dir.path(), &["--then", "echo a"]);
+  assert_eq!(code, 1);
+}
+
+#[test]
+fn sequential_runs_one_at_a_time_until_a_failure() {
+  let dir = tempfile::tempdir().unwrap();
+  let (code, output) = run_mlti_in(
+    dir.path(),
+    &[
+      "--sequential",
+      "sleep 0.2; echo first",
+      "echo second; exit 1",
+      "echo third",
+    ],
+  );
+  assert_eq!(code, 1, "{}", output);
+  assert!(line_of(&output, "]: first") < line_of(&output, "]: second"));
+  assert!(!output.contains("]: third"), "{}", output);
+  assert!(output.contains("echo third skipped"), "{}", output);
+
+  let (code, output) = run_mlti_in(
+    dir.path(),
+    &[
+      "--sequential",
+      "--continue-on-error",
+      "echo first; exit 1",
+      "sleep 0.2; echo second",
+      "echo third",
+    ],
+  );
+  assert_eq!(code, 1, "{}", output);
+  assert!(line_of(&output, "]: first") < line_of(&output, "]: second"));
+  assert!(line_of(&output, "]: second") < line_of(&output, "]: third"));
+}
+
+#[test]
+fn stages_pick_processes_from_the_config_file() {
+  let dir = tempfile::tempdir().unwrap();
+  std::fs::write(
+    dir.path().join("mlti.toml"),
+    r#"
+[[processes]]
+name = "server"
+command = "echo serving"
+
+[[processes]]
+name = "build"
+command = "sleep 0.3; echo building"
+
+[[processes]]
+name = "unused"
+command = "echo unused"
+"#,
+  )
+  .unwrap();
+  let (code, output) = run_mlti_in(dir.path(), &["build", "--then", "server"]);
+  assert_eq!(code, 0, "{}", output);
+  assert!(line_of(&output, "]: building") < line_of(&output, "]: serving"));
+  assert!(!output.contains("]: unused"), "{}", output);
+}
@@ -10,11 +10,9 @@ use std::time::{Duration, Instant};
 const READY_MARKER: &str = "MLTI_READY";
 
 /// Write a tiny shell script to $CARGO_TARGET_TMPDIR and return its
-/// absolute path. mlti's own command parser splits on whitespace
-/// without respecting quotes (see `src/command.rs::parse`), so we
-/// can't pass `sh -c 'echo MLTI_READY; exec cat'` as a single
-/// positional — the shell would see mangled argv. Using a concrete
-/// file path sidesteps the parser entirely.
+/// absolute path. Commands run through `sh -c` by default, but a
+/// concrete file path keeps these tests independent of shell mode
+/// and of how the command string gets tokenized.
 fn write_helper_script(name: &str, body: &str) -> String {
   // $CARGO_TARGET_TMPDIR is guaranteed writable for integration
   // tests; fall back to `std::env::temp_dir()` for older toolchains.
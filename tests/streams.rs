@@ -0,0 +1,52 @@
+mod common;
+use common::mlti;
+
+/// Run mlti to completion with colors disabled and return
+/// (exit code, stdout, stderr).
+fn run_mlti(args: &[&str]) -> (i32, String, String) {
+  let output = mlti()
+    .arg("--no-color")
+    .args(args)
+    .output()
+    .expect("failed to run mlti");
+  (
+    output.status.code().unwrap_or(-1),
+    String::from_utf8_lossy(&output.stdout).into_owned(),
+    String::from_utf8_lossy(&output.stderr).into_owned(),
+  )
+}
+
+const API: &str = "echo api-out; echo api-err >&2";
+const WEB: &str = "echo web-out; echo web-err >&2";
+
+#[test]
+fn stderr_lines_get_the_marker() {
+  let (code, stdout, _) =
+    run_mlti(&["-n", "api,web", "--stderr-marker", "E>", API, WEB]);
+  assert_eq!(code, 0, "{}", stdout);
+  assert!(stdout.contains("[api]: api-out\n"), "{}", stdout);
+  assert!(stdout.contains("[api]: E> api-err\n"), "{}", stdout);
+  assert!(stdout.contains("[web]: E> web-err\n"), "{}", stdout);
+}
+
+#[test]
+fn hide_can_target_one_stream() {
+  let (code, stdout, _) =
+    run_mlti(&["-n", "api,web", "--hide", "api:stdout,1:stderr", API, WEB]);
+  assert_eq!(code, 0, "{}", stdout);
+  assert!(!stdout.contains("]: api-out"), "{}", stdout);
+  assert!(stdout.contains("[api]: api-err"), "{}", stdout);
+  assert!(stdout.contains("[web]: web-out"), "{}", stdout);
+  assert!(!stdout.contains("]: web-err"), "{}", stdout);
+  // Status lines aren't output of either stream and stay visible.
+  assert!(stdout.contains("exited with code 0"), "{}", stdout);
+}
+
+#[test]
+fn raw_mode_keeps_stderr_on_stderr() {
+  let (code, stdout, stderr) = run_mlti(&["--raw", API]);
+  assert_eq!(code, 0, "{}", stdout);
+  assert!(stdout.contains("api-out"), "{}", stdout);
+  assert!(!stdout.contains("api-err"), "{}", stdout);
+  assert!(stderr.contains("api-err"), "{}", stderr);
+}
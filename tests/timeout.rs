@@ -0,0 +1,63 @@
+use std::time::{Duration, Instant};
+
+mod common;
+use common::{mlti, run};
+
+/// Run mlti with colors disabled and return (exit code, combined
+/// stdout+stderr, how long it took).
+fn run_mlti(args: &[&str]) -> (i32, String, Duration) {
+  let start = Instant::now();
+  let (code, text) = run(
+    mlti()
+      .env_remove("MLTI_TIMEOUT")
+      .arg("--no-color")
+      .args(args),
+  );
+  (code, text, start.elapsed())
+}
+
+#[test]
+fn a_process_that_runs_too_long_is_stopped_and_fails() {
+  let (code, output, elapsed) =
+    run_mlti(&["--timings", "--timeout", "1s", "sleep 30", "echo fast"]);
+  assert_eq!(code, 124, "{}", output);
+  assert!(elapsed < Duration::from_secs(10), "took {:?}", elapsed);
+  assert!(output.contains("sleep 30 timed out after 1s"), "{}", output);
+  assert!(
+    output.contains("echo fast exited with code 0"),
+    "{}",
+    output
+  );
+  assert!(output.contains("[0] sleep 30 \u{2014} 1."), "{}", output);
+  assert!(output.contains("(timed out)"), "{}", output);
+}
+
+#[test]
+fn per_process_timeouts_override_the_global_one() {
+  // The slow process handles the signal and exits 0, which still counts
+  // as a failure.
+  let (code, output, _) = run_mlti(&[
+    "-n",
+    "slow,server",
+    "--timeout",
+    "10m",
+    "--timeout",
+    "slow=500ms",
+    "--timeout",
+    "server=0",
+    "trap 'exit 0' TERM; sleep 30 & wait",
+    "sleep 1; echo served",
+  ]);
+  assert_eq!(code, 124, "{}", output);
+  assert!(output.contains("timed out after 500ms"), "{}", output);
+  assert!(output.contains("]: served"), "{}", output);
+  assert_eq!(output.matches("timed out after").count(), 1, "{}", output);
+}
+
+#[test]
+fn rejects_an_invalid_timeout() {
+  let (code, output, _) = run_mlti(&["--timeout", "soon", "echo should-not-run"]);
+  assert_eq!(code, 1);
+  assert!(output.contains("Invalid duration 'soon'"), "{}", output);
+  assert!(!output.contains("should-not-run"), "{}", output);
+}
@@ -0,0 +1,39 @@
+mod common;
+use common::run_mlti;
+
+/// The epoch seconds at the start of the line that ends with `suffix`.
+fn line_time(output: &str, suffix: &str) -> u64 {
+  let line = output
+    .lines()
+    .find(|l| l.ends_with(suffix))
+    .unwrap_or_else(|| panic!("no line ending in {:?} in {}", suffix, output));
+  line
+    .trim_start_matches('[')
+    .split([' ', ']'])
+    .next()
+    .and_then(|t| t.parse().ok())
+    .unwrap_or_else(|| panic!("no time at the start of {:?}", line))
+}
+
+#[test]
+fn time_in_prefix_is_taken_per_line() {
+  let (code, output) = run_mlti(&[
+    "-p",
+    "{time}",
+    "-t",
+    "%s",
+    "echo first; sleep 1.2; echo second",
+  ]);
+  assert_eq!(code, 0, "{}", output);
+  assert!(line_time(&output, "]: second") > line_time(&output, "]: first"));
+}
+
+#[test]
+fn timestamps_prepend_the_time_to_every_prefix() {
+  let (code, output) =
+    run_mlti(&["--timestamps", "-t", "%s", "-n", "api", "echo hello"]);
+  assert_eq!(code, 0, "{}", output);
+  let line = output.lines().find(|l| l.ends_with("]: hello")).unwrap();
+  assert!(line.ends_with(" api]: hello"), "{}", line);
+  assert!(line_time(&output, "]: hello") > 1_600_000_000, "{}", line);
+}
@@ -0,0 +1,155 @@
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread::sleep;
+use std::time::Duration;
+
+/// Start mlti with colors disabled in `dir`, sending each line it prints
+/// down the returned channel.
+fn start_mlti(dir: &std::path::Path, args: &[&str]) -> (Child, Receiver<String>) {
+  let mut child = Command::new(env!("CARGO_BIN_EXE_mlti"))
+    .arg("--no-color")
+    .args(args)
+    .current_dir(dir)
+    .stdout(Stdio::piped())
+    .spawn()
+    .expect("failed to run mlti");
+  let stdout = child.stdout.take().unwrap();
+  let (tx, rx) = mpsc::channel();
+  std::thread::spawn(move || {
+    for line in BufReader::new(stdout).lines() {
+      if tx.send(line.unwrap()).is_err() {
+        break;
+      }
+    }
+  });
+  (child, rx)
+}
+
+/// Read lines until one contains `text`, returning everything read.
+fn wait_for_line(lines: &Receiver<String>, text: &str) -> String {
+  let mut seen = String::new();
+  loop {
+    let line = lines
+      .recv_timeout(Duration::from_secs(10))
+      .unwrap_or_else(|_| panic!("no line containing {:?} in:\n{}", text, seen));
+    seen.push_str(&line);
+    seen.push('\n');
+    if line.contains(text) {
+      return seen;
+    }
+  }
+}
+
+fn interrupt(child: &Child) {
+  unsafe {
+    libc::kill(child.id() as i32, libc::SIGINT);
+  }
+}
+
+#[test]
+fn restarts_only_the_process_whose_files_changed() {
+  let dir = tempfile::tempdir().unwrap();
+  std::fs::create_dir_all(dir.path().join("api/src")).unwrap();
+  std::fs::create_dir_all(dir.path().join("web")).unwrap();
+  let (mut child, lines) = start_mlti(
+    dir.path(),
+    &[
+      "-n",
+      "api,web",
+      "--cwd",
+      "api=api",
+      "--watch",
+      "api=src/**/*.rs",
+      "--watch",
+      "web=web",
+      "echo api-up; sleep 30",
+      "echo web-up; sleep 30",
+    ],
+  );
+  let output = wait_for_line(&lines, "]: api-up");
+  if !output.contains("]: web-up") {
+    wait_for_line(&lines, "]: web-up");
+  }
+  // Give the watcher a moment to register with the kernel.
+  sleep(Duration::from_millis(300));
+
+  // A burst of writes restarts the process once.
+  std::fs::write(dir.path().join("api/src/main.rs"), "fn main() {}").unwrap();
+  std::fs::write(dir.path().join("api/src/lib.rs"), "").unwrap();
+  std::fs::write(dir.path().join("api/README.md"), "").unwrap();
+  let output = wait_for_line(&lines, "]: api-up");
+  assert!(
+    output.contains("sleep 30 restarting (src/main.rs changed)"),
+    "{}",
+    output
+  );
+  assert!(!output.contains("web"), "{}", output);
+
+  sleep(Duration::from_millis(500));
+  interrupt(&child);
+  child.wait().unwrap();
+  let rest: String = lines.iter().collect::<Vec<_>>().join("\n");
+  assert!(!rest.contains("restarting"), "{}", rest);
+}
+
+#[test]
+fn starts_a_finished_process_again() {
+  let dir = tempfile::tempdir().unwrap();
+  let (mut child, lines) =
+    start_mlti(dir.path(), &["--watch", "0=*.txt", "echo built"]);
+  wait_for_line(&lines, "echo built exited with code 0");
+  sleep(Duration::from_millis(300));
+
+  std::fs::write(dir.path().join("notes.txt"), "hi").unwrap();
+  let output = wait_for_line(&lines, "]: built");
+  assert!(
+    output.contains("echo built starting (notes.txt changed)"),
+    "{}",
+    output
+  );
+
+  interrupt(&child);
+  child.wait().unwrap();
+}
+
+#[test]
+fn group_mode_prints_a_restarted_run_as_it_happens() {
+  let dir = tempfile::tempdir().unwrap();
+  let (mut child, lines) = start_mlti(
+    dir.path(),
+    &[
+      "--group",
+      "--watch",
+      "0=*.txt",
+      "echo built-$(cat notes.txt 2>/dev/null)",
+      "sleep 30",
+    ],
+  );
+  wait_for_line(&lines, "exited with code 0");
+  sleep(Duration::from_millis(300));
+
+  // The first process is done and the group has moved on to the second,
+  // which is still running; the rebuild must not wait for it.
+  std::fs::write(dir.path().join("notes.txt"), "again").unwrap();
+  wait_for_line(&lines, "]: built-again");
+
+  interrupt(&child);
+  child.wait().unwrap();
+}
+
+#[test]
+fn rejects_an_invalid_glob() {
+  let output = Command::new(env!("CARGO_BIN_EXE_mlti"))
+    .args(["--watch", "0=src/[", "echo should-not-run"])
+    .output()
+    .unwrap();
+  assert_eq!(output.status.code(), Some(1));
+  let stderr = String::from_utf8_lossy(&output.stderr);
+  assert!(
+    stderr.contains("Invalid --watch glob 'src/['"),
+    "{}",
+    stderr
+  );
+  assert!(!String::from_utf8_lossy(&output.stdout).contains("should-not-run"));
+}